@@ -27,6 +27,14 @@ impl From<std::io::Error> for Error {
     }
 }
 
+// Packet fields are read with TryFrom, which can't fail when the type is
+// already correct
+impl From<std::convert::Infallible> for Error {
+    fn from(value: std::convert::Infallible) -> Self {
+        match value {}
+    }
+}
+
 impl From<ErrorKind> for Error {
     fn from(value: ErrorKind) -> Self {
         Self { kind: value }
@@ -53,7 +61,15 @@ pub enum ErrorKind {
     InvalidOperation,
     CompressedDataTooLarge,
     InvalidNbt,
+    NbtLimitExceeded,
+    InvalidSnbt(usize),
+    InvalidRegion,
+    InvalidChunkPosition,
+    MissingExternalChunk,
+    Serde(String),
     InvalidState(i32),
+    InvalidEnumValue(i32),
+    UnsupportedPacket,
     IOError(std::io::Error),
     InvalidString(Utf8Error),
     InvalidCesu8String(cesu8::Cesu8DecodingError),
@@ -72,7 +88,15 @@ impl std::fmt::Display for ErrorKind {
             Self::InvalidOperation => write!(f, "invalid operation"),
             Self::CompressedDataTooLarge => write!(f, "compressed data too large"),
             Self::InvalidNbt => write!(f, "invalid NBT"),
+            Self::NbtLimitExceeded => write!(f, "NBT exceeds the configured limits"),
+            Self::InvalidSnbt(p) => write!(f, "invalid SNBT at position {}", p),
+            Self::InvalidRegion => write!(f, "invalid region file"),
+            Self::InvalidChunkPosition => write!(f, "chunk position outside of the region"),
+            Self::MissingExternalChunk => write!(f, "chunk is stored in an unknown external file"),
+            Self::Serde(e) => write!(f, "serde error: {}", e),
             Self::InvalidState(s) => write!(f, "invalid state: {}", s),
+            Self::InvalidEnumValue(v) => write!(f, "invalid enum value: {}", v),
+            Self::UnsupportedPacket => write!(f, "packet not supported by the protocol version"),
             Self::IOError(e) => write!(f, "I/O error: {}", e),
             Self::InvalidString(e) => write!(f, "invalid string: {}", e),
             Self::InvalidCesu8String(e) => write!(f, "invalid CESU8 string: {}", e),
@@ -1,11 +1,18 @@
+// The NbtCompound derive refers to racemus_binary, which also needs to
+// resolve within this crate
+extern crate self as racemus_binary;
+
 mod error;
 pub mod nbt;
+mod paletted_container;
 pub mod proto;
 mod reader;
+pub mod region;
 mod var_vec;
 mod writer;
 
 pub use error::*;
+pub use paletted_container::*;
 pub use reader::*;
 pub use var_vec::*;
 pub use writer::*;
@@ -0,0 +1,71 @@
+// Modules for use with #[serde(with = "...")], which store sequences as
+// typed arrays instead of lists.
+
+pub mod byte_array {
+    use serde::{de, Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer, T: AsRef<[u8]>>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(value.as_ref())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: From<Vec<u8>>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> de::Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a byte array")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(v)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                let mut seq = seq;
+                let mut result = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(v) = seq.next_element::<i8>()? {
+                    result.push(v as u8);
+                }
+                Ok(result)
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor).map(T::from)
+    }
+}
+
+macro_rules! typed_array_module {
+    ($module:ident, $name:path, $type:ty) => {
+        pub mod $module {
+            use serde::{Deserialize, Deserializer, Serializer};
+
+            pub fn serialize<S: Serializer, T: AsRef<[$type]>>(
+                value: &T,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct($name, value.as_ref())
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>, T: From<Vec<$type>>>(
+                deserializer: D,
+            ) -> Result<T, D::Error> {
+                Vec::<$type>::deserialize(deserializer).map(T::from)
+            }
+        }
+    };
+}
+
+typed_array_module!(int_array, crate::nbt::INT_ARRAY_NAME, i32);
+typed_array_module!(long_array, crate::nbt::LONG_ARRAY_NAME, i64);
@@ -0,0 +1,213 @@
+use crate::nbt::Value;
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    iter::FromIterator,
+    ops::{Index, IndexMut},
+    slice,
+    sync::Arc,
+    vec,
+};
+
+// The entries of a compound, in the order that they were inserted. Vanilla
+// files are written back out in the order they were read, so that they
+// round trip exactly. Equality ignores the order, like it does in vanilla.
+#[derive(Clone, Default)]
+pub struct Compound {
+    entries: Vec<(Arc<str>, Value)>,
+    index: HashMap<Arc<str>, usize>,
+}
+
+impl Compound {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+            index: HashMap::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> Option<&Value>
+    where
+        Arc<str>: Borrow<Q>,
+    {
+        self.index.get(key).map(|i| &self.entries[*i].1)
+    }
+
+    pub fn get_mut<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<&mut Value>
+    where
+        Arc<str>: Borrow<Q>,
+    {
+        match self.index.get(key) {
+            Some(i) => Some(&mut self.entries[*i].1),
+            None => None,
+        }
+    }
+
+    pub fn contains_key<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> bool
+    where
+        Arc<str>: Borrow<Q>,
+    {
+        self.index.contains_key(key)
+    }
+
+    // Replacing a value keeps its position
+    pub fn insert(&mut self, key: Arc<str>, value: Value) -> Option<Value> {
+        match self.index.get(&key) {
+            Some(i) => Some(std::mem::replace(&mut self.entries[*i].1, value)),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    // Keeps the order of the remaining entries
+    pub fn remove<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<Value>
+    where
+        Arc<str>: Borrow<Q>,
+    {
+        let removed = self.index.remove(key)?;
+        for i in self.index.values_mut() {
+            if *i > removed {
+                *i -= 1;
+            }
+        }
+        Some(self.entries.remove(removed).1)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.entries.iter())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Arc<str>> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
+
+impl PartialEq for Compound {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(k, v)| other.get(k.as_ref()).map_or(false, |o| v == o))
+    }
+}
+
+impl fmt::Debug for Compound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<Q: Hash + Eq + ?Sized> Index<&Q> for Compound
+where
+    Arc<str>: Borrow<Q>,
+{
+    type Output = Value;
+
+    fn index(&self, key: &Q) -> &Value {
+        self.get(key).expect("key is not in the compound")
+    }
+}
+
+impl<Q: Hash + Eq + ?Sized> IndexMut<&Q> for Compound
+where
+    Arc<str>: Borrow<Q>,
+{
+    fn index_mut(&mut self, key: &Q) -> &mut Value {
+        self.get_mut(key).expect("key is not in the compound")
+    }
+}
+
+impl FromIterator<(Arc<str>, Value)> for Compound {
+    fn from_iter<T: IntoIterator<Item = (Arc<str>, Value)>>(iter: T) -> Self {
+        let iter = iter.into_iter();
+        let mut result = Self::with_capacity(iter.size_hint().0);
+        for (key, value) in iter {
+            result.insert(key, value);
+        }
+        result
+    }
+}
+
+impl IntoIterator for Compound {
+    type Item = (Arc<str>, Value);
+    type IntoIter = vec::IntoIter<(Arc<str>, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Compound {
+    type Item = (&'a Arc<str>, &'a Value);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+pub struct Iter<'a>(slice::Iter<'a, (Arc<str>, Value)>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a Arc<str>, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn nbt_compound_order() {
+        let mut compound = Compound::new();
+        for key in ["z", "a", "m", "b"].iter() {
+            compound.insert((*key).into(), Value::Byte(0));
+        }
+        compound.insert("a".into(), Value::Byte(1));
+        assert_eq!(compound.remove("m"), Some(Value::Byte(0)));
+        assert_eq!(compound.remove("m"), None);
+        compound.insert("c".into(), Value::Byte(2));
+
+        let keys: Vec<_> = compound.keys().map(|k| k.as_ref()).collect();
+        assert_eq!(keys, vec!["z", "a", "b", "c"]);
+        assert_eq!(compound["a"], Value::Byte(1));
+        assert_eq!(compound["b"], Value::Byte(0));
+        assert_eq!(compound["c"], Value::Byte(2));
+
+        let reversed: Compound = compound.clone().into_iter().rev().collect();
+        assert_eq!(reversed, compound);
+        assert_ne!(reversed, Compound::new());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{
+    nbt::{NbtLimits, Value},
+    BinaryReader, BinaryWriter, Error,
+};
+use async_std::io::Cursor;
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use std::{
+    io::{Read, Write},
+    path::Path,
+    sync::Arc,
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+// Only the deflate method is valid
+const ZLIB_METHOD_DEFLATE: u8 = 0x08;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NbtCompression {
+    None,
+    Gzip,
+    Zlib,
+}
+
+impl NbtCompression {
+    // Uncompressed NBT always starts with a tag type, which can't be confused
+    // with either of the compressed headers.
+    pub fn detect(data: &[u8]) -> Self {
+        match data {
+            [a, b, ..] if [*a, *b] == GZIP_MAGIC => Self::Gzip,
+            [a, b, ..]
+                if a & 0x0f == ZLIB_METHOD_DEFLATE
+                    && (u16::from(*a) << 8 | u16::from(*b)) % 31 == 0 =>
+            {
+                Self::Zlib
+            }
+            _ => Self::None,
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut result = Vec::new();
+        match self {
+            Self::None => result.extend_from_slice(data),
+            Self::Gzip => {
+                GzDecoder::new(data).read_to_end(&mut result)?;
+            }
+            Self::Zlib => {
+                ZlibDecoder::new(data).read_to_end(&mut result)?;
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::None => Ok(data.to_vec()),
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            Self::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+        }
+    }
+}
+
+// Reads NBT that may be compressed, returning the compression that was used.
+// Files are trusted, and chunks can be larger than the network limits.
+pub async fn decode_nbt(data: &[u8]) -> Result<(Arc<str>, Value, NbtCompression), Error> {
+    let compression = NbtCompression::detect(data);
+    let data = compression.decompress(data)?;
+    let mut reader = BinaryReader::new(Cursor::new(data));
+    let (name, value) = reader.nbt_with_limits(NbtLimits::trusted()).await?;
+    Ok((name, value, compression))
+}
+
+pub async fn encode_nbt(
+    name: &str,
+    value: &Value,
+    compression: NbtCompression,
+) -> Result<Vec<u8>, Error> {
+    let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+    writer.nbt(name, value)?;
+    writer.flush().await?;
+    compression.compress(&writer.into_inner().into_inner())
+}
+
+pub async fn read_nbt_file(
+    path: impl AsRef<Path>,
+) -> Result<(Arc<str>, Value, NbtCompression), Error> {
+    let data = async_std::fs::read(path.as_ref()).await?;
+    decode_nbt(&data).await
+}
+
+// Vanilla compresses level.dat and playerdata with gzip. The file is
+// replaced in a single rename, so it is never left partially written.
+pub async fn write_nbt_file(
+    path: impl AsRef<Path>,
+    name: &str,
+    value: &Value,
+    compression: NbtCompression,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let data = encode_nbt(name, value, compression).await?;
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    async_std::fs::write(&temporary, data).await?;
+    async_std::fs::rename(&temporary, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    fn hello_world() -> Value {
+        crate::nbt_compound! {
+            "name" => Value::String("Bananrama".into())
+        }
+    }
+
+    #[test]
+    pub fn nbt_compression_detect() {
+        let raw = include_bytes!("test-data/nbt-hello-world.in");
+        assert_eq!(NbtCompression::detect(raw), NbtCompression::None);
+        assert_eq!(NbtCompression::detect(&[]), NbtCompression::None);
+        for compression in [NbtCompression::Gzip, NbtCompression::Zlib].iter() {
+            let data = compression.compress(raw).unwrap();
+            assert_eq!(NbtCompression::detect(&data), *compression);
+        }
+    }
+
+    #[test]
+    pub fn nbt_compression_round_trip() -> Result<(), Error> {
+        for compression in [
+            NbtCompression::None,
+            NbtCompression::Gzip,
+            NbtCompression::Zlib,
+        ]
+        .iter()
+        {
+            let data = block_on(encode_nbt("hello world", &hello_world(), *compression))?;
+            assert_eq!(
+                block_on(decode_nbt(&data))?,
+                ("hello world".into(), hello_world(), *compression)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    pub fn nbt_file_round_trip() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("racemus-nbt-{}.dat", std::process::id()));
+        block_on(write_nbt_file(
+            &path,
+            "",
+            &hello_world(),
+            NbtCompression::Gzip,
+        ))?;
+        let result = block_on(read_nbt_file(&path));
+        std::fs::remove_file(&path)?;
+        assert_eq!(result?, ("".into(), hello_world(), NbtCompression::Gzip));
+        Ok(())
+    }
+}
@@ -0,0 +1,381 @@
+use crate::{
+    nbt::{Compound, Value},
+    Error, ErrorKind,
+};
+use std::sync::Arc;
+
+// Conversions between types and NBT, usually implemented with
+// #[derive(NbtCompound)].
+pub trait ToNbt {
+    fn to_nbt(&self) -> Value;
+}
+
+pub trait FromNbt: Sized {
+    fn from_nbt(value: &Value) -> Result<Self, Error>;
+}
+
+fn required<T>(value: Option<T>) -> Result<T, Error> {
+    match value {
+        Some(r) => Ok(r),
+        None => Err(ErrorKind::InvalidNbt.into()),
+    }
+}
+
+pub fn expect_compound(value: &Value) -> Result<&Compound, Error> {
+    required(value.as_compound())
+}
+
+pub fn expect_str(value: &Value) -> Result<&str, Error> {
+    required(value.as_str())
+}
+
+pub fn required_field<T: FromNbt>(compound: &Compound, key: &str) -> Result<T, Error> {
+    T::from_nbt(required(compound.get(key))?)
+}
+
+pub fn optional_field<T: FromNbt>(compound: &Compound, key: &str) -> Result<Option<T>, Error> {
+    match compound.get(key) {
+        Some(value) => T::from_nbt(value).map(Some),
+        None => Ok(None),
+    }
+}
+
+macro_rules! impl_nbt {
+    ($type:ty, $variant:ident, $accessor:ident) => {
+        impl ToNbt for $type {
+            fn to_nbt(&self) -> Value {
+                Value::$variant((*self).into())
+            }
+        }
+
+        impl FromNbt for $type {
+            fn from_nbt(value: &Value) -> Result<Self, Error> {
+                required(value.$accessor())
+            }
+        }
+    };
+}
+
+impl_nbt!(i8, Byte, as_i8);
+impl_nbt!(i16, Short, as_i16);
+impl_nbt!(i32, Int, as_i32);
+impl_nbt!(i64, Long, as_i64);
+impl_nbt!(f32, Float, as_f32);
+impl_nbt!(f64, Double, as_f64);
+impl_nbt!(bool, Byte, as_bool);
+
+impl ToNbt for str {
+    fn to_nbt(&self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl ToNbt for String {
+    fn to_nbt(&self) -> Value {
+        Value::String(self.as_str().into())
+    }
+}
+
+impl FromNbt for String {
+    fn from_nbt(value: &Value) -> Result<Self, Error> {
+        expect_str(value).map(String::from)
+    }
+}
+
+impl ToNbt for Arc<str> {
+    fn to_nbt(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl FromNbt for Arc<str> {
+    fn from_nbt(value: &Value) -> Result<Self, Error> {
+        match value {
+            Value::String(r) => Ok(r.clone()),
+            _ => Err(ErrorKind::InvalidNbt.into()),
+        }
+    }
+}
+
+// Sequences are stored as lists, these are stored as typed arrays
+macro_rules! impl_nbt_array {
+    ($type:ty, $variant:ident) => {
+        impl ToNbt for Arc<[$type]> {
+            fn to_nbt(&self) -> Value {
+                Value::$variant(self.clone())
+            }
+        }
+
+        impl FromNbt for Arc<[$type]> {
+            fn from_nbt(value: &Value) -> Result<Self, Error> {
+                match value {
+                    Value::$variant(r) => Ok(r.clone()),
+                    _ => Err(ErrorKind::InvalidNbt.into()),
+                }
+            }
+        }
+    };
+}
+
+impl_nbt_array!(u8, ByteArray);
+impl_nbt_array!(i32, IntArray);
+impl_nbt_array!(i64, LongArray);
+
+impl<T: ToNbt> ToNbt for Vec<T> {
+    fn to_nbt(&self) -> Value {
+        Value::List(self.iter().map(ToNbt::to_nbt).collect::<Vec<_>>().into())
+    }
+}
+
+impl<T: FromNbt> FromNbt for Vec<T> {
+    fn from_nbt(value: &Value) -> Result<Self, Error> {
+        required(value.as_list())?.iter().map(T::from_nbt).collect()
+    }
+}
+
+impl ToNbt for Compound {
+    fn to_nbt(&self) -> Value {
+        Value::Compound(self.clone())
+    }
+}
+
+impl FromNbt for Compound {
+    fn from_nbt(value: &Value) -> Result<Self, Error> {
+        expect_compound(value).map(Clone::clone)
+    }
+}
+
+impl ToNbt for Value {
+    fn to_nbt(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl FromNbt for Value {
+    fn from_nbt(value: &Value) -> Result<Self, Error> {
+        Ok(value.clone())
+    }
+}
+
+impl<T: ToNbt + ?Sized> ToNbt for &T {
+    fn to_nbt(&self) -> Value {
+        (**self).to_nbt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::NbtCompound;
+
+    #[derive(Debug, PartialEq, NbtCompound)]
+    #[nbt(rename_all = "PascalCase")]
+    struct Level {
+        level_name: String,
+        random_seed: i64,
+        hardcore: bool,
+        #[nbt(rename = "version")]
+        data_version: i32,
+        border_size: Option<f64>,
+        #[nbt(default)]
+        game_rules: Vec<GameRule>,
+        biomes: Arc<[i32]>,
+        spawn: Spawn,
+        block_entities: Vec<BlockEntity>,
+        difficulty: Difficulty,
+    }
+
+    #[derive(Debug, PartialEq, NbtCompound)]
+    struct GameRule(String);
+
+    #[derive(Debug, PartialEq, NbtCompound)]
+    struct Spawn {
+        x: i32,
+        y: i32,
+        z: i32,
+    }
+
+    #[derive(Debug, PartialEq, NbtCompound)]
+    #[nbt(tag = "id")]
+    enum BlockEntity {
+        #[nbt(rename = "minecraft:sign")]
+        Sign {
+            #[nbt(rename = "Text1")]
+            text: String,
+        },
+        #[nbt(rename = "minecraft:bed")]
+        Bed,
+    }
+
+    #[derive(Debug, PartialEq, NbtCompound)]
+    #[nbt(rename_all = "snake_case")]
+    enum Difficulty {
+        Peaceful,
+        VeryHard,
+    }
+
+    // The field names are also used for locals by the derive
+    #[derive(Debug, PartialEq, NbtCompound)]
+    struct Shadowed {
+        compound: i32,
+        value: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, NbtCompound)]
+    #[nbt(tag = "id")]
+    enum ShadowedVariant {
+        Item { compound: i32, value: String },
+    }
+
+    fn level() -> Level {
+        Level {
+            level_name: "world".into(),
+            random_seed: -1,
+            hardcore: false,
+            data_version: 2230,
+            border_size: None,
+            game_rules: vec![GameRule("keepInventory".into())],
+            biomes: vec![1, 2][..].into(),
+            spawn: Spawn { x: 1, y: 64, z: -1 },
+            block_entities: vec![
+                BlockEntity::Sign {
+                    text: "hello".into(),
+                },
+                BlockEntity::Bed,
+            ],
+            difficulty: Difficulty::VeryHard,
+        }
+    }
+
+    #[test]
+    pub fn nbt_derive_to_nbt() {
+        let value = level().to_nbt();
+        let keys: Vec<_> = value
+            .as_compound()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_ref())
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "LevelName",
+                "RandomSeed",
+                "Hardcore",
+                "version",
+                "GameRules",
+                "Biomes",
+                "Spawn",
+                "BlockEntities",
+                "Difficulty"
+            ]
+        );
+        assert_eq!(value["Hardcore"], Value::Byte(0));
+        assert_eq!(
+            value["GameRules"],
+            crate::nbt_list![Value::String("keepInventory".into())]
+        );
+        assert_eq!(value["Biomes"], crate::nbt_int_array![1, 2]);
+        assert_eq!(
+            value["Spawn"],
+            crate::nbt_compound! {
+                "x" => Value::Int(1),
+                "y" => Value::Int(64),
+                "z" => Value::Int(-1)
+            }
+        );
+        assert_eq!(
+            value["BlockEntities"],
+            crate::nbt_list![
+                crate::nbt_compound! {
+                    "id" => Value::String("minecraft:sign".into()),
+                    "Text1" => Value::String("hello".into())
+                },
+                crate::nbt_compound! {
+                    "id" => Value::String("minecraft:bed".into())
+                }
+            ]
+        );
+        assert_eq!(value["Difficulty"], Value::String("very_hard".into()));
+    }
+
+    #[test]
+    pub fn nbt_derive_round_trip() -> Result<(), Error> {
+        let level = level();
+        assert_eq!(Level::from_nbt(&level.to_nbt())?, level);
+
+        let level = Level {
+            border_size: Some(1000.0),
+            ..level
+        };
+        assert_eq!(Level::from_nbt(&level.to_nbt())?, level);
+
+        // Missing default fields are empty
+        let mut value = level.to_nbt();
+        value.as_compound_mut().unwrap().remove("GameRules");
+        assert_eq!(Level::from_nbt(&value)?.game_rules, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    pub fn nbt_derive_shadowed() -> Result<(), Error> {
+        let shadowed = Shadowed {
+            compound: 1,
+            value: Some("a".into()),
+        };
+        assert_eq!(
+            shadowed.to_nbt(),
+            crate::nbt_compound! {
+                "compound" => Value::Int(1),
+                "value" => Value::String("a".into())
+            }
+        );
+        assert_eq!(Shadowed::from_nbt(&shadowed.to_nbt())?, shadowed);
+
+        let variant = ShadowedVariant::Item {
+            compound: 2,
+            value: "b".into(),
+        };
+        assert_eq!(
+            variant.to_nbt(),
+            crate::nbt_compound! {
+                "id" => Value::String("Item".into()),
+                "compound" => Value::Int(2),
+                "value" => Value::String("b".into())
+            }
+        );
+        assert_eq!(ShadowedVariant::from_nbt(&variant.to_nbt())?, variant);
+        Ok(())
+    }
+
+    #[test]
+    pub fn nbt_derive_invalid() -> Result<(), Error> {
+        let invalid = [
+            ("RandomSeed", None),
+            ("Hardcore", Some(Value::Int(1))),
+            ("Difficulty", Some(Value::String("hard".into()))),
+            (
+                "BlockEntities",
+                Some(crate::nbt_list![crate::nbt_compound! {
+                    "id" => Value::String("minecraft:chest".into())
+                }]),
+            ),
+        ];
+        for (key, replacement) in invalid.iter() {
+            let mut value = level().to_nbt();
+            let compound = value.as_compound_mut().unwrap();
+            match replacement {
+                Some(replacement) => compound.insert((*key).into(), replacement.clone()),
+                None => compound.remove(*key),
+            };
+            match Level::from_nbt(&value) {
+                Ok(_) => panic!("expected {} to be invalid", key),
+                Err(e) => match e.kind() {
+                    ErrorKind::InvalidNbt => {}
+                    _ => return Err(e),
+                },
+            }
+        }
+        Ok(())
+    }
+}
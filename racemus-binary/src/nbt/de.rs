@@ -0,0 +1,410 @@
+use crate::{nbt::Value, Error, ErrorKind};
+use serde::de::{
+    self, value::StringDeserializer, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor,
+};
+use std::{fmt::Display, sync::Arc, vec};
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        ErrorKind::Serde(msg.to_string()).into()
+    }
+}
+
+// Converts NBT into a value. Bytes can be read as booleans, and typed arrays
+// can be read as sequences.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, Error> {
+    T::deserialize(value)
+}
+
+fn invalid_type<T>(value: &Value, expected: &dyn de::Expected) -> Result<T, Error> {
+    let unexpected = match value {
+        Value::Byte(v) => de::Unexpected::Signed((*v).into()),
+        Value::Short(v) => de::Unexpected::Signed((*v).into()),
+        Value::Int(v) => de::Unexpected::Signed((*v).into()),
+        Value::Long(v) => de::Unexpected::Signed(*v),
+        Value::Float(v) => de::Unexpected::Float((*v).into()),
+        Value::Double(v) => de::Unexpected::Float(*v),
+        Value::String(v) => de::Unexpected::Str(v),
+        Value::ByteArray(v) => de::Unexpected::Bytes(v),
+        Value::List(_) | Value::IntArray(_) | Value::LongArray(_) => de::Unexpected::Seq,
+        Value::Compound(_) => de::Unexpected::Map,
+    };
+    Err(de::Error::invalid_type(unexpected, expected))
+}
+
+// Unsigned values are stored in the signed type of the same size
+macro_rules! deserialize_unsigned {
+    ($name:ident, $visit:ident, $variant:ident, $type:ty) => {
+        fn $name<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self {
+                Value::$variant(v) => visitor.$visit(v as $type),
+                value => value.deserialize_any(visitor),
+            }
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Byte(v) => visitor.visit_i8(v),
+            Value::Short(v) => visitor.visit_i16(v),
+            Value::Int(v) => visitor.visit_i32(v),
+            Value::Long(v) => visitor.visit_i64(v),
+            Value::Float(v) => visitor.visit_f32(v),
+            Value::Double(v) => visitor.visit_f64(v),
+            Value::String(v) => visitor.visit_str(&v),
+            Value::ByteArray(v) => visitor.visit_seq(SeqAccess::new(
+                v.iter().map(|b| Value::Byte(*b as i8)).collect(),
+            )),
+            Value::IntArray(v) => {
+                visitor.visit_seq(SeqAccess::new(v.iter().map(|i| Value::Int(*i)).collect()))
+            }
+            Value::LongArray(v) => {
+                visitor.visit_seq(SeqAccess::new(v.iter().map(|l| Value::Long(*l)).collect()))
+            }
+            Value::List(v) => visitor.visit_seq(SeqAccess::new(v.to_vec())),
+            Value::Compound(v) => visitor.visit_map(MapAccess {
+                iter: v.into_iter(),
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Byte(v) => visitor.visit_bool(v != 0),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_unsigned!(deserialize_u8, visit_u8, Byte, u8);
+    deserialize_unsigned!(deserialize_u16, visit_u16, Short, u16);
+    deserialize_unsigned!(deserialize_u32, visit_u32, Int, u32);
+    deserialize_unsigned!(deserialize_u64, visit_u64, Long, u64);
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::ByteArray(v) => visitor.visit_bytes(&v),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    // Absent fields are None, so any value that is present is Some
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Compound(ref v) if v.is_empty() => visitor.visit_unit(),
+            value => invalid_type(&value, &visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Value::String(variant) => visitor.visit_enum(EnumAccess {
+                variant,
+                value: None,
+            }),
+            Value::Compound(v) if v.len() == 1 => {
+                let (variant, value) = v.into_iter().next().unwrap();
+                visitor.visit_enum(EnumAccess {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            value => invalid_type(&value, &visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct SeqAccess {
+    iter: std::vec::IntoIter<Value>,
+}
+
+impl SeqAccess {
+    fn new(values: Vec<Value>) -> Self {
+        Self {
+            iter: values.into_iter(),
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.iter.next() {
+            Some(value) => seed.deserialize(value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapAccess {
+    iter: vec::IntoIter<(Arc<str>, Value)>,
+    value: Option<Value>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                let key: StringDeserializer<Error> = key.to_string().into_deserializer();
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EnumAccess {
+    variant: Arc<str>,
+    value: Option<Value>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = Error;
+    type Variant = VariantAccess;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let variant: StringDeserializer<Error> = self.variant.to_string().into_deserializer();
+        let variant = seed.deserialize(variant)?;
+        Ok((variant, VariantAccess(self.value)))
+    }
+}
+
+struct VariantAccess(Option<Value>);
+
+impl<'de> de::VariantAccess<'de> for VariantAccess {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.0 {
+            None => Ok(()),
+            Some(value) => invalid_type(&value, &"a unit variant"),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        match self.0 {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"a newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Some(value) => de::Deserializer::deserialize_seq(value, visitor),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"a tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            Some(value) => de::Deserializer::deserialize_map(value, visitor),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"a struct variant",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::to_value;
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        Plain,
+        Named(String),
+        Sized { width: i32, height: i32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Section {
+        #[serde(rename = "Y")]
+        y: u8,
+        #[serde(with = "crate::nbt::long_array")]
+        states: Vec<i64>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Level {
+        name: String,
+        hardcore: bool,
+        seed: i64,
+        spawn: (i32, i32, i32),
+        #[serde(with = "crate::nbt::int_array")]
+        biomes: Vec<i32>,
+        #[serde(with = "crate::nbt::byte_array")]
+        light: Vec<u8>,
+        sections: Vec<Section>,
+        kind: Kind,
+        kinds: Vec<Kind>,
+        #[serde(default)]
+        generator: Option<String>,
+    }
+
+    fn level() -> Level {
+        Level {
+            name: "world".into(),
+            hardcore: true,
+            seed: -42,
+            spawn: (1, 64, -1),
+            biomes: vec![1, 2, 3],
+            light: vec![0, 255],
+            sections: vec![Section {
+                y: 200,
+                states: vec![i64::MAX],
+            }],
+            kind: Kind::Plain,
+            kinds: vec![
+                Kind::Named("a".into()),
+                Kind::Sized {
+                    width: 1,
+                    height: 2,
+                },
+            ],
+            generator: None,
+        }
+    }
+
+    #[test]
+    pub fn nbt_serde_to_value() -> Result<(), Error> {
+        let value = to_value(&level())?;
+        let compound = match &value {
+            Value::Compound(v) => v,
+            _ => panic!("expected a compound"),
+        };
+        assert_eq!(compound["hardcore"], Value::Byte(1));
+        assert_eq!(compound["biomes"], crate::nbt_int_array![1, 2, 3]);
+        assert_eq!(compound["light"], crate::nbt_byte_array![0, 255]);
+        assert_eq!(
+            compound["sections"],
+            crate::nbt_list![crate::nbt_compound! {
+                "Y" => Value::Byte(-56),
+                "states" => crate::nbt_long_array![i64::MAX]
+            }]
+        );
+        assert_eq!(
+            compound["spawn"],
+            crate::nbt_list![Value::Int(1), Value::Int(64), Value::Int(-1)]
+        );
+        assert_eq!(compound["kind"], Value::String("Plain".into()));
+        assert!(!compound.contains_key("generator"));
+        Ok(())
+    }
+
+    #[test]
+    pub fn nbt_serde_round_trip() -> Result<(), Error> {
+        let level = level();
+        assert_eq!(from_value::<Level>(to_value(&level)?)?, level);
+
+        let level = Level {
+            generator: Some("flat".into()),
+            ..level
+        };
+        assert_eq!(from_value::<Level>(to_value(&level)?)?, level);
+        Ok(())
+    }
+
+    #[test]
+    pub fn nbt_serde_mixed_list() -> Result<(), Error> {
+        match to_value(&vec![Kind::Plain, Kind::Named("a".into())]) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => match e.kind() {
+                ErrorKind::Serde(_) => Ok(()),
+                _ => Err(e),
+            },
+        }
+    }
+
+    #[test]
+    pub fn nbt_serde_invalid_type() -> Result<(), Error> {
+        match from_value::<Section>(crate::nbt_compound! {
+            "Y" => Value::String("1".into()),
+            "states" => crate::nbt_long_array![]
+        }) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => match e.kind() {
+                ErrorKind::Serde(_) => Ok(()),
+                _ => Err(e),
+            },
+        }
+    }
+}
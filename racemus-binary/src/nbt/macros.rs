@@ -3,7 +3,7 @@ macro_rules! nbt_compound(
     { $($key:expr => $value:expr),* } => {
         {
             #[allow(unused_mut)]
-            let mut m = ::std::collections::HashMap::new();
+            let mut m = $crate::nbt::Compound::new();
             $(
                 m.insert($key[..].into(), $value);
             )*
@@ -1,9 +1,26 @@
+mod arrays;
+mod compound;
+mod compression;
+mod convert;
+mod de;
 mod macros;
 mod reader;
+mod ser;
+mod snbt;
 mod value;
+mod view;
 mod writer;
 
+pub use arrays::*;
+pub use compound::*;
+pub use compression::*;
+pub use convert::*;
+pub use de::*;
 pub use macros::*;
 pub use reader::*;
+pub use ser::*;
 pub use value::*;
+pub use view::*;
 pub use writer::*;
+
+pub use racemus_nbt_derive::NbtCompound;
@@ -1,9 +1,89 @@
-use crate::{nbt::Value, BinaryReader, Error, ErrorKind};
+use crate::{
+    nbt::{Compound, Value},
+    BinaryReader, Error, ErrorKind,
+};
 use async_std::io::Read;
-use std::{collections::HashMap, marker::Unpin, sync::Arc};
+use std::{marker::Unpin, mem::size_of, sync::Arc};
+
+// Limits that are enforced while reading NBT, so that untrusted input (such
+// as item data sent by clients) can't exhaust memory. The defaults match the
+// limits that vanilla uses for the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NbtLimits {
+    // The number of compounds and lists that can be nested
+    pub max_depth: usize,
+    // An estimate of the memory allocated for the values and names
+    pub max_bytes: usize,
+    // The number of entries in a single list or array
+    pub max_length: usize,
+}
+
+impl NbtLimits {
+    // For data that is trusted, such as world files. Nesting is still
+    // limited because dropping a value is recursive, and the size is limited
+    // so that a corrupt length can't exhaust memory.
+    pub fn trusted() -> Self {
+        Self {
+            max_depth: 512,
+            max_bytes: 256 * 1024 * 1024,
+            max_length: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for NbtLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 512,
+            max_bytes: 2 * 1024 * 1024,
+            max_length: 512 * 1024,
+        }
+    }
+}
+
+const MAX_PREALLOCATED_BYTES: usize = 64 * 1024;
+
+struct Accounter {
+    limits: NbtLimits,
+    bytes: usize,
+}
+
+impl Accounter {
+    fn allocate(&mut self, bytes: usize) -> Result<(), Error> {
+        self.bytes = self.bytes.saturating_add(bytes);
+        if self.bytes > self.limits.max_bytes {
+            return Err(ErrorKind::NbtLimitExceeded.into());
+        }
+        Ok(())
+    }
+
+    fn length(&mut self, length: usize, element_size: usize) -> Result<(), Error> {
+        if length > self.limits.max_length {
+            return Err(ErrorKind::NbtLimitExceeded.into());
+        }
+        self.allocate(length.saturating_mul(element_size))
+    }
+
+    fn depth(&self, stack: &[StackState]) -> Result<(), Error> {
+        if stack.len() >= self.limits.max_depth {
+            return Err(ErrorKind::NbtLimitExceeded.into());
+        }
+        Ok(())
+    }
+
+    // Avoids allocating up front for lengths that can't be satisfied, or
+    // that the input is too short to contain. Larger values grow as they are
+    // read.
+    fn capacity(&self, length: usize, element_size: usize) -> usize {
+        let remaining = self.limits.max_bytes.saturating_sub(self.bytes);
+        length
+            .min(remaining / element_size)
+            .min(MAX_PREALLOCATED_BYTES / element_size)
+    }
+}
 
 enum StackState {
-    Compound(Arc<str>, HashMap<Arc<str>, Value>),
+    Compound(Arc<str>, Compound),
     List(Arc<str>, Vec<Value>, u8, usize),
 }
 
@@ -94,22 +174,30 @@ impl<R: Read + Unpin> BinaryReader<R> {
     }
 
     pub async fn nbt(&mut self) -> Result<(Arc<str>, Value), Error> {
+        self.nbt_with_limits(NbtLimits::default()).await
+    }
+
+    pub async fn nbt_with_limits(&mut self, limits: NbtLimits) -> Result<(Arc<str>, Value), Error> {
         let mut stack = Vec::with_capacity(2);
+        let mut accounter = Accounter { limits, bytes: 0 };
 
         loop {
             let (type_id, name) = self.tag(&mut stack).await?;
+            accounter.allocate(size_of::<Value>() + name.len())?;
 
             let mut result = match type_id {
                 0x09 => {
                     let type_id = self.fix_u8().await?;
                     let size = self.length_fix_i32().await?;
+                    accounter.length(size, 0)?;
                     if size == 0 {
                         let arr: Vec<Value> = Vec::with_capacity(0);
                         Some((name, Value::List(arr[..].into())))
                     } else {
+                        accounter.depth(&stack)?;
                         stack.push(StackState::List(
                             name,
-                            Vec::with_capacity(size),
+                            Vec::with_capacity(accounter.capacity(size, size_of::<Value>())),
                             type_id,
                             size,
                         ));
@@ -117,7 +205,8 @@ impl<R: Read + Unpin> BinaryReader<R> {
                     }
                 }
                 0x0a => {
-                    stack.push(StackState::Compound(name, HashMap::new()));
+                    accounter.depth(&stack)?;
+                    stack.push(StackState::Compound(name, Compound::new()));
                     None
                 }
                 0x00 => {
@@ -138,14 +227,20 @@ impl<R: Read + Unpin> BinaryReader<R> {
                 0x06 => Some((name, Value::Double(self.fix_f64().await?))),
                 0x07 => {
                     let count = self.length_fix_i32().await?;
+                    accounter.length(count, 1)?;
                     let data = Value::ByteArray(self.data(count).await?.into());
                     self.consume(count);
                     Some((name, data))
                 }
-                0x08 => Some((name, Value::String(self.str_fix_i16().await?))),
+                0x08 => {
+                    let value = self.str_fix_i16().await?;
+                    accounter.allocate(value.len())?;
+                    Some((name, Value::String(value)))
+                }
                 0x0b => {
                     let len = self.length_fix_i32().await?;
-                    let mut vec = Vec::with_capacity(len);
+                    accounter.length(len, size_of::<i32>())?;
+                    let mut vec = Vec::with_capacity(accounter.capacity(len, size_of::<i32>()));
                     for _ in 0..len {
                         vec.push(self.fix_i32().await?)
                     }
@@ -153,7 +248,8 @@ impl<R: Read + Unpin> BinaryReader<R> {
                 }
                 0x0c => {
                     let len = self.length_fix_i32().await?;
-                    let mut vec = Vec::with_capacity(len);
+                    accounter.length(len, size_of::<i64>())?;
+                    let mut vec = Vec::with_capacity(accounter.capacity(len, size_of::<i64>()));
                     for _ in 0..len {
                         vec.push(self.fix_i64().await?)
                     }
@@ -269,4 +365,97 @@ mod tests {
             r.nbt(), ("larray"[..].into(), crate::nbt_long_array![1, 2, 3, 4]);
         };
     }
+
+    fn nested_compounds(depth: usize) -> Vec<u8> {
+        let mut data = vec![0x0a, 0x00, 0x00];
+        for _ in 0..depth {
+            data.extend_from_slice(&[0x0a, 0x00, 0x01, b'a']);
+        }
+        data.resize(data.len() + depth + 1, 0x00);
+        data
+    }
+
+    fn expect_limit(result: Result<(Arc<str>, Value), Error>) -> Result<(), Error> {
+        match result {
+            Ok(_) => panic!("expected the limits to be exceeded"),
+            Err(e) => match e.kind() {
+                ErrorKind::NbtLimitExceeded => Ok(()),
+                _ => Err(e),
+            },
+        }
+    }
+
+    #[test]
+    pub fn binary_reader_nbt_limits_depth() -> Result<(), Error> {
+        let mut r = make_reader(&nested_compounds(511));
+        block_on(r.nbt())?;
+
+        let mut r = make_reader(&nested_compounds(512));
+        expect_limit(block_on(r.nbt()))?;
+
+        let mut r = make_reader(&nested_compounds(100_000));
+        expect_limit(block_on(r.nbt()))
+    }
+
+    #[test]
+    pub fn binary_reader_nbt_limits_length() -> Result<(), Error> {
+        // The lengths are checked before any elements are read
+        for type_id in [0x07, 0x0b, 0x0c].iter() {
+            let mut r = make_reader(&[*type_id, 0x00, 0x00, 0x7f, 0xff, 0xff, 0xff]);
+            expect_limit(block_on(r.nbt()))?;
+        }
+
+        let mut r = make_reader(&[0x09, 0x00, 0x00, 0x01, 0x7f, 0xff, 0xff, 0xff]);
+        expect_limit(block_on(r.nbt()))
+    }
+
+    #[test]
+    pub fn binary_reader_nbt_limits_bytes() -> Result<(), Error> {
+        // A list of 64 strings that are 1KiB each
+        let mut data = vec![0x09, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x40];
+        for _ in 0..64 {
+            data.extend_from_slice(&[0x04, 0x00]);
+            data.extend_from_slice(&[b'a'; 1024]);
+        }
+
+        let mut r = make_reader(&data);
+        block_on(r.nbt())?;
+
+        let limits = NbtLimits {
+            max_bytes: 32 * 1024,
+            ..NbtLimits::default()
+        };
+        let mut r = make_reader(&data);
+        expect_limit(block_on(r.nbt_with_limits(limits)))?;
+
+        let mut r = make_reader(&data);
+        block_on(r.nbt_with_limits(NbtLimits::trusted()))?;
+        Ok(())
+    }
+
+    #[test]
+    pub fn binary_reader_nbt_limits_trusted() -> Result<(), Error> {
+        for type_id in [0x07, 0x0b, 0x0c].iter() {
+            let mut r = make_reader(&[*type_id, 0x00, 0x00, 0x7f, 0xff, 0xff, 0xff]);
+            expect_limit(block_on(r.nbt_with_limits(NbtLimits::trusted())))?;
+        }
+
+        // Lengths within the limits aren't allocated before the data is read
+        let inputs: [&[u8]; 3] = [
+            &[0x09, 0x00, 0x00, 0x03, 0x00, 0x10, 0x00, 0x00],
+            &[0x0b, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00],
+            &[0x0c, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00],
+        ];
+        for input in inputs.iter() {
+            let mut r = make_reader(input);
+            match block_on(r.nbt_with_limits(NbtLimits::trusted())) {
+                Err(e) => match e.kind() {
+                    ErrorKind::EndOfData => {}
+                    _ => return Err(e),
+                },
+                Ok(_) => panic!("expected the data to end"),
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,392 @@
+use crate::{
+    nbt::{Compound, Value},
+    Error, ErrorKind,
+};
+use serde::ser::{self, Serialize};
+use std::{fmt::Display, sync::Arc};
+
+// Newtype structs with these names are serialized as typed arrays instead of
+// lists, see the int_array and long_array modules.
+pub(crate) const INT_ARRAY_NAME: &str = "$racemus_nbt_int_array";
+pub(crate) const LONG_ARRAY_NAME: &str = "$racemus_nbt_long_array";
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        ErrorKind::Serde(msg.to_string()).into()
+    }
+}
+
+// Converts a value into NBT. Structs and maps become compounds, sequences
+// become lists, booleans become bytes and None fields are left out.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, Error> {
+    required(value.serialize(Serializer)?)
+}
+
+fn required(value: Option<Value>) -> Result<Value, Error> {
+    match value {
+        Some(r) => Ok(r),
+        None => Err(ErrorKind::Serde("None can only be used for fields".into()).into()),
+    }
+}
+
+fn type_error<T>(expected: &str) -> Result<T, Error> {
+    Err(ErrorKind::Serde(format!("expected {}", expected)).into())
+}
+
+// None is represented by the absence of a value, so that it can be skipped
+// in compounds.
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeVariant<SerializeList>;
+    type SerializeMap = SerializeCompound;
+    type SerializeStruct = SerializeCompound;
+    type SerializeStructVariant = SerializeVariant<SerializeCompound>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Byte(v as i8)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Long(v)))
+    }
+
+    // NBT has no unsigned types, so the bits are stored in the signed type
+    // of the same size.
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Byte(v as i8)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Short(v as i16)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Int(v as i32)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Long(v as i64)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::String(v.to_string().into())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::String(v.into())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::ByteArray(v.into())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Compound(Compound::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        let value = value.serialize(self)?;
+        match (name, value) {
+            (INT_ARRAY_NAME, Some(Value::List(list))) => {
+                let mut result = Vec::with_capacity(list.len());
+                for value in list.iter() {
+                    match value {
+                        Value::Int(v) => result.push(*v),
+                        _ => return type_error("an int"),
+                    }
+                }
+                Ok(Some(Value::IntArray(result.into())))
+            }
+            (LONG_ARRAY_NAME, Some(Value::List(list))) => {
+                let mut result = Vec::with_capacity(list.len());
+                for value in list.iter() {
+                    match value {
+                        Value::Long(v) => result.push(*v),
+                        _ => return type_error("a long"),
+                    }
+                }
+                Ok(Some(Value::LongArray(result.into())))
+            }
+            (INT_ARRAY_NAME, _) | (LONG_ARRAY_NAME, _) => type_error("a sequence"),
+            (_, value) => Ok(value),
+        }
+    }
+
+    // Enums are externally tagged, like most serde formats
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        let mut result = Compound::with_capacity(1);
+        if let Some(value) = value.serialize(self)? {
+            result.insert(variant.into(), value);
+        }
+        Ok(Some(Value::Compound(result)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Ok(SerializeList(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Ok(SerializeVariant(variant, self.serialize_seq(Some(len))?))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(SerializeCompound {
+            values: Compound::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Ok(SerializeVariant(variant, self.serialize_map(Some(len))?))
+    }
+}
+
+struct SerializeList(Vec<Value>);
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let value = required(value.serialize(Serializer)?)?;
+        // Lists can only contain a single type
+        if let Some(first) = self.0.first() {
+            if std::mem::discriminant(first) != std::mem::discriminant(&value) {
+                return type_error("list elements of the same type");
+            }
+        }
+        self.0.push(value);
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        Value::List(self.0.into())
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(self.finish()))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(self.finish()))
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(self.finish()))
+    }
+}
+
+struct SerializeCompound {
+    values: Compound,
+    key: Option<Arc<str>>,
+}
+
+impl SerializeCompound {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: Arc<str>, value: &T) -> Result<(), Error> {
+        if let Some(value) = value.serialize(Serializer)? {
+            self.values.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        Value::Compound(self.values)
+    }
+}
+
+impl ser::SerializeMap for SerializeCompound {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        match key.serialize(Serializer)? {
+            Some(Value::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => type_error("a string key"),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        match self.key.take() {
+            Some(key) => self.insert(key, value),
+            None => type_error("a key before the value"),
+        }
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(self.finish()))
+    }
+}
+
+impl ser::SerializeStruct for SerializeCompound {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key.into(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(self.finish()))
+    }
+}
+
+struct SerializeVariant<T>(&'static str, T);
+
+impl<T> SerializeVariant<T> {
+    fn finish(variant: &'static str, value: Value) -> Option<Value> {
+        let mut result = Compound::with_capacity(1);
+        result.insert(variant.into(), value);
+        Some(Value::Compound(result))
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeList> {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.1.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Self::finish(self.0, self.1.finish()))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeCompound> {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.1.insert(key.into(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Self::finish(self.0, self.1.finish()))
+    }
+}
@@ -0,0 +1,550 @@
+use crate::{
+    nbt::{Compound, Value},
+    Error, ErrorKind,
+};
+use std::{fmt, str::FromStr, sync::Arc};
+
+// Deeply nested input would otherwise overflow the stack
+const MAX_DEPTH: usize = 512;
+const INDENT: &str = "    ";
+
+fn is_unquoted(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '+'
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    // Like vanilla, single quotes are used to avoid escaping double quotes
+    let quote = if value.contains('"') { '\'' } else { '"' };
+    write!(f, "{}", quote)?;
+    for c in value.chars() {
+        if c == quote || c == '\\' {
+            write!(f, "\\")?;
+        }
+        write!(f, "{}", c)?;
+    }
+    write!(f, "{}", quote)
+}
+
+fn write_key(f: &mut fmt::Formatter<'_>, key: &str) -> fmt::Result {
+    if !key.is_empty() && key.chars().all(is_unquoted) {
+        write!(f, "{}", key)
+    } else {
+        write_str(f, key)
+    }
+}
+
+fn write_indent(f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+    for _ in 0..depth {
+        write!(f, "{}", INDENT)?;
+    }
+    Ok(())
+}
+
+fn write_array<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    prefix: &str,
+    suffix: &str,
+    values: impl Iterator<Item = T>,
+) -> fmt::Result {
+    let separator = if f.alternate() { ", " } else { "," };
+    write!(f, "[{};", prefix)?;
+    for (i, value) in values.enumerate() {
+        if i != 0 {
+            write!(f, "{}", separator)?;
+        } else if f.alternate() {
+            write!(f, " ")?;
+        }
+        write!(f, "{}{}", value, suffix)?;
+    }
+    write!(f, "]")
+}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: &Value, depth: usize) -> fmt::Result {
+    let pretty = f.alternate();
+    match value {
+        Value::Byte(v) => write!(f, "{}b", v),
+        Value::Short(v) => write!(f, "{}s", v),
+        Value::Int(v) => write!(f, "{}", v),
+        Value::Long(v) => write!(f, "{}L", v),
+        // Debug always includes a decimal point or exponent
+        Value::Float(v) => write!(f, "{:?}f", v),
+        Value::Double(v) => write!(f, "{:?}d", v),
+        Value::String(v) => write_str(f, v),
+        Value::ByteArray(v) => write_array(f, "B", "B", v.iter().map(|b| *b as i8)),
+        Value::IntArray(v) => write_array(f, "I", "", v.iter()),
+        Value::LongArray(v) => write_array(f, "L", "L", v.iter()),
+        Value::List(v) => {
+            // Lists of primitives stay on a single line
+            let multiline = pretty
+                && v.iter()
+                    .any(|v| matches!(v, Value::List(_) | Value::Compound(_)));
+            write!(f, "[")?;
+            for (i, value) in v.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ",")?;
+                    if pretty && !multiline {
+                        write!(f, " ")?;
+                    }
+                }
+                if multiline {
+                    writeln!(f)?;
+                    write_indent(f, depth + 1)?;
+                }
+                write_value(f, value, depth + 1)?;
+            }
+            if multiline {
+                writeln!(f)?;
+                write_indent(f, depth)?;
+            }
+            write!(f, "]")
+        }
+        Value::Compound(v) => {
+            write!(f, "{{")?;
+            for (i, (key, value)) in v.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ",")?;
+                }
+                if pretty {
+                    writeln!(f)?;
+                    write_indent(f, depth + 1)?;
+                }
+                write_key(f, key)?;
+                write!(f, "{}", if pretty { ": " } else { ":" })?;
+                write_value(f, value, depth + 1)?;
+            }
+            if pretty && !v.is_empty() {
+                writeln!(f)?;
+                write_indent(f, depth)?;
+            }
+            write!(f, "}}")
+        }
+    }
+}
+
+// Formats the value as SNBT. The alternate flag ({:#}) adds line breaks and
+// indentation.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_value(f, self, 0)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self) -> Result<T, Error> {
+        Err(ErrorKind::InvalidSnbt(self.position).into())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error()
+        }
+    }
+
+    // Consumes the separator between elements, returning true if there is
+    // another element.
+    fn separator(&mut self, end: char) -> Result<bool, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(',') => {
+                self.position += 1;
+                self.skip_whitespace();
+                Ok(self.peek() != Some(end))
+            }
+            Some(c) if c == end => Ok(false),
+            _ => self.error(),
+        }
+    }
+
+    fn quoted(&mut self) -> Result<Arc<str>, Error> {
+        let quote = match self.peek() {
+            Some(c) => c,
+            None => return self.error(),
+        };
+        self.position += 1;
+
+        let mut result = String::new();
+        let mut escaped = false;
+        while let Some(c) = self.peek() {
+            self.position += c.len_utf8();
+            if escaped {
+                if c != quote && c != '\\' {
+                    self.position -= c.len_utf8();
+                    return self.error();
+                }
+                result.push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                return Ok(result.into());
+            } else {
+                result.push(c);
+            }
+        }
+        self.error()
+    }
+
+    fn unquoted(&mut self) -> &'a str {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if !is_unquoted(c) {
+                break;
+            }
+            self.position += 1;
+        }
+        &self.input[start..self.position]
+    }
+
+    fn string(&mut self) -> Result<Arc<str>, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') | Some('\'') => self.quoted(),
+            _ => {
+                let result = self.unquoted();
+                if result.is_empty() {
+                    self.error()
+                } else {
+                    Ok(result.into())
+                }
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.nested(Self::compound),
+            Some('[') => self.nested(Self::list_or_array),
+            Some('"') | Some('\'') => Ok(Value::String(self.quoted()?)),
+            _ => {
+                let start = self.position;
+                let literal = self.unquoted();
+                if literal.is_empty() {
+                    self.position = start;
+                    return self.error();
+                }
+                Ok(literal_value(literal))
+            }
+        }
+    }
+
+    fn nested(&mut self, f: fn(&mut Self) -> Result<Value, Error>) -> Result<Value, Error> {
+        if self.depth == MAX_DEPTH {
+            return self.error();
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn compound(&mut self) -> Result<Value, Error> {
+        self.expect('{')?;
+        let mut result = Compound::new();
+        self.skip_whitespace();
+        if self.peek() != Some('}') {
+            loop {
+                let key = self.string()?;
+                self.expect(':')?;
+                let value = self.value()?;
+                result.insert(key, value);
+                if !self.separator('}')? {
+                    break;
+                }
+            }
+        }
+        self.expect('}')?;
+        Ok(Value::Compound(result))
+    }
+
+    fn list_or_array(&mut self) -> Result<Value, Error> {
+        self.expect('[')?;
+        let rest = &self.input[self.position..];
+        let mut prefix = rest.chars();
+        if let (Some(kind), Some(';')) = (prefix.next(), prefix.next()) {
+            if let 'B' | 'I' | 'L' = kind {
+                self.position += 2;
+                return self.array(kind);
+            }
+        }
+
+        let mut result = Vec::new();
+        self.skip_whitespace();
+        if self.peek() != Some(']') {
+            loop {
+                let start = self.position;
+                let value = self.value()?;
+                // Lists can only contain a single type
+                if let Some(first) = result.first() {
+                    if std::mem::discriminant(first) != std::mem::discriminant(&value) {
+                        self.position = start;
+                        return self.error();
+                    }
+                }
+                result.push(value);
+                if !self.separator(']')? {
+                    break;
+                }
+            }
+        }
+        self.expect(']')?;
+        Ok(Value::List(result.into()))
+    }
+
+    fn array(&mut self, kind: char) -> Result<Value, Error> {
+        let mut bytes = Vec::new();
+        let mut ints = Vec::new();
+        let mut longs = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() != Some(']') {
+            loop {
+                let start = self.position;
+                match (kind, self.value()?) {
+                    ('B', Value::Byte(v)) => bytes.push(v as u8),
+                    ('I', Value::Int(v)) => ints.push(v),
+                    ('L', Value::Long(v)) => longs.push(v),
+                    _ => {
+                        self.position = start;
+                        return self.error();
+                    }
+                }
+                if !self.separator(']')? {
+                    break;
+                }
+            }
+        }
+        self.expect(']')?;
+
+        Ok(match kind {
+            'B' => Value::ByteArray(bytes.into()),
+            'I' => Value::IntArray(ints.into()),
+            _ => Value::LongArray(longs.into()),
+        })
+    }
+}
+
+fn strip_sign(value: &str) -> &str {
+    if value.starts_with('-') || value.starts_with('+') {
+        &value[1..]
+    } else {
+        value
+    }
+}
+
+fn is_integer(value: &str) -> bool {
+    let digits = strip_sign(value);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_decimal(value: &str) -> bool {
+    let value = strip_sign(value);
+    let (mantissa, exponent) = match value.find(['e', 'E'].as_ref()) {
+        Some(index) => (&value[..index], Some(&value[index + 1..])),
+        None => (value, None),
+    };
+    let mut parts = mantissa.splitn(2, '.');
+    let whole = parts.next().unwrap_or("");
+    let fraction = parts.next().unwrap_or("");
+    let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    !(whole.is_empty() && fraction.is_empty())
+        && digits(whole)
+        && digits(fraction)
+        && exponent.map_or(true, is_integer)
+}
+
+// Unquoted values that are not numbers in range, or booleans, are strings.
+fn literal_value(literal: &str) -> Value {
+    let (body, suffix) = literal.split_at(literal.len() - 1);
+    let parsed = match suffix {
+        "b" | "B" if is_integer(body) => body.parse().ok().map(Value::Byte),
+        "s" | "S" if is_integer(body) => body.parse().ok().map(Value::Short),
+        "l" | "L" if is_integer(body) => body.parse().ok().map(Value::Long),
+        "f" | "F" if is_decimal(body) => body.parse().ok().map(Value::Float),
+        "d" | "D" if is_decimal(body) => body.parse().ok().map(Value::Double),
+        _ if is_integer(literal) => literal.parse().ok().map(Value::Int),
+        _ if is_decimal(literal) && literal.contains('.') => {
+            literal.parse().ok().map(Value::Double)
+        }
+        _ if literal == "true" => Some(Value::Byte(1)),
+        _ if literal == "false" => Some(Value::Byte(0)),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| Value::String(literal.into()))
+}
+
+// Parses SNBT, for example: {foo:1b,bar:[I;1,2]}
+impl FromStr for Value {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            input: value,
+            position: 0,
+            depth: 0,
+        };
+        let result = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position != value.len() {
+            return parser.error();
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    fn parse(value: &str) -> Value {
+        value.parse().unwrap()
+    }
+
+    fn parse_error(value: &str) -> usize {
+        match value.parse::<Value>() {
+            Ok(r) => panic!("expected error, got {}", r),
+            Err(e) => match e.kind() {
+                ErrorKind::InvalidSnbt(position) => *position,
+                _ => panic!("unexpected error: {}", e),
+            },
+        }
+    }
+
+    #[test]
+    pub fn snbt_parse_numbers() {
+        assert_eq!(parse("1b"), nbt_byte!(1));
+        assert_eq!(parse("-128B"), nbt_byte!(-128));
+        assert_eq!(parse("true"), nbt_byte!(1));
+        assert_eq!(parse("false"), nbt_byte!(0));
+        assert_eq!(parse("300s"), nbt_short!(300));
+        assert_eq!(parse("-7"), nbt_int!(-7));
+        assert_eq!(parse("+7"), nbt_int!(7));
+        assert_eq!(parse("9000000000L"), nbt_long!(9_000_000_000));
+        assert_eq!(parse("1.5f"), nbt_float!(1.5));
+        assert_eq!(parse("2f"), nbt_float!(2.0));
+        assert_eq!(parse(".5"), nbt_double!(0.5));
+        assert_eq!(parse("1e3d"), nbt_double!(1000.0));
+        assert_eq!(parse("-2.5E-1D"), nbt_double!(-0.25));
+    }
+
+    #[test]
+    pub fn snbt_parse_strings() {
+        // Numbers that are out of range are strings, like in vanilla
+        assert_eq!(parse("300b"), nbt_string!("300b"));
+        assert_eq!(parse("1e3"), nbt_string!("1e3"));
+        assert_eq!(parse("foo.bar-baz_1+"), nbt_string!("foo.bar-baz_1+"));
+        assert_eq!(parse(r#""say \"hi\"""#), nbt_string!(r#"say "hi""#));
+        assert_eq!(parse(r#"'it\'s \\ "ok"'"#), nbt_string!(r#"it's \ "ok""#));
+        assert_eq!(parse("\"\""), nbt_string!(""));
+    }
+
+    #[test]
+    pub fn snbt_parse_compound() {
+        assert_eq!(
+            parse(r#"{ foo : 1b , "bar baz": [I; 1, 2], 'q': {}, list: [1L, 2L,], }"#),
+            nbt_compound! {
+                "foo" => nbt_byte!(1),
+                "bar baz" => nbt_int_array!(1, 2),
+                "q" => nbt_compound! {},
+                "list" => nbt_list!(nbt_long!(1), nbt_long!(2))
+            }
+        );
+        assert_eq!(parse("[B;1b,-1B]"), nbt_byte_array!(1, 255));
+        assert_eq!(parse("[L;]"), nbt_long_array!());
+        assert_eq!(parse("[]"), nbt_list!());
+    }
+
+    #[test]
+    pub fn snbt_parse_errors() {
+        assert_eq!(parse_error(""), 0);
+        assert_eq!(parse_error("{foo:1"), 6);
+        assert_eq!(parse_error("{foo 1}"), 5);
+        assert_eq!(parse_error("[1, 2b]"), 4);
+        assert_eq!(parse_error("[I; 1, 2L]"), 7);
+        assert_eq!(parse_error("[L; 1]"), 4);
+        assert_eq!(parse_error("\"abc"), 4);
+        assert_eq!(parse_error(r#""\n""#), 2);
+        assert_eq!(parse_error("1 2"), 2);
+        assert_eq!(parse_error("minecraft:stone"), 9);
+        // Not an array because of the space
+        assert_eq!(parse_error("[I ;1]"), 3);
+        assert_eq!(parse_error("{,}"), 1);
+        assert!(parse_error(&"[".repeat(MAX_DEPTH + 1)) <= MAX_DEPTH);
+    }
+
+    #[test]
+    pub fn snbt_display() {
+        let value = nbt_compound! {
+            "foo" => nbt_byte!(1),
+            "bar" => nbt_int_array!(1, 2),
+            "bytes" => nbt_byte_array!(1, 255),
+            "longs" => nbt_long_array!(3),
+            "name" => nbt_string!("say \"hi\""),
+            "needs quotes" => nbt_list!(nbt_float!(1.0), nbt_float!(0.5)),
+            "nested" => nbt_list!(nbt_compound! { "d" => nbt_double!(2.0) }, nbt_compound! {})
+        };
+        assert_eq!(
+            value.to_string(),
+            concat!(
+                r#"{foo:1b,bar:[I;1,2],bytes:[B;1B,-1B],longs:[L;3L],name:'say "hi"',"#,
+                r#""needs quotes":[1.0f,0.5f],nested:[{d:2.0d},{}]}"#
+            )
+        );
+        assert_eq!(
+            format!("{:#}", value),
+            r#"{
+    foo: 1b,
+    bar: [I; 1, 2],
+    bytes: [B; 1B, -1B],
+    longs: [L; 3L],
+    name: 'say "hi"',
+    "needs quotes": [1.0f, 0.5f],
+    nested: [
+        {
+            d: 2.0d
+        },
+        {}
+    ]
+}"#
+        );
+    }
+
+    #[test]
+    pub fn snbt_round_trip() {
+        let value = nbt_compound! {
+            "short" => nbt_short!(-3),
+            "long" => nbt_long!(i64::MIN),
+            "float" => nbt_float!(1.0e-10),
+            "double" => nbt_double!(123_456.789),
+            "string" => nbt_string!("it's \\ \"quoted\""),
+            "empty" => nbt_string!(""),
+            "lists" => nbt_list!(nbt_list!(), nbt_list!(nbt_int!(1)))
+        };
+        assert_eq!(parse(&value.to_string()), value);
+        assert_eq!(parse(&format!("{:#}", value)), value);
+    }
+}
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use crate::nbt::Compound;
+use std::ops::Index;
 use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -12,11 +13,171 @@ pub enum Value {
     ByteArray(Arc<[u8]>),
     String(Arc<str>),
     List(Arc<[Value]>),
-    Compound(HashMap<Arc<str>, Value>),
+    Compound(Compound),
     IntArray(Arc<[i32]>),
     LongArray(Arc<[i64]>),
 }
 
+// The integer accessors also accept smaller types, since vanilla isn't
+// always consistent about the type that it uses for a field.
+impl Value {
+    pub fn as_i8(&self) -> Option<i8> {
+        match self {
+            Value::Byte(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i16(&self) -> Option<i16> {
+        match self {
+            Value::Byte(v) => Some((*v).into()),
+            Value::Short(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            Value::Byte(v) => Some((*v).into()),
+            Value::Short(v) => Some((*v).into()),
+            Value::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Byte(v) => Some((*v).into()),
+            Value::Short(v) => Some((*v).into()),
+            Value::Int(v) => Some((*v).into()),
+            Value::Long(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Value::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Float(v) => Some((*v).into()),
+            Value::Double(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_i8().map(|v| v != 0)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_byte_array(&self) -> Option<&[u8]> {
+        match self {
+            Value::ByteArray(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_int_array(&self) -> Option<&[i32]> {
+        match self {
+            Value::IntArray(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Value::LongArray(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Value::Compound(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound_mut(&mut self) -> Option<&mut Compound> {
+        match self {
+            Value::Compound(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // Returns None if this is not a compound or the key is missing
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_compound()?.get(key)
+    }
+
+    // Looks up a value with a path such as "Level.Sections[0].Y". Keys that
+    // contain dots or brackets can't be used in paths.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        let mut current = self;
+        for segment in path.split('.') {
+            let (key, mut indices) = match segment.find('[') {
+                Some(i) => segment.split_at(i),
+                None => (segment, ""),
+            };
+            if !key.is_empty() {
+                current = current.get(key)?;
+            }
+            while !indices.is_empty() {
+                let end = indices.find(']')?;
+                let index: usize = indices[1..end].parse().ok()?;
+                current = current.as_list()?.get(index)?;
+                indices = &indices[end + 1..];
+                if !indices.is_empty() && !indices.starts_with('[') {
+                    return None;
+                }
+            }
+        }
+        Some(current)
+    }
+}
+
+// Panics if this is not a compound or the key is missing, use get to check
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        match self.get(key) {
+            Some(r) => r,
+            None => panic!("no value for the key {:?}", key),
+        }
+    }
+}
+
+// Panics if this is not a list or the index is out of bounds
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        match self.as_list().and_then(|list| list.get(index)) {
+            Some(r) => r,
+            None => panic!("no value at the index {}", index),
+        }
+    }
+}
+
 impl From<i8> for Value {
     fn from(value: i8) -> Self {
         Value::Byte(value)
@@ -82,3 +243,67 @@ impl From<&[i64]> for Value {
         Value::LongArray(value.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level() -> Value {
+        crate::nbt_compound! {
+            "Level" => crate::nbt_compound! {
+                "xPos" => Value::Int(-3),
+                "Status" => Value::String("full".into()),
+                "Sections" => crate::nbt_list![
+                    crate::nbt_compound! { "Y" => Value::Byte(0) },
+                    crate::nbt_compound! { "Y" => Value::Byte(1) }
+                ],
+                "Lights" => crate::nbt_list![crate::nbt_list![Value::Short(7)]]
+            }
+        }
+    }
+
+    #[test]
+    pub fn nbt_value_get_path() {
+        let value = level();
+        assert_eq!(
+            value.get_path("Level.xPos").and_then(Value::as_i32),
+            Some(-3)
+        );
+        assert_eq!(
+            value.get_path("Level.Status").and_then(Value::as_str),
+            Some("full")
+        );
+        assert_eq!(
+            value
+                .get_path("Level.Sections[1].Y")
+                .and_then(Value::as_i64),
+            Some(1)
+        );
+        assert_eq!(value.get_path("Level.Lights[0][0]"), Some(&Value::Short(7)));
+        assert_eq!(value.get_path("Level.Sections[2].Y"), None);
+        assert_eq!(value.get_path("Level.Sections.Y"), None);
+        assert_eq!(value.get_path("Level.xPos.y"), None);
+        assert_eq!(value.get_path("Level.Lights[0]x"), None);
+        assert_eq!(value.get_path("Level.Missing"), None);
+    }
+
+    #[test]
+    pub fn nbt_value_accessors() {
+        let value = level();
+        assert_eq!(value["Level"]["Sections"][0]["Y"].as_bool(), Some(false));
+        assert_eq!(value["Level"]["xPos"].as_i16(), None);
+        assert_eq!(value["Level"]["xPos"].as_f64(), None);
+        assert_eq!(
+            value["Level"]["Lights"][0].as_list().map(<[_]>::len),
+            Some(1)
+        );
+        assert_eq!(value.get("Level").and_then(|v| v.get("Missing")), None);
+        assert!(value.as_compound().unwrap().contains_key("Level"));
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn nbt_value_index_missing() {
+        let _ = &level()["Level"]["Missing"];
+    }
+}
@@ -0,0 +1,559 @@
+use crate::{
+    nbt::{Compound, Value},
+    Error, ErrorKind,
+};
+use std::{borrow::Cow, convert::TryInto, marker::PhantomData};
+
+// Subtrees are skipped recursively, so nesting has to be limited
+const MAX_DEPTH: usize = 512;
+
+// A view over binary NBT that borrows from the data and only parses the
+// parts that are visited, so that a few fields can be read from a large
+// payload (such as a chunk) without building the whole tree. Finding an
+// entry only skips over the entries before it, so errors in the rest of the
+// data are not noticed unless it is visited.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtView<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(&'a [u8]),
+    String(Cow<'a, str>),
+    List(ListView<'a>),
+    Compound(CompoundView<'a>),
+    IntArray(ArrayView<'a, i32>),
+    LongArray(ArrayView<'a, i64>),
+}
+
+// Returns the name and the view of the root tag
+pub fn view_nbt(data: &[u8]) -> Result<(Cow<'_, str>, NbtView<'_>), Error> {
+    let mut input = Input(data);
+    let type_id = input.u8()?;
+    if type_id == 0x00 {
+        return Err(ErrorKind::InvalidNbt.into());
+    }
+    let name = input.str()?;
+    let value = input.view(type_id)?;
+    Ok((name, value))
+}
+
+// Like the accessors of Value, the integer accessors also accept smaller
+// types.
+impl<'a> NbtView<'a> {
+    pub fn as_i8(&self) -> Option<i8> {
+        match self {
+            NbtView::Byte(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            NbtView::Byte(v) => Some((*v).into()),
+            NbtView::Short(v) => Some((*v).into()),
+            NbtView::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_i8().map(|v| v != 0)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            NbtView::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&CompoundView<'a>> {
+        match self {
+            NbtView::Compound(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&ListView<'a>> {
+        match self {
+            NbtView::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // Parses the whole subtree
+    pub fn to_value(&self) -> Result<Value, Error> {
+        Ok(match self {
+            NbtView::Byte(v) => Value::Byte(*v),
+            NbtView::Short(v) => Value::Short(*v),
+            NbtView::Int(v) => Value::Int(*v),
+            NbtView::Long(v) => Value::Long(*v),
+            NbtView::Float(v) => Value::Float(*v),
+            NbtView::Double(v) => Value::Double(*v),
+            NbtView::ByteArray(v) => Value::ByteArray((*v).into()),
+            NbtView::String(v) => Value::String(v.as_ref().into()),
+            NbtView::List(v) => {
+                // The length hasn't been checked, but every element takes at
+                // least a byte
+                let mut result = Vec::with_capacity(v.len().min(v.data.len()));
+                for value in v.iter() {
+                    result.push(value?.to_value()?);
+                }
+                Value::List(result.into())
+            }
+            NbtView::Compound(v) => {
+                let mut result = Compound::new();
+                for entry in v.iter() {
+                    let (name, value) = entry?;
+                    result.insert(name.as_ref().into(), value.to_value()?);
+                }
+                Value::Compound(result)
+            }
+            NbtView::IntArray(v) => Value::IntArray(v.iter().collect::<Vec<_>>().into()),
+            NbtView::LongArray(v) => Value::LongArray(v.iter().collect::<Vec<_>>().into()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompoundView<'a> {
+    // The entries, followed by the rest of the input
+    data: &'a [u8],
+}
+
+impl<'a> CompoundView<'a> {
+    pub fn iter(&self) -> CompoundIter<'a> {
+        CompoundIter {
+            input: Input(self.data),
+            done: false,
+        }
+    }
+
+    // Finds an entry, skipping over the values of the entries before it
+    pub fn get(&self, name: &str) -> Result<Option<NbtView<'a>>, Error> {
+        let name = cesu8::to_java_cesu8(name);
+        let mut input = Input(self.data);
+        loop {
+            let type_id = input.u8()?;
+            if type_id == 0x00 {
+                return Ok(None);
+            }
+            let length = input.length_u16()?;
+            if input.take(length)? == name.as_ref() {
+                return input.view(type_id).map(Some);
+            }
+            input.skip(type_id, 0)?;
+        }
+    }
+}
+
+pub struct CompoundIter<'a> {
+    input: Input<'a>,
+    done: bool,
+}
+
+impl<'a> Iterator for CompoundIter<'a> {
+    type Item = Result<(Cow<'a, str>, NbtView<'a>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = match self.input.u8() {
+            Ok(0x00) => {
+                self.done = true;
+                return None;
+            }
+            Ok(type_id) => self
+                .input
+                .str()
+                .and_then(|name| Ok((name, self.input.payload(type_id)?))),
+            Err(e) => Err(e),
+        };
+        // Don't continue from a position that is not at the start of a tag
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ListView<'a> {
+    type_id: u8,
+    len: usize,
+    // The elements, followed by the rest of the input
+    data: &'a [u8],
+}
+
+impl<'a> ListView<'a> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> ListIter<'a> {
+        ListIter {
+            type_id: self.type_id,
+            remaining: self.len,
+            input: Input(self.data),
+        }
+    }
+}
+
+pub struct ListIter<'a> {
+    type_id: u8,
+    remaining: usize,
+    input: Input<'a>,
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = Result<NbtView<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let result = self.input.payload(self.type_id);
+        self.remaining = if result.is_ok() {
+            self.remaining - 1
+        } else {
+            0
+        };
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+pub trait ArrayElement: Sized + 'static {
+    const SIZE: usize;
+    fn from_be_bytes(data: &[u8]) -> Self;
+}
+
+impl ArrayElement for i32 {
+    const SIZE: usize = 4;
+    fn from_be_bytes(data: &[u8]) -> Self {
+        i32::from_be_bytes(data.try_into().unwrap())
+    }
+}
+
+impl ArrayElement for i64 {
+    const SIZE: usize = 8;
+    fn from_be_bytes(data: &[u8]) -> Self {
+        i64::from_be_bytes(data.try_into().unwrap())
+    }
+}
+
+// Int and long arrays are big endian, so the elements are converted as
+// they are read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArrayView<'a, T: ArrayElement> {
+    data: &'a [u8],
+    element: PhantomData<T>,
+}
+
+impl<'a, T: ArrayElement> ArrayView<'a, T> {
+    pub fn len(&self) -> usize {
+        self.data.len() / T::SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<T> {
+        let start = index.checked_mul(T::SIZE)?;
+        self.data
+            .get(start..start + T::SIZE)
+            .map(ArrayElement::from_be_bytes)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + 'a {
+        self.data
+            .chunks_exact(T::SIZE)
+            .map(ArrayElement::from_be_bytes)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < count {
+            return Err(ErrorKind::EndOfData.into());
+        }
+        let (result, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(result)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn length_u16(&mut self) -> Result<usize, Error> {
+        let data = self.take(2)?;
+        Ok(u16::from_be_bytes([data[0], data[1]]) as usize)
+    }
+
+    fn length_i32(&mut self) -> Result<usize, Error> {
+        let length = i32::from_be_bytes(self.take(4)?.try_into().unwrap());
+        if length < 0 {
+            return Err(ErrorKind::InvalidLengthPrefix.into());
+        }
+        Ok(length as usize)
+    }
+
+    fn array(&mut self, element_size: usize) -> Result<&'a [u8], Error> {
+        let length = self.length_i32()?;
+        self.take(length.saturating_mul(element_size))
+    }
+
+    fn str(&mut self) -> Result<Cow<'a, str>, Error> {
+        let length = self.length_u16()?;
+        Ok(cesu8::from_java_cesu8(self.take(length)?)?)
+    }
+
+    // Views a value without finding where it ends, which compounds and lists
+    // can only do by walking over their contents.
+    fn view(mut self, type_id: u8) -> Result<NbtView<'a>, Error> {
+        match type_id {
+            0x09 => {
+                let type_id = self.u8()?;
+                let len = self.length_i32()?;
+                Ok(NbtView::List(ListView {
+                    type_id,
+                    len,
+                    data: self.0,
+                }))
+            }
+            0x0a => Ok(NbtView::Compound(CompoundView { data: self.0 })),
+            _ => self.payload(type_id),
+        }
+    }
+
+    // Reads a value and moves past it
+    fn payload(&mut self, type_id: u8) -> Result<NbtView<'a>, Error> {
+        Ok(match type_id {
+            0x01 => NbtView::Byte(self.take(1)?[0] as i8),
+            0x02 => NbtView::Short(i16::from_be_bytes(self.take(2)?.try_into().unwrap())),
+            0x03 => NbtView::Int(i32::from_be_bytes(self.take(4)?.try_into().unwrap())),
+            0x04 => NbtView::Long(i64::from_be_bytes(self.take(8)?.try_into().unwrap())),
+            0x05 => NbtView::Float(f32::from_be_bytes(self.take(4)?.try_into().unwrap())),
+            0x06 => NbtView::Double(f64::from_be_bytes(self.take(8)?.try_into().unwrap())),
+            0x07 => NbtView::ByteArray(self.array(1)?),
+            0x08 => NbtView::String(self.str()?),
+            0x09 | 0x0a => {
+                let view = self.view(type_id)?;
+                self.skip(type_id, 0)?;
+                view
+            }
+            0x0b => NbtView::IntArray(ArrayView {
+                data: self.array(4)?,
+                element: PhantomData,
+            }),
+            0x0c => NbtView::LongArray(ArrayView {
+                data: self.array(8)?,
+                element: PhantomData,
+            }),
+            _ => return Err(ErrorKind::InvalidNbt.into()),
+        })
+    }
+
+    fn skip(&mut self, type_id: u8, depth: usize) -> Result<(), Error> {
+        match type_id {
+            0x01 => self.take(1).map(drop),
+            0x02 => self.take(2).map(drop),
+            0x03 | 0x05 => self.take(4).map(drop),
+            0x04 | 0x06 => self.take(8).map(drop),
+            0x07 => self.array(1).map(drop),
+            0x08 => {
+                let length = self.length_u16()?;
+                self.take(length).map(drop)
+            }
+            0x09 => {
+                if depth >= MAX_DEPTH {
+                    return Err(ErrorKind::NbtLimitExceeded.into());
+                }
+                let type_id = self.u8()?;
+                let len = self.length_i32()?;
+                match type_id {
+                    // The fixed size types can be skipped at once
+                    0x01 => self.take(len).map(drop),
+                    0x02 => self.take(len.saturating_mul(2)).map(drop),
+                    0x03 | 0x05 => self.take(len.saturating_mul(4)).map(drop),
+                    0x04 | 0x06 => self.take(len.saturating_mul(8)).map(drop),
+                    // Empty lists can have the end type
+                    0x00 if len == 0 => Ok(()),
+                    _ => {
+                        for _ in 0..len {
+                            self.skip(type_id, depth + 1)?;
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            0x0a => {
+                if depth >= MAX_DEPTH {
+                    return Err(ErrorKind::NbtLimitExceeded.into());
+                }
+                loop {
+                    let type_id = self.u8()?;
+                    if type_id == 0x00 {
+                        return Ok(());
+                    }
+                    let length = self.length_u16()?;
+                    self.take(length)?;
+                    self.skip(type_id, depth + 1)?;
+                }
+            }
+            0x0b => self.array(4).map(drop),
+            0x0c => self.array(8).map(drop),
+            _ => Err(ErrorKind::InvalidNbt.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        nbt::{encode_nbt, NbtCompression},
+        tests::*,
+    };
+
+    fn chunk() -> Value {
+        crate::nbt_compound! {
+            "DataVersion" => Value::Int(2230),
+            "Level" => crate::nbt_compound! {
+                "xPos" => Value::Int(-3),
+                "Status" => Value::String("full".into()),
+                "Heightmaps" => crate::nbt_compound! {
+                    "MOTION_BLOCKING" => crate::nbt_long_array![1, 2, 3]
+                },
+                "Sections" => crate::nbt_list![
+                    crate::nbt_compound! {
+                        "Y" => Value::Byte(0),
+                        "BlockLight" => crate::nbt_byte_array![1, 2]
+                    },
+                    crate::nbt_compound! {
+                        "Y" => Value::Byte(1),
+                        "BlockStates" => crate::nbt_long_array![-1, i64::MIN]
+                    }
+                ],
+                "Biomes" => crate::nbt_int_array![1, -2],
+                "Entities" => crate::nbt_list![],
+                "Lights" => crate::nbt_list![
+                    crate::nbt_list![Value::Short(1)],
+                    crate::nbt_list![]
+                ]
+            }
+        }
+    }
+
+    fn encode(value: &Value) -> Result<Vec<u8>, Error> {
+        block_on(encode_nbt("chunk", value, NbtCompression::None))
+    }
+
+    #[test]
+    pub fn nbt_view_get() -> Result<(), Error> {
+        let data = encode(&chunk())?;
+        let (name, root) = view_nbt(&data)?;
+        assert_eq!(name, "chunk");
+
+        let root = root.as_compound().unwrap();
+        assert_eq!(root.get("DataVersion")?, Some(NbtView::Int(2230)));
+        assert_eq!(root.get("Missing")?, None);
+
+        let level = root.get("Level")?.unwrap();
+        let level = level.as_compound().unwrap();
+        assert_eq!(level.get("Status")?, Some(NbtView::String("full".into())));
+        match level.get("Biomes")? {
+            Some(NbtView::IntArray(v)) => {
+                assert_eq!(v.len(), 2);
+                assert_eq!(v.get(1), Some(-2));
+                assert_eq!(v.get(2), None);
+            }
+            v => panic!("unexpected biomes: {:?}", v),
+        }
+
+        let sections = level.get("Sections")?.unwrap();
+        let sections = sections.as_list().unwrap();
+        assert_eq!(sections.len(), 2);
+        let mut y = Vec::new();
+        for section in sections.iter() {
+            y.push(section?.as_compound().unwrap().get("Y")?);
+        }
+        assert_eq!(y, vec![Some(NbtView::Byte(0)), Some(NbtView::Byte(1))]);
+        Ok(())
+    }
+
+    #[test]
+    pub fn nbt_view_to_value() -> Result<(), Error> {
+        let chunk = chunk();
+        let data = encode(&chunk)?;
+        let (_, root) = view_nbt(&data)?;
+        assert_eq!(root.to_value()?, chunk);
+
+        let mut entries = 0;
+        for entry in root.as_compound().unwrap().iter() {
+            let (name, value) = entry?;
+            assert!(name == "DataVersion" || name == "Level");
+            value.to_value()?;
+            entries += 1;
+        }
+        assert_eq!(entries, 2);
+        Ok(())
+    }
+
+    fn expect_end(result: Result<(), Error>) -> Result<(), Error> {
+        match result {
+            Ok(_) => panic!("expected the data to end"),
+            Err(e) => match e.kind() {
+                ErrorKind::EndOfData => Ok(()),
+                _ => Err(e),
+            },
+        }
+    }
+
+    #[test]
+    pub fn nbt_view_invalid() -> Result<(), Error> {
+        let data = encode(&chunk())?;
+
+        // Truncated data is only detected when the end is visited
+        let (_, root) = view_nbt(&data[..data.len() - 1])?;
+        let compound = root.as_compound().unwrap();
+        let level = compound.get("Level")?.unwrap();
+        let level = level.as_compound().unwrap();
+        assert_eq!(level.get("Status")?, Some(NbtView::String("full".into())));
+        assert_eq!(level.get("Sections")?.unwrap().as_list().unwrap().len(), 2);
+        expect_end(compound.get("Missing").map(drop))?;
+        expect_end(root.to_value().map(drop))?;
+
+        // A length that is larger than the data
+        let (_, list) = view_nbt(&[0x09, 0x00, 0x00, 0x04, 0x7f, 0xff, 0xff, 0xff])?;
+        assert!(list.to_value().is_err());
+
+        let mut nested = vec![0x0a, 0x00, 0x00];
+        for _ in 0..=MAX_DEPTH {
+            nested.extend_from_slice(&[0x0a, 0x00, 0x00]);
+        }
+        nested.resize(nested.len() + MAX_DEPTH + 2, 0x00);
+        let (_, root) = view_nbt(&nested)?;
+        match root.to_value() {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => match e.kind() {
+                ErrorKind::NbtLimitExceeded => Ok(()),
+                _ => Err(e),
+            },
+        }
+    }
+}
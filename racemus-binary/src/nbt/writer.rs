@@ -108,9 +108,10 @@ mod tests {
     use super::*;
     use crate::tests::*;
 
-    // HashMap is not deterministic, so we have to use the read functions as a
-    // source of truth for compound with multiple values. The read functions are
-    // in-turn tested against a nbt file generated with an external tool.
+    // Compounds are compared without regard to order, so the read functions
+    // are used as a source of truth for compounds with multiple values. The
+    // read functions are in-turn tested against a nbt file generated with an
+    // external tool.
 
     macro_rules! identity_tests {
         ($($name:ident, $nbt_name:literal => $expected:expr;)*) => {
@@ -152,6 +153,16 @@ mod tests {
         };
     }
 
+    #[test]
+    fn binary_writer_nbt_preserves_order() -> Result<(), Error> {
+        let expected = include_bytes!("test-data/nbt-hello-world.in") as &[u8];
+        let (name, value) = block_on(make_reader(expected).nbt())?;
+        let mut writer = make_writer();
+        writer.nbt(&name, &value)?;
+        assert_eq!(make_buffer(writer), expected);
+        Ok(())
+    }
+
     macro_rules! raw_write_tests {
         ($($name:ident, $expected:expr, $writer:ident => $expr:expr;)*) => {
             $(
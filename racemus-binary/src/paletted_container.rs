@@ -0,0 +1,289 @@
+use crate::{proto::ChunkSection, VarVec};
+
+pub const SECTION_WIDTH: usize = 16;
+pub const SECTION_VOLUME: usize = SECTION_WIDTH * SECTION_WIDTH * SECTION_WIDTH;
+
+// The client always uses at least 4 bits, and anything over 8 bits must use
+// the global palette.
+const MIN_BITS_PER_ENTRY: u8 = 4;
+const MAX_INDIRECT_BITS_PER_ENTRY: u8 = 8;
+pub const GLOBAL_BITS_PER_ENTRY: u8 = 14;
+
+const AIR: u32 = 0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PalettedContainer {
+    // When absent the global palette is used
+    palette: Option<Vec<u32>>,
+    entries: VarVec,
+    block_count: u16,
+}
+
+impl Default for PalettedContainer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PalettedContainer {
+    pub fn new() -> Self {
+        Self {
+            palette: Some(vec![AIR]),
+            entries: VarVec::with_capacity(SECTION_VOLUME, MIN_BITS_PER_ENTRY),
+            block_count: 0,
+        }
+    }
+
+    #[inline]
+    pub fn index(x: usize, y: usize, z: usize) -> usize {
+        debug_assert!(x < SECTION_WIDTH && y < SECTION_WIDTH && z < SECTION_WIDTH);
+        (y * SECTION_WIDTH + z) * SECTION_WIDTH + x
+    }
+
+    #[inline]
+    pub fn block_count(&self) -> u16 {
+        self.block_count
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.block_count == 0
+    }
+
+    #[inline]
+    pub fn bits_per_entry(&self) -> u8 {
+        self.entries.bits_per_entry()
+    }
+
+    #[inline]
+    pub fn palette(&self) -> Option<&[u32]> {
+        match &self.palette {
+            Some(palette) => Some(&palette[..]),
+            None => None,
+        }
+    }
+
+    // The number of bytes allocated for the palette and entries
+    pub fn heap_size(&self) -> usize {
+        let palette = self
+            .palette
+            .as_ref()
+            .map_or(0, |p| p.capacity() * std::mem::size_of::<u32>());
+        palette + std::mem::size_of_val(self.entries.get_inner())
+    }
+
+    #[inline]
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.get_index(Self::index(x, y, z))
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: usize, y: usize, z: usize, state: u32) -> u32 {
+        self.set_index(Self::index(x, y, z), state)
+    }
+
+    pub fn get_index(&self, index: usize) -> u32 {
+        let value = self.entries.get(index).unwrap() as u32;
+        match &self.palette {
+            Some(palette) => palette[value as usize],
+            None => value,
+        }
+    }
+
+    pub fn set_index(&mut self, index: usize, state: u32) -> u32 {
+        let value = self.palette_index(state);
+        let old = self.entries.set(index, value as u64).unwrap() as u32;
+        let old = match &self.palette {
+            Some(palette) => palette[old as usize],
+            None => old,
+        };
+
+        if old == AIR && state != AIR {
+            self.block_count += 1;
+        } else if old != AIR && state == AIR {
+            self.block_count -= 1;
+        }
+
+        old
+    }
+
+    fn palette_index(&mut self, state: u32) -> u32 {
+        let palette = match &mut self.palette {
+            Some(palette) => palette,
+            None => return state,
+        };
+
+        if let Some(index) = palette.iter().position(|p| *p == state) {
+            return index as u32;
+        }
+
+        let index = palette.len();
+        if index < (1 << self.entries.bits_per_entry()) {
+            palette.push(state);
+            return index as u32;
+        }
+
+        let bits_per_entry = self.entries.bits_per_entry() + 1;
+        if bits_per_entry <= MAX_INDIRECT_BITS_PER_ENTRY {
+            palette.push(state);
+            self.entries.resize_bits_per_entry(bits_per_entry);
+            index as u32
+        } else {
+            self.use_global_palette();
+            state
+        }
+    }
+
+    fn use_global_palette(&mut self) {
+        let palette = match self.palette.take() {
+            Some(palette) => palette,
+            None => return,
+        };
+
+        let mut entries = VarVec::with_capacity(SECTION_VOLUME, GLOBAL_BITS_PER_ENTRY);
+        for index in 0..SECTION_VOLUME {
+            let value = self.entries.get(index).unwrap() as usize;
+            entries.set(index, palette[value] as u64);
+        }
+        self.entries = entries;
+    }
+
+    // Returns None if an entry refers past the end of the palette
+    pub fn from_raw(palette: Option<Vec<u32>>, entries: VarVec) -> Option<Self> {
+        let mut result = Self {
+            palette,
+            entries,
+            block_count: 0,
+        };
+        result.entries.resize(SECTION_VOLUME);
+        if let Some(palette) = &result.palette {
+            for index in 0..SECTION_VOLUME {
+                if result.entries.get(index)? >= palette.len() as u64 {
+                    return None;
+                }
+            }
+        }
+        for index in 0..SECTION_VOLUME {
+            if result.get_index(index) != AIR {
+                result.block_count += 1;
+            }
+        }
+        Some(result)
+    }
+}
+
+impl<'a> From<&'a PalettedContainer> for ChunkSection<'a> {
+    fn from(value: &'a PalettedContainer) -> Self {
+        ChunkSection {
+            block_count: value.block_count,
+            palette: value.palette(),
+            blocks: &value.entries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::*, Error};
+
+    #[test]
+    pub fn paletted_container_new() {
+        let c = PalettedContainer::new();
+        assert_eq!(c.block_count(), 0);
+        assert_eq!(c.bits_per_entry(), 4);
+        assert_eq!(c.palette(), Some(&[AIR][..]));
+        assert_eq!(c.get(15, 15, 15), AIR);
+    }
+
+    #[test]
+    pub fn paletted_container_set() {
+        let mut c = PalettedContainer::new();
+        assert_eq!(c.set(1, 2, 3, 33), AIR);
+        assert_eq!(c.set(1, 2, 3, 34), 33);
+        assert_eq!(c.get(1, 2, 3), 34);
+        assert_eq!(c.get(3, 2, 1), AIR);
+        assert_eq!(c.palette(), Some(&[AIR, 33, 34][..]));
+        assert_eq!(c.block_count(), 1);
+
+        c.set(1, 2, 3, AIR);
+        assert_eq!(c.block_count(), 0);
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    pub fn paletted_container_grow() {
+        let mut c = PalettedContainer::new();
+        for i in 0..16 {
+            c.set_index(i, i as u32);
+        }
+        assert_eq!(c.bits_per_entry(), 4);
+
+        c.set_index(16, 16);
+        assert_eq!(c.bits_per_entry(), 5);
+        for i in 0..17 {
+            assert_eq!(c.get_index(i), i as u32);
+        }
+        assert_eq!(c.block_count(), 16);
+    }
+
+    #[test]
+    pub fn paletted_container_global() {
+        let mut c = PalettedContainer::new();
+        for i in 0..256 {
+            c.set_index(i * 2, i as u32 * 3);
+        }
+        assert_eq!(c.bits_per_entry(), 8);
+        assert!(c.palette().is_some());
+
+        c.set_index(4095, 11000);
+        assert_eq!(c.bits_per_entry(), GLOBAL_BITS_PER_ENTRY);
+        assert_eq!(c.palette(), None);
+        for i in 0..256 {
+            assert_eq!(c.get_index(i * 2), i as u32 * 3);
+            assert_eq!(c.get_index(i * 2 + 1), AIR);
+        }
+        assert_eq!(c.get_index(4095), 11000);
+        assert_eq!(c.block_count(), 256);
+    }
+
+    #[test]
+    pub fn paletted_container_from_raw() {
+        let mut c = PalettedContainer::new();
+        c.set(0, 0, 0, 1);
+        c.set(15, 15, 15, 2);
+
+        let raw = PalettedContainer::from_raw(c.palette.clone(), c.entries.clone()).unwrap();
+        assert_eq!(raw, c);
+        assert_eq!(raw.block_count(), 2);
+
+        let mut entries = c.entries.clone();
+        entries.set(100, 3);
+        assert!(PalettedContainer::from_raw(c.palette.clone(), entries.clone()).is_none());
+        let raw = PalettedContainer::from_raw(None, entries).unwrap();
+        assert_eq!(raw.get_index(100), 3);
+        assert_eq!(raw.block_count(), 3);
+    }
+
+    #[test]
+    pub fn binary_writer_paletted_container() -> Result<(), Error> {
+        let mut c = PalettedContainer::new();
+        for x in 0..16 {
+            for z in 0..16 {
+                c.set(x, 0, z, 33);
+                c.set(x, 1, z, 10);
+            }
+        }
+        c.set(15, 15, 15, 1);
+
+        let mut writer = make_writer();
+        writer.chunk_section(&(&c).into())?;
+        let buf = make_buffer(writer);
+        assert_eq!(
+            buf,
+            include_bytes!("test-data/paletted-container-1.in") as &[u8]
+        );
+
+        Ok(())
+    }
+}
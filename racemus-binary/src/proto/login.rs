@@ -1,99 +1,59 @@
-use crate::{
-    proto::packet_ids::login as packet_ids, writer::StructuredWriter, BinaryReader, BinaryWriter,
-    Error,
-};
-use async_std::io::{Read, Write};
+use racemus_proto_derive::Packet;
 use std::sync::Arc;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Packet)]
+#[packet(read = "read_login")]
 pub enum LoginRequest {
+    #[packet(id = 0x00)]
     Start {
+        #[packet(arr_char(16))]
         player_name: Arc<str>,
     },
+    #[packet(id = 0x01)]
     EncryptionResponse {
+        #[packet(arr_u8(128))]
         encrypted_shared_secret: Arc<[u8]>,
+        #[packet(arr_u8(128))]
         encrypted_verifier: Arc<[u8]>,
     },
-    Unknown {
-        packet_id: i32,
-    },
-}
-
-impl<R: Read + Unpin> BinaryReader<R> {
-    pub async fn read_login(&mut self) -> Result<LoginRequest, Error> {
-        let packet_id = self.packet_header().await?;
-        match packet_id {
-            packet_ids::START => {
-                let player_name = self.arr_char(Some(16)).await?;
-                Ok(LoginRequest::Start { player_name })
-            }
-            packet_ids::ENCRYPTION_RESPONSE => {
-                let encrypted_shared_secret = self.arr_u8(Some(128)).await?;
-                let encrypted_verifier = self.arr_u8(Some(128)).await?;
-                Ok(LoginRequest::EncryptionResponse {
-                    encrypted_shared_secret,
-                    encrypted_verifier,
-                })
-            }
-            _ => Ok(LoginRequest::Unknown { packet_id }),
-        }
-    }
+    #[packet(unknown)]
+    Unknown { packet_id: i32 },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Packet)]
+#[packet(write)]
 pub enum LoginResponse<'a> {
+    #[packet(id = 0x01)]
     EncryptionRequest {
+        // Server ID (obsolete)
+        #[packet(pad(arr_char = ""), arr_u8)]
         public_key: &'a [u8],
+        #[packet(arr_u8)]
         verify_token: &'a [u8],
     },
+    #[packet(id = 0x02)]
     Success {
+        #[packet(arr_char)]
         player_uuid: &'a str,
+        #[packet(arr_char)]
         player_name: &'a str,
     },
+    #[packet(id = 0x03)]
     SetCompression {
+        #[packet(var_i32)]
         compression_threshold: u16,
     },
+    #[packet(id = 0x00)]
     Disconnect {
+        #[packet(arr_char)]
         reason: &'a str,
     },
 }
 
-impl<'a, W: Write + Unpin> StructuredWriter<W, LoginResponse<'a>> for BinaryWriter<W> {
-    fn structure(&mut self, val: &LoginResponse<'a>) -> Result<&mut Self, Error> {
-        let packet = self.start_packet();
-        match val {
-            LoginResponse::EncryptionRequest {
-                public_key,
-                verify_token,
-            } => self
-                .var_i32(packet_ids::ENCRYPTION_REQUEST)?
-                .var_i32(0)? // Server ID (obsolete)
-                .arr_u8(public_key)?
-                .arr_u8(verify_token)?,
-            LoginResponse::Success {
-                player_uuid,
-                player_name,
-            } => self
-                .var_i32(packet_ids::SUCCESS)?
-                .arr_char(player_uuid)?
-                .arr_char(player_name)?,
-            LoginResponse::SetCompression {
-                compression_threshold,
-            } => self
-                .var_i32(packet_ids::SET_COMPRESSION)?
-                .var_i32(*compression_threshold as i32)?,
-            LoginResponse::Disconnect { reason } => {
-                self.var_i32(packet_ids::DISCONNECT)?.arr_char(reason)?
-            }
-        }
-        .complete_packet(packet)
-    }
-}
-
 #[cfg(test)]
 mod tests {
-    use super::{LoginRequest::*, LoginResponse::*, *};
-    use crate::tests::*;
+    use super::{LoginRequest::*, LoginResponse::*};
+    use crate::{tests::*, Error, StructuredWriter};
 
     macro_rules! raw_write_tests {
         ($($name:ident, $expected:expr, $writer:ident => $expr:expr;)*) => {
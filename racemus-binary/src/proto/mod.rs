@@ -1,12 +1,13 @@
 mod login;
 mod open;
-pub(crate) mod packet_ids;
 mod play;
 mod reader;
 mod status;
+mod version;
 mod writer;
 
 pub use login::*;
 pub use open::*;
 pub use play::*;
 pub use status::*;
+pub use version::*;
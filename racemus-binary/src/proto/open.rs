@@ -1,9 +1,7 @@
-use crate::{
-    proto::packet_ids::open as packet_ids, writer::StructuredWriter, BinaryReader, BinaryWriter,
-    Error, ErrorKind,
-};
+use crate::{writer::StructuredWriter, BinaryReader, BinaryWriter, Error, ErrorKind};
 use async_std::io::{Read, Write};
-use std::sync::Arc;
+use racemus_proto_derive::Packet;
+use std::{convert::TryFrom, sync::Arc};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RequestedState {
@@ -11,18 +9,36 @@ pub enum RequestedState {
     Login,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl TryFrom<i32> for RequestedState {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(RequestedState::Status),
+            0x02 => Ok(RequestedState::Login),
+            _ => Err(ErrorKind::InvalidState(value).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Packet)]
+#[packet(read = "read_handshake")]
 pub enum OpenRequest {
+    #[packet(id = 0x00)]
     Handshake {
+        #[packet(var_i32)]
         version: i32,
+        #[packet(arr_char(255))]
         address: Arc<str>,
+        #[packet(fix_u16)]
         port: u16,
+        #[packet(var_i32)]
         next_state: RequestedState,
     },
+    #[packet(skip)]
     HttpGet {},
-    Unknown {
-        packet_id: i32,
-    },
+    #[packet(unknown)]
+    Unknown { packet_id: i32 },
 }
 
 impl<R: Read + Unpin> BinaryReader<R> {
@@ -36,27 +52,7 @@ impl<R: Read + Unpin> BinaryReader<R> {
             return Ok(OpenRequest::HttpGet {});
         }
 
-        let packet_id = self.packet_header().await?;
-        match packet_id {
-            packet_ids::HANDSHAKE => {
-                let version = self.var_i32().await?;
-                let address = self.arr_char(Some(255)).await?;
-                let port = self.fix_u16().await?;
-                let next_state = self.var_i32().await?;
-                let next_state = match next_state {
-                    0x01 => RequestedState::Status,
-                    0x02 => RequestedState::Login,
-                    _ => return Err(ErrorKind::InvalidState(next_state).into()),
-                };
-                Ok(OpenRequest::Handshake {
-                    version,
-                    address,
-                    port,
-                    next_state,
-                })
-            }
-            _ => Ok(OpenRequest::Unknown { packet_id }),
-        }
+        self.read_handshake().await
     }
 }
 
@@ -1,8 +1,11 @@
-use crate::{
-    proto::packet_ids::play as packet_ids, writer::StructuredWriter, BinaryReader, BinaryWriter,
-    Error,
-};
-use async_std::io::{Read, Write};
+use crate::{nbt::Value, proto::writer::var_i32_size, BinaryWriter, Error, ErrorKind, VarVec};
+use async_std::io::Write;
+use racemus_proto_derive::Packet;
+use std::{convert::TryFrom, sync::Arc};
+
+const MAX_CHAT_LEN: usize = 256 * 4;
+const MAX_IDENTIFIER_LEN: usize = 32767;
+const MAX_PLUGIN_DATA_LEN: usize = 32767;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GameModeKind {
@@ -60,124 +63,564 @@ impl From<Difficulty> for u8 {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl TryFrom<u8> for Difficulty {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(Difficulty::Peaceful),
+            0x1 => Ok(Difficulty::Easy),
+            0x2 => Ok(Difficulty::Medium),
+            0x3 => Ok(Difficulty::Hard),
+            _ => Err(ErrorKind::InvalidEnumValue(value as i32).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientStatusAction {
+    PerformRespawn,
+    RequestStats,
+}
+
+impl TryFrom<i32> for ClientStatusAction {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(ClientStatusAction::PerformRespawn),
+            0x1 => Ok(ClientStatusAction::RequestStats),
+            _ => Err(ErrorKind::InvalidEnumValue(value).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatMode {
+    Enabled,
+    CommandsOnly,
+    Hidden,
+}
+
+impl TryFrom<i32> for ChatMode {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(ChatMode::Enabled),
+            0x1 => Ok(ChatMode::CommandsOnly),
+            0x2 => Ok(ChatMode::Hidden),
+            _ => Err(ErrorKind::InvalidEnumValue(value).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MainHand {
+    Left,
+    Right,
+}
+
+impl TryFrom<i32> for MainHand {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(MainHand::Left),
+            0x1 => Ok(MainHand::Right),
+            _ => Err(ErrorKind::InvalidEnumValue(value).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hand {
+    Main,
+    Off,
+}
+
+impl TryFrom<i32> for Hand {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(Hand::Main),
+            0x1 => Ok(Hand::Off),
+            _ => Err(ErrorKind::InvalidEnumValue(value).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockFace {
+    Bottom,
+    Top,
+    North,
+    South,
+    West,
+    East,
+}
+
+impl TryFrom<i32> for BlockFace {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(BlockFace::Bottom),
+            0x1 => Ok(BlockFace::Top),
+            0x2 => Ok(BlockFace::North),
+            0x3 => Ok(BlockFace::South),
+            0x4 => Ok(BlockFace::West),
+            0x5 => Ok(BlockFace::East),
+            _ => Err(ErrorKind::InvalidEnumValue(value).into()),
+        }
+    }
+}
+
+impl TryFrom<u8> for BlockFace {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Self::try_from(value as i32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiggingStatus {
+    Started,
+    Cancelled,
+    Finished,
+    DropItemStack,
+    DropItem,
+    ShootArrowOrFinishEating,
+    SwapItemInHand,
+}
+
+impl TryFrom<i32> for DiggingStatus {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(DiggingStatus::Started),
+            0x1 => Ok(DiggingStatus::Cancelled),
+            0x2 => Ok(DiggingStatus::Finished),
+            0x3 => Ok(DiggingStatus::DropItemStack),
+            0x4 => Ok(DiggingStatus::DropItem),
+            0x5 => Ok(DiggingStatus::ShootArrowOrFinishEating),
+            0x6 => Ok(DiggingStatus::SwapItemInHand),
+            _ => Err(ErrorKind::InvalidEnumValue(value).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityActionKind {
+    StartSneaking,
+    StopSneaking,
+    LeaveBed,
+    StartSprinting,
+    StopSprinting,
+    StartJumpWithHorse,
+    StopJumpWithHorse,
+    OpenHorseInventory,
+    StartFlyingWithElytra,
+}
+
+impl TryFrom<i32> for EntityActionKind {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(EntityActionKind::StartSneaking),
+            0x1 => Ok(EntityActionKind::StopSneaking),
+            0x2 => Ok(EntityActionKind::LeaveBed),
+            0x3 => Ok(EntityActionKind::StartSprinting),
+            0x4 => Ok(EntityActionKind::StopSprinting),
+            0x5 => Ok(EntityActionKind::StartJumpWithHorse),
+            0x6 => Ok(EntityActionKind::StopJumpWithHorse),
+            0x7 => Ok(EntityActionKind::OpenHorseInventory),
+            0x8 => Ok(EntityActionKind::StartFlyingWithElytra),
+            _ => Err(ErrorKind::InvalidEnumValue(value).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Packet)]
+#[packet(read = "read_play")]
 pub enum PlayRequest {
+    #[packet(id = 0x00)]
+    TeleportConfirm {
+        #[packet(var_i32)]
+        teleport_id: i32,
+    },
+    #[packet(id = 0x02)]
+    SetDifficulty {
+        #[packet(fix_u8)]
+        difficulty: Difficulty,
+    },
+    #[packet(id = 0x03)]
+    ChatMessage {
+        #[packet(arr_char(MAX_CHAT_LEN))]
+        message: Arc<str>,
+    },
+    #[packet(id = 0x04)]
+    ClientStatus {
+        #[packet(var_i32)]
+        action: ClientStatusAction,
+    },
+    #[packet(id = 0x05)]
+    ClientSettings {
+        #[packet(arr_char(64))]
+        locale: Arc<str>,
+        #[packet(fix_u8)]
+        view_distance: u8,
+        #[packet(var_i32)]
+        chat_mode: ChatMode,
+        #[packet(fix_bool)]
+        chat_colors: bool,
+        #[packet(fix_u8)]
+        displayed_skin_parts: u8,
+        #[packet(var_i32)]
+        main_hand: MainHand,
+    },
+    #[packet(id = 0x0a)]
+    CloseWindow {
+        #[packet(fix_u8)]
+        window_id: u8,
+    },
+    #[packet(id = 0x0b)]
+    Plugin {
+        #[packet(arr_char(MAX_IDENTIFIER_LEN))]
+        channel: Arc<str>,
+        #[packet(arr_u8_remainder(MAX_PLUGIN_DATA_LEN))]
+        data: Arc<[u8]>,
+    },
+    #[packet(id(v1_15 = 0x0f))]
+    KeepAlive {
+        #[packet(fix_u64)]
+        keep_alive_id: u64,
+    },
+    #[packet(id(v1_15 = 0x10))]
+    LockDifficulty {
+        #[packet(fix_bool)]
+        locked: bool,
+    },
+    #[packet(id(v1_15 = 0x11))]
+    PlayerPosition {
+        #[packet(each(fix_f64))]
+        position: [f64; 3],
+        #[packet(fix_bool)]
+        on_ground: bool,
+    },
+    #[packet(id(v1_15 = 0x12))]
+    PlayerPositionAndLook {
+        #[packet(each(fix_f64))]
+        position: [f64; 3],
+        #[packet(each(fix_f32))]
+        look: [f32; 2],
+        #[packet(fix_bool)]
+        on_ground: bool,
+    },
+    #[packet(id(v1_15 = 0x13))]
+    PlayerLook {
+        #[packet(each(fix_f32))]
+        look: [f32; 2],
+        #[packet(fix_bool)]
+        on_ground: bool,
+    },
+    #[packet(id(v1_15 = 0x14))]
+    PlayerMovement {
+        #[packet(fix_bool)]
+        on_ground: bool,
+    },
+    #[packet(id(v1_15 = 0x19))]
+    PlayerAbilities {
+        #[packet(fix_u8)]
+        flags: u8,
+        #[packet(fix_f32)]
+        flying_speed: f32,
+        #[packet(fix_f32)]
+        walking_speed: f32,
+    },
+    #[packet(id(v1_15 = 0x1a))]
+    PlayerDigging {
+        #[packet(var_i32)]
+        status: DiggingStatus,
+        #[packet(fix_position)]
+        location: [i32; 3],
+        #[packet(fix_u8)]
+        face: BlockFace,
+    },
+    #[packet(id(v1_15 = 0x1b))]
+    EntityAction {
+        #[packet(var_u32)]
+        entity_id: u32,
+        #[packet(var_i32)]
+        action: EntityActionKind,
+        #[packet(var_i32)]
+        jump_boost: i32,
+    },
+    #[packet(id(v1_15 = 0x23))]
+    HeldItemChange {
+        #[packet(fix_i16)]
+        slot: i16,
+    },
+    #[packet(id(v1_15 = 0x2a))]
+    Animation {
+        #[packet(var_i32)]
+        hand: Hand,
+    },
+    #[packet(id(v1_15 = 0x2c))]
+    PlayerBlockPlacement {
+        #[packet(var_i32)]
+        hand: Hand,
+        #[packet(fix_position)]
+        location: [i32; 3],
+        #[packet(var_i32)]
+        face: BlockFace,
+        #[packet(each(fix_f32))]
+        cursor_position: [f32; 3],
+        #[packet(fix_bool)]
+        inside_block: bool,
+    },
+    #[packet(id(v1_15 = 0x2d))]
+    UseItem {
+        #[packet(var_i32)]
+        hand: Hand,
+    },
+    #[packet(unknown)]
     Unknown { packet_id: i32 },
 }
 
-impl<R: Read + Unpin> BinaryReader<R> {
-    pub async fn read_play(&mut self) -> Result<PlayRequest, Error> {
-        let packet_id = self.packet_header().await?;
-        match packet_id {
-            _ => Ok(PlayRequest::Unknown { packet_id }),
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkSection<'a> {
+    pub block_count: u16,
+    // The global palette is used when no palette is present
+    pub palette: Option<&'a [u32]>,
+    pub blocks: &'a VarVec,
+}
+
+impl<'a> ChunkSection<'a> {
+    fn size(&self) -> usize {
+        let palette = match self.palette {
+            Some(palette) => palette
+                .iter()
+                .fold(var_i32_size(palette.len() as i32), |acc, p| {
+                    acc + var_i32_size(*p as i32)
+                }),
+            None => 0,
+        };
+        let data = self.blocks.get_inner();
+        2 + 1 + palette + var_i32_size(data.len() as i32) + data.len() * 8
+    }
+}
+
+impl<W: Write + Unpin> BinaryWriter<W> {
+    pub(crate) fn chunk_section(&mut self, section: &ChunkSection<'_>) -> Result<&mut Self, Error> {
+        self.fix_u16(section.block_count)?
+            .fix_u8(section.blocks.bits_per_entry())?;
+        if let Some(palette) = section.palette {
+            self.len_var_i32(palette.len())?;
+            for p in palette.iter() {
+                self.var_i32(*p as i32)?;
+            }
+        }
+        let data = section.blocks.get_inner();
+        self.len_var_i32(data.len())?;
+        for d in data.iter() {
+            self.fix_u64(*d)?;
         }
+        Ok(self)
     }
 }
 
+pub const LIGHT_ARRAY_LEN: usize = 2048;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightSection<'a> {
+    // The client keeps whatever light it already has for the section
+    Unchanged,
+    // The client clears the section, equivalent to an array of zeroes
+    Empty,
+    // One nibble per block
+    Data(&'a [u8]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Packet)]
+#[packet(write)]
 pub enum PlayResponse<'a> {
+    #[packet(id(v1_15 = 0x0e))]
     ServerDifficulty {
-        // 0x0e
+        #[packet(fix_u8)]
         difficulty: Difficulty,
+        #[packet(fix_bool)]
         difficulty_locked: bool,
     },
+    #[packet(id(v1_15 = 0x19))]
     Plugin {
-        // 0x19
+        #[packet(arr_char)]
         channel: &'a str,
+        #[packet(arr_u8)]
         data: &'a [u8],
     },
+    #[packet(id(v1_15 = 0x1b))]
     Disconnect {
-        // 0x1b
+        #[packet(arr_char)]
         reason: &'a str,
     },
+    #[packet(id(v1_15 = 0x1e))]
+    UnloadChunk {
+        #[packet(each(fix_i32))]
+        position: [i32; 2],
+    },
+    #[packet(id(v1_15 = 0x21))]
+    KeepAlive {
+        #[packet(fix_u64)]
+        keep_alive_id: u64,
+    },
+    #[packet(id(v1_15 = 0x22), with = "chunk_data")]
+    ChunkData {
+        position: [i32; 2],
+        // Indexed by section Y, absent sections are not sent
+        sections: &'a [Option<ChunkSection<'a>>],
+        heightmaps: &'a Value,
+        // Only present for full chunks
+        biomes: Option<&'a [i32]>,
+        block_entities: &'a [Value],
+    },
+    #[packet(id(v1_15 = 0x25), with = "update_light")]
+    UpdateLight {
+        position: [i32; 2],
+        // Indexed by section Y + 1, covering the sections immediately below
+        // and above the chunk
+        sky_light: &'a [LightSection<'a>],
+        block_light: &'a [LightSection<'a>],
+    },
+    #[packet(id(v1_15 = 0x26))]
     JoinGame {
-        // 0x26
+        #[packet(fix_u32)]
         entity_id: u32,
+        #[packet(fix_u8)]
         game_mode: GameMode,
+        #[packet(fix_i32)]
         dimension: i32,
+        #[packet(fix_u64)]
         hashed_seed: u64,
+        // Max players, no longer supported
+        #[packet(pad(fix_u8 = 0), arr_char)]
         level_type: &'a str,
+        #[packet(var_i32)]
         view_distance: u8,
+        #[packet(fix_bool)]
         reduce_debug: bool,
+        #[packet(fix_bool)]
         enable_respawn_screen: bool,
     },
+    #[packet(id(v1_15 = 0x36))]
     PlayerPositionAndLook {
-        // 0x36
+        #[packet(each(fix_f64))]
         position: [f64; 3],
+        #[packet(each(fix_f32))]
         look: [f32; 2],
+        #[packet(fix_u8)]
         flags: u8,
+        #[packet(var_i32)]
         teleport_id: i32,
     },
+    #[packet(id(v1_15 = 0x40))]
     HeldItemChange {
-        // 0x40
+        #[packet(fix_u8)]
         slot: u8,
     },
+    #[packet(id(v1_15 = 0x41))]
+    UpdateViewPosition {
+        #[packet(each(var_i32))]
+        position: [i32; 2],
+    },
+}
+
+fn light_masks(sections: &[LightSection<'_>]) -> (i32, i32) {
+    let mut mask = 0i32;
+    let mut empty_mask = 0i32;
+    for (y, section) in sections.iter().enumerate() {
+        match section {
+            LightSection::Unchanged => {}
+            LightSection::Empty => empty_mask |= 1 << y,
+            LightSection::Data(_) => mask |= 1 << y,
+        }
+    }
+    (mask, empty_mask)
 }
 
-impl<'a, W: Write + Unpin> StructuredWriter<W, PlayResponse<'a>> for BinaryWriter<W> {
-    fn structure(&mut self, val: &PlayResponse<'a>) -> Result<&mut Self, Error> {
-        let packet = self.start_packet();
-        match val {
-            PlayResponse::ServerDifficulty {
-                difficulty,
-                difficulty_locked,
-            } => self
-                .var_i32(packet_ids::SERVER_DIFFICULTY)?
-                .fix_u8((*difficulty).into())?
-                .fix_bool(*difficulty_locked)?,
-            PlayResponse::Plugin { channel, data } => self
-                .var_i32(packet_ids::PLUGIN)?
-                .arr_char(channel)?
-                .arr_u8(data)?,
-            PlayResponse::Disconnect { reason } => {
-                self.var_i32(packet_ids::DISCONNECT)?.arr_char(reason)?
+impl<W: Write + Unpin> BinaryWriter<W> {
+    fn chunk_data(
+        &mut self,
+        position: &[i32; 2],
+        sections: &[Option<ChunkSection<'_>>],
+        heightmaps: &Value,
+        biomes: &Option<&[i32]>,
+        block_entities: &[Value],
+    ) -> Result<&mut Self, Error> {
+        let mut primary_bit_mask = 0i32;
+        let mut size = 0usize;
+        for (y, section) in sections.iter().enumerate() {
+            if let Some(section) = section {
+                primary_bit_mask |= 1 << y;
+                size += section.size();
             }
-            PlayResponse::JoinGame {
-                entity_id,
-                game_mode,
-                dimension,
-                hashed_seed,
-                level_type,
-                view_distance,
-                reduce_debug,
-                enable_respawn_screen,
-            } => self
-                .var_i32(packet_ids::JOIN_GAME)?
-                .fix_i32(*entity_id as i32)?
-                .fix_u8((*game_mode).into())?
-                .fix_i32(*dimension)?
-                .fix_u64(*hashed_seed)?
-                .fix_u8(0)? // Max players, no longer supported
-                .arr_char(level_type)?
-                .var_i32(*view_distance as i32)?
-                .fix_bool(*reduce_debug)?
-                .fix_bool(*enable_respawn_screen)?,
-            PlayResponse::PlayerPositionAndLook {
-                position,
-                look,
-                flags,
-                teleport_id,
-            } => self
-                .var_i32(packet_ids::SET_POSITION_AND_LOOK)?
-                .fix_f64(position[0])?
-                .fix_f64(position[1])?
-                .fix_f64(position[2])?
-                .fix_f32(look[0])?
-                .fix_f32(look[1])?
-                .fix_u8(*flags)?
-                .var_i32(*teleport_id)?,
-            PlayResponse::HeldItemChange { slot } => {
-                self.var_i32(packet_ids::HELD_ITEM_CHANGE)?.fix_u8(*slot)?
+        }
+
+        self.fix_i32(position[0])?
+            .fix_i32(position[1])?
+            .fix_bool(biomes.is_some())?
+            .var_i32(primary_bit_mask)?
+            .nbt("", heightmaps)?;
+        if let Some(biomes) = biomes {
+            for biome in biomes.iter() {
+                self.fix_i32(*biome)?;
+            }
+        }
+        self.len_var_i32(size)?;
+        for section in sections.iter().flatten() {
+            self.chunk_section(section)?;
+        }
+        self.len_var_i32(block_entities.len())?;
+        for block_entity in block_entities.iter() {
+            self.nbt("", block_entity)?;
+        }
+        Ok(self)
+    }
+
+    fn update_light(
+        &mut self,
+        position: &[i32; 2],
+        sky_light: &[LightSection<'_>],
+        block_light: &[LightSection<'_>],
+    ) -> Result<&mut Self, Error> {
+        let (sky_light_mask, empty_sky_light_mask) = light_masks(sky_light);
+        let (block_light_mask, empty_block_light_mask) = light_masks(block_light);
+        self.var_i32(position[0])?
+            .var_i32(position[1])?
+            .var_i32(sky_light_mask)?
+            .var_i32(block_light_mask)?
+            .var_i32(empty_sky_light_mask)?
+            .var_i32(empty_block_light_mask)?;
+        for section in sky_light.iter().chain(block_light.iter()) {
+            if let LightSection::Data(data) = section {
+                if data.len() != LIGHT_ARRAY_LEN {
+                    return Err(ErrorKind::InvalidLengthPrefix.into());
+                }
+                self.arr_u8(data)?;
             }
         }
-        .complete_packet(packet)
+        Ok(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{PlayResponse::*, *};
-    use crate::tests::*;
+    use crate::{tests::*, StructuredWriter};
 
     macro_rules! raw_write_tests {
         ($($name:ident, $expected:expr, $writer:ident => $expr:expr;)*) => {
@@ -219,5 +662,241 @@ mod tests {
         binary_writer_play_disconnect, "test-data/play-disconnect-1.in", w => w.structure(&Disconnect{
             reason: "kicked"
         })?;
+        binary_writer_play_unload_chunk, "test-data/play-unload-chunk-1.in", w => w.structure(&UnloadChunk{
+            position: [-2, 3]
+        })?;
+        binary_writer_play_update_view_position, "test-data/play-update-view-position-1.in", w => w.structure(&UpdateViewPosition{
+            position: [-2, 300]
+        })?;
+        binary_writer_play_keep_alive, "test-data/play-keep-alive-1.in", w => w.structure(&PlayResponse::KeepAlive{
+            keep_alive_id: 0x1526_3749_5015_2637
+        })?;
+        binary_writer_play_update_light, "test-data/play-update-light-1.in", w => {
+            let mut sky_light = [LightSection::Unchanged; 18];
+            sky_light[0] = LightSection::Empty;
+            sky_light[1] = LightSection::Data(&[0xff; LIGHT_ARRAY_LEN]);
+            let mut block_light = [LightSection::Unchanged; 18];
+            block_light[1] = LightSection::Data(&[0x12; LIGHT_ARRAY_LEN]);
+            block_light[17] = LightSection::Empty;
+
+            w.structure(&UpdateLight{
+                position: [-2, 3],
+                sky_light: &sky_light,
+                block_light: &block_light,
+            })?
+        };
+    }
+
+    // The expected packet is assembled field by field from the documented
+    // 1.15.2 Chunk Data layout (https://wiki.vg/Chunk_Format), without going
+    // through VarVec or the NBT writer. It is not a capture from a vanilla
+    // server, so it can't catch a misreading of that layout.
+    // TODO: replace it with a captured packet in test-data.
+    #[test]
+    pub fn binary_writer_play_chunk_data() -> Result<(), Error> {
+        fn var_int(buf: &mut Vec<u8>, value: i32) {
+            let mut value = value as u32;
+            loop {
+                if value < 0x80 {
+                    buf.push(value as u8);
+                    return;
+                }
+                buf.push((value & 0x7f) as u8 | 0x80);
+                value >>= 7;
+            }
+        }
+
+        // Entries are packed from the least significant bit and may span
+        // two longs. Both arrays used here fill a whole number of longs.
+        fn pack(values: &[u64], bits: usize) -> Vec<u64> {
+            let mut result = vec![0u64; values.len() * bits / 64];
+            for (i, value) in values.iter().enumerate() {
+                for b in 0..bits {
+                    if value & (1 << b) != 0 {
+                        let bit = i * bits + b;
+                        result[bit / 64] |= 1 << (bit % 64);
+                    }
+                }
+            }
+            result
+        }
+
+        let mut states = vec![0u64; 4096];
+        for i in 0..256 {
+            states[i] = 1;
+            states[i + 256] = 2;
+        }
+        states[4095] = 3;
+        let heights = vec![2u64; 256];
+
+        let mut body = Vec::new();
+        var_int(&mut body, 0x22);
+        body.extend_from_slice(&(-2i32).to_be_bytes());
+        body.extend_from_slice(&3i32.to_be_bytes());
+        // Full chunk, primary bit mask
+        body.push(1);
+        var_int(&mut body, 0b1);
+        // Heightmaps: an unnamed compound with a single long array
+        body.extend_from_slice(&[0x0a, 0x00, 0x00, 0x0c, 0x00, 15]);
+        body.extend_from_slice(b"MOTION_BLOCKING");
+        let packed_heights = pack(&heights, 9);
+        body.extend_from_slice(&(packed_heights.len() as i32).to_be_bytes());
+        for l in packed_heights.iter() {
+            body.extend_from_slice(&l.to_be_bytes());
+        }
+        body.push(0x00);
+        for _ in 0..1024 {
+            body.extend_from_slice(&1i32.to_be_bytes());
+        }
+
+        let mut section = Vec::new();
+        section.extend_from_slice(&513u16.to_be_bytes());
+        section.push(4);
+        var_int(&mut section, 4);
+        for p in [0, 33, 10, 1].iter() {
+            var_int(&mut section, *p);
+        }
+        let packed_states = pack(&states, 4);
+        var_int(&mut section, packed_states.len() as i32);
+        for l in packed_states.iter() {
+            section.extend_from_slice(&l.to_be_bytes());
+        }
+        var_int(&mut body, section.len() as i32);
+        body.extend_from_slice(&section);
+        // No block entities
+        var_int(&mut body, 0);
+
+        let mut expected = Vec::new();
+        var_int(&mut expected, body.len() as i32);
+        expected.extend_from_slice(&body);
+
+        let mut blocks = VarVec::with_capacity(4096, 4);
+        for (i, state) in states.iter().enumerate() {
+            blocks.set(i, *state);
+        }
+        let mut heightmap = VarVec::with_capacity(256, 9);
+        for (i, height) in heights.iter().enumerate() {
+            heightmap.set(i, *height);
+        }
+        let heightmap: Vec<i64> = heightmap.get_inner().iter().map(|l| *l as i64).collect();
+
+        let mut sections = [None; 16];
+        sections[0] = Some(ChunkSection {
+            block_count: 513,
+            palette: Some(&[0, 33, 10, 1]),
+            blocks: &blocks,
+        });
+
+        let mut w = make_writer();
+        w.structure(&ChunkData {
+            position: [-2, 3],
+            sections: &sections,
+            heightmaps: &crate::nbt_compound! {
+                "MOTION_BLOCKING" => Value::LongArray(heightmap[..].into())
+            },
+            biomes: Some(&[1; 1024]),
+            block_entities: &[],
+        })?;
+        assert_eq!(make_buffer(w), expected);
+        Ok(())
+    }
+
+    macro_rules! raw_read_tests {
+        ($($name:ident, $input:expr, $expected:expr;)*) => {
+            $(
+                #[test]
+                pub fn $name() -> Result<(), Error> {
+                    let mut reader = make_reader(include_bytes!($input) as &[u8]);
+                    assert_eq!(block_on(reader.read_play())?, $expected);
+                    Ok(())
+                }
+            )*
+        }
+    }
+
+    raw_read_tests! {
+        binary_reader_play_teleport_confirm, "test-data/play-teleport-confirm-1.in", PlayRequest::TeleportConfirm {
+            teleport_id: 0x1526
+        };
+        binary_reader_play_set_difficulty, "test-data/play-set-difficulty-1.in", PlayRequest::SetDifficulty {
+            difficulty: Difficulty::Medium
+        };
+        binary_reader_play_chat_message, "test-data/play-chat-message-1.in", PlayRequest::ChatMessage {
+            message: "hello world 🎉".into()
+        };
+        binary_reader_play_client_status, "test-data/play-client-status-1.in", PlayRequest::ClientStatus {
+            action: ClientStatusAction::RequestStats
+        };
+        binary_reader_play_client_settings, "test-data/play-client-settings-1.in", PlayRequest::ClientSettings {
+            locale: "en_GB".into(),
+            view_distance: 12,
+            chat_mode: ChatMode::CommandsOnly,
+            chat_colors: true,
+            displayed_skin_parts: 0x7f,
+            main_hand: MainHand::Left,
+        };
+        binary_reader_play_close_window, "test-data/play-close-window-1.in", PlayRequest::CloseWindow {
+            window_id: 3
+        };
+        binary_reader_play_plugin, "test-data/play-plugin-request-1.in", PlayRequest::Plugin {
+            channel: "minecraft:brand".into(),
+            data: (b"vanilla" as &[u8]).into(),
+        };
+        binary_reader_play_keep_alive, "test-data/play-keep-alive-request-1.in", PlayRequest::KeepAlive {
+            keep_alive_id: 0x1526_3749_5015_2637
+        };
+        binary_reader_play_lock_difficulty, "test-data/play-lock-difficulty-1.in", PlayRequest::LockDifficulty {
+            locked: true
+        };
+        binary_reader_play_player_position, "test-data/play-player-position-1.in", PlayRequest::PlayerPosition {
+            position: [1.5, 64.0, -2.25],
+            on_ground: true,
+        };
+        binary_reader_play_player_position_and_look, "test-data/play-player-position-and-look-1.in", PlayRequest::PlayerPositionAndLook {
+            position: [1.5, 64.0, -2.25],
+            look: [90.0, -45.0],
+            on_ground: false,
+        };
+        binary_reader_play_player_look, "test-data/play-player-look-1.in", PlayRequest::PlayerLook {
+            look: [180.0, 12.5],
+            on_ground: true,
+        };
+        binary_reader_play_player_movement, "test-data/play-player-movement-1.in", PlayRequest::PlayerMovement {
+            on_ground: false
+        };
+        binary_reader_play_player_abilities, "test-data/play-player-abilities-1.in", PlayRequest::PlayerAbilities {
+            flags: 0x02,
+            flying_speed: 0.05,
+            walking_speed: 0.1,
+        };
+        binary_reader_play_player_digging, "test-data/play-player-digging-1.in", PlayRequest::PlayerDigging {
+            status: DiggingStatus::Finished,
+            location: [18_357_644, 831, -20_882_616],
+            face: BlockFace::Top,
+        };
+        binary_reader_play_entity_action, "test-data/play-entity-action-1.in", PlayRequest::EntityAction {
+            entity_id: 0x1526,
+            action: EntityActionKind::StartSprinting,
+            jump_boost: 0,
+        };
+        binary_reader_play_held_item_change, "test-data/play-held-item-change-request-1.in", PlayRequest::HeldItemChange {
+            slot: 4
+        };
+        binary_reader_play_animation, "test-data/play-animation-1.in", PlayRequest::Animation {
+            hand: Hand::Off
+        };
+        binary_reader_play_player_block_placement, "test-data/play-player-block-placement-1.in", PlayRequest::PlayerBlockPlacement {
+            hand: Hand::Main,
+            location: [-1, 64, 1],
+            face: BlockFace::East,
+            cursor_position: [0.5, 1.0, 0.25],
+            inside_block: false,
+        };
+        binary_reader_play_use_item, "test-data/play-use-item-1.in", PlayRequest::UseItem {
+            hand: Hand::Off
+        };
+        binary_reader_play_unknown, "test-data/play-held-item-change-1.in", PlayRequest::Unknown {
+            packet_id: 0x40
+        };
     }
 }
@@ -66,6 +66,27 @@ impl<R: Read + Unpin> BinaryReader<R> {
         Ok(data)
     }
 
+    #[inline]
+    #[allow(dead_code)]
+    pub(crate) async fn arr_u8_remainder(
+        &mut self,
+        max: Option<usize>,
+    ) -> Result<Arc<[u8]>, Error> {
+        let count = match self.remaining() {
+            Some(r) => r,
+            None => return Err(ErrorKind::InvalidLengthPrefix.into()),
+        };
+        if let Some(max) = max {
+            if count > max {
+                return Err(ErrorKind::InvalidLengthPrefix.into());
+            }
+        }
+
+        let data: Arc<[u8]> = self.data(count).await?.into();
+        self.consume(count);
+        Ok(data)
+    }
+
     #[inline]
     #[allow(dead_code)]
     pub(crate) async fn arr_char(&mut self, max: Option<usize>) -> Result<Arc<str>, Error> {
@@ -80,6 +101,17 @@ impl<R: Read + Unpin> BinaryReader<R> {
             Err(e) => Err(ErrorKind::InvalidString(e).into()),
         }
     }
+
+    #[inline]
+    #[allow(dead_code)]
+    pub(crate) async fn fix_position(&mut self) -> Result<[i32; 3], Error> {
+        // x: 26 bits, z: 26 bits, y: 12 bits, all sign-extended
+        let val = self.fix_i64().await?;
+        let x = (val >> 38) as i32;
+        let y = ((val << 52) >> 52) as i32;
+        let z = ((val << 26) >> 38) as i32;
+        Ok([x, y, z])
+    }
 }
 
 #[cfg(test)]
@@ -150,6 +182,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    pub fn binary_reader_arr_u8_remainder() -> Result<(), Error> {
+        let mut reader = make_reader(b"\x01\x02\x03\x04");
+        reader.with_size(Some(3));
+        assert_eq!(
+            block_on(reader.arr_u8_remainder(None))?,
+            (b"\x01\x02\x03" as &[u8]).into()
+        );
+        assert_eq!(reader.remaining(), Some(0));
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn binary_reader_arr_u8_remainder_long() -> Result<(), Error> {
+        let mut reader = make_reader(b"\x01\x02\x03\x04");
+        reader.with_size(Some(3));
+        match block_on(reader.arr_u8_remainder(Some(2))) {
+            Ok(r) => assert_ne!(r, r),
+            Err(e) => match e.kind() {
+                ErrorKind::InvalidLengthPrefix => {}
+                _ => return Err(e),
+            },
+        }
+
+        Ok(())
+    }
+
     macro_rules! raw_read_tests {
         ($($name:ident, $input:expr, $reader:ident => { $($expr:expr, $expected:expr;)* };)*) => {
             $(
@@ -174,5 +234,10 @@ mod tests {
             r.arr_u8(None), (b"12345" as &[u8]).into();
             r.arr_u8(None), (b"567890" as &[u8]).into();
         };
+        binary_reader_fix_position, "test-data/fix-position-1.in", r => {
+            r.fix_position(), [18_357_644, 831, -20_882_616];
+            r.fix_position(), [-1, -1, -1];
+            r.fix_position(), [0, 0, 0];
+        };
     }
 }
@@ -1,81 +1,71 @@
-use crate::{
-    proto::packet_ids::status as packet_ids, writer::StructuredWriter, BinaryReader, BinaryWriter,
-    Error,
-};
-use async_std::io::{Read, Write};
+use crate::{proto::ProtocolVersion, BinaryWriter, Error};
+use async_std::io::Write;
+use racemus_proto_derive::Packet;
 use serde_json::json;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Packet)]
+#[packet(read = "read_status")]
 pub enum StatusRequest {
+    #[packet(id = 0x00)]
     InfoRequest,
-    Ping { timestamp: u64 },
+    #[packet(id = 0x01)]
+    Ping {
+        #[packet(fix_u64)]
+        timestamp: u64,
+    },
+    #[packet(unknown)]
     Unknown { packet_id: i32 },
 }
 
-impl<R: Read + Unpin> BinaryReader<R> {
-    pub async fn read_status(&mut self) -> Result<StatusRequest, Error> {
-        let packet_id = self.packet_header().await?;
-        match packet_id {
-            packet_ids::INFO_REQUEST => Ok(StatusRequest::InfoRequest),
-            packet_ids::PING => {
-                let timestamp = self.fix_u64().await?;
-                Ok(StatusRequest::Ping { timestamp })
-            }
-            _ => Ok(StatusRequest::Unknown { packet_id }),
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Packet)]
+#[packet(write)]
 pub enum StatusResponse<'a> {
+    #[packet(id = 0x00, with = "info_response")]
     InfoResponse {
+        // The protocol number that is advertised, clients that don't match it
+        // show the supported range as incompatible
+        protocol_version: i32,
         max_players: u16,
         current_players: u16,
         description: &'a str,
     },
+    #[packet(id = 0x01)]
     Pong {
+        #[packet(fix_u64)]
         timestamp: u64,
     },
 }
 
-impl<'a, W: Write + Unpin> StructuredWriter<W, StatusResponse<'a>> for BinaryWriter<W> {
-    fn structure(&mut self, val: &StatusResponse) -> Result<&mut Self, Error> {
-        let packet = self.start_packet();
-        match val {
-            StatusResponse::InfoResponse {
-                max_players,
-                current_players,
-                description,
-            } => {
-                let response = json!({
-                    "version": {
-                        "name": crate::SERVER_VERSION,
-                        "protocol": crate::SERVER_VERSION_NUMBER
-                    },
-                    "players": {
-                        "max": max_players,
-                        "online": current_players
-                    },
-                    "description": {
-                        "text": description
-                    }
-                });
-                let response = serde_json::to_string(&response).unwrap();
-                self.var_i32(packet_ids::INFO_RESPONSE)?
-                    .arr_char(&response)?
+impl<W: Write + Unpin> BinaryWriter<W> {
+    fn info_response(
+        &mut self,
+        protocol_version: &i32,
+        max_players: &u16,
+        current_players: &u16,
+        description: &str,
+    ) -> Result<&mut Self, Error> {
+        let response = json!({
+            "version": {
+                "name": ProtocolVersion::supported_range(),
+                "protocol": protocol_version
+            },
+            "players": {
+                "max": max_players,
+                "online": current_players
+            },
+            "description": {
+                "text": description
             }
-            StatusResponse::Pong { timestamp } => {
-                self.var_i32(packet_ids::PONG)?.fix_u64(*timestamp)?
-            }
-        }
-        .complete_packet(packet)
+        });
+        let response = serde_json::to_string(&response).unwrap();
+        self.arr_char(&response)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{StatusRequest::*, StatusResponse::*, *};
-    use crate::tests::*;
+    use crate::{tests::*, StructuredWriter};
 
     macro_rules! raw_write_tests {
         ($($name:ident, $expected:expr, $writer:ident => $expr:expr;)*) => {
@@ -97,6 +87,7 @@ mod tests {
             timestamp: 0x1526_3749_5015_2637
         })?;
         binary_writer_status_info_response, "test-data/status-info-response-1.in", w => w.structure(&InfoResponse {
+            protocol_version: crate::SERVER_VERSION_NUMBER,
             max_players: 50,
             current_players: 21,
             description: "Welcome!"
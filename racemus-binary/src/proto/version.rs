@@ -0,0 +1,60 @@
+// Groups of protocol versions that share packet ids and layouts, in release
+// order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProtocolVersion {
+    // The version of crate::SERVER_VERSION_NUMBER
+    V1_15,
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> Self {
+        ProtocolVersion::V1_15
+    }
+}
+
+impl ProtocolVersion {
+    pub const ALL: &'static [ProtocolVersion] = &[ProtocolVersion::V1_15];
+
+    // The releases that use the version, with their protocol numbers
+    pub fn releases(self) -> &'static [(&'static str, i32)] {
+        match self {
+            ProtocolVersion::V1_15 => &[("1.15.1", 575), ("1.15.2", 578)],
+        }
+    }
+
+    // Returns None if the version is not supported
+    pub fn from_number(number: i32) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|v| v.releases().iter().any(|(_, n)| *n == number))
+    }
+
+    // The names of the oldest and newest supported releases, e.g.
+    // "1.15.1-1.15.2"
+    pub fn supported_range() -> String {
+        let oldest = Self::ALL[0].releases()[0].0;
+        let newest = Self::ALL[Self::ALL.len() - 1].releases();
+        format!("{}-{}", oldest, newest[newest.len() - 1].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn protocol_version_from_number() {
+        assert_eq!(
+            ProtocolVersion::from_number(crate::SERVER_VERSION_NUMBER),
+            Some(ProtocolVersion::default())
+        );
+        assert_eq!(
+            ProtocolVersion::from_number(575),
+            Some(ProtocolVersion::V1_15)
+        );
+        assert_eq!(ProtocolVersion::from_number(736), None);
+        assert_eq!(ProtocolVersion::from_number(751), None);
+        assert_eq!(ProtocolVersion::supported_range(), "1.15.1-1.15.2");
+    }
+}
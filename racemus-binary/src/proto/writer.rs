@@ -3,6 +3,17 @@ use async_std::io::Write;
 
 const MAX_LEN: usize = (std::i32::MAX as u32) as usize;
 
+#[inline]
+pub(crate) fn var_i32_size(val: i32) -> usize {
+    match val as u32 {
+        0x0000_0000..=0x0000_007f => 1,
+        0x0000_0080..=0x0000_3fff => 2,
+        0x0000_4000..=0x001f_ffff => 3,
+        0x0020_0000..=0x0fff_ffff => 4,
+        _ => 5,
+    }
+}
+
 impl<W: Write + Unpin> BinaryWriter<W> {
     #[inline]
     pub(crate) fn len_var_i32(&mut self, val: usize) -> Result<&mut Self, Error> {
@@ -23,6 +34,16 @@ impl<W: Write + Unpin> BinaryWriter<W> {
         self.arr_u8(val.as_bytes())
     }
 
+    #[inline]
+    #[allow(dead_code)]
+    pub(crate) fn fix_position(&mut self, val: [i32; 3]) -> Result<&mut Self, Error> {
+        // x: 26 bits, z: 26 bits, y: 12 bits
+        let x = (val[0] as i64 & 0x3ff_ffff) << 38;
+        let z = (val[2] as i64 & 0x3ff_ffff) << 12;
+        let y = val[1] as i64 & 0xfff;
+        self.fix_i64(x | z | y)
+    }
+
     pub(crate) fn start_packet(&mut self) -> PacketInsertion {
         if self.compression_allowed() {
             PacketInsertion {
@@ -122,6 +143,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    pub fn binary_writer_var_i32_size() -> Result<(), Error> {
+        for val in &[
+            0,
+            1,
+            0x7f,
+            0x80,
+            0x3fff,
+            0x4000,
+            0x7fff_ffff,
+            -1,
+            -0x8000_0000,
+        ] {
+            let mut writer = make_writer();
+            writer.var_i32(*val)?;
+            let buf = make_buffer(writer);
+            assert_eq!((val, var_i32_size(*val)), (val, buf.len()));
+        }
+
+        Ok(())
+    }
+
     macro_rules! raw_write_tests {
         ($($name:ident, $expected:expr, $writer:ident => $expr:expr;)*) => {
             $(
@@ -144,5 +187,9 @@ mod tests {
         binary_writer_arr_u8, "test-data/arr-u8-1.in", w => w
             .arr_u8(b"12345")?
             .arr_u8(b"567890")?;
+        binary_writer_fix_position, "test-data/fix-position-1.in", w => w
+            .fix_position([18_357_644, 831, -20_882_616])?
+            .fix_position([-1, -1, -1])?
+            .fix_position([0, 0, 0])?;
     }
 }
@@ -1,4 +1,4 @@
-use crate::{AesCfb8, Error, ErrorKind};
+use crate::{proto::ProtocolVersion, AesCfb8, Error, ErrorKind};
 use async_std::io::{prelude::*, Read};
 use cfb8::stream_cipher::StreamCipher;
 use flate2::read::ZlibDecoder;
@@ -12,6 +12,7 @@ pub struct BinaryReader<R: Read + Unpin> {
     reader: R,
     cipher: Option<AesCfb8>,
     allow_compression: bool,
+    protocol_version: ProtocolVersion,
 }
 
 macro_rules! build_read_varint {
@@ -59,6 +60,7 @@ impl<R: Read + Unpin> BinaryReader<R> {
             current_len: None,
             reader,
             cipher: None,
+            protocol_version: ProtocolVersion::default(),
         }
     }
 
@@ -108,6 +110,16 @@ impl<R: Read + Unpin> BinaryReader<R> {
         self.allow_compression
     }
 
+    #[inline]
+    pub fn set_protocol_version(&mut self, version: ProtocolVersion) {
+        self.protocol_version = version;
+    }
+
+    #[inline]
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
     #[inline]
     pub(crate) async fn decompress(
         &mut self,
@@ -191,6 +203,12 @@ impl<R: Read + Unpin> BinaryReader<R> {
         Ok(())
     }
 
+    #[inline]
+    #[allow(dead_code)]
+    pub(crate) async fn fix_bool(&mut self) -> Result<bool, Error> {
+        Ok(self.fix_u8().await? != 0)
+    }
+
     #[inline]
     pub(crate) fn with_size(&mut self, count: Option<usize>) {
         self.current_len = count;
@@ -326,6 +344,10 @@ mod tests {
     }
 
     raw_read_tests! {
+        binary_reader_fix_bool, "test-data/fix-bool-1.in", r => {
+            r.fix_bool(), false;
+            r.fix_bool(), true;
+        };
         binary_reader_fix_unsigned, "test-data/fix-unsigned-1.in", r => {
             r.fix_u8(), 0x15;
             r.fix_u16(), 0x1526;
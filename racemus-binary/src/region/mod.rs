@@ -0,0 +1,181 @@
+mod reader;
+mod writer;
+
+pub use reader::*;
+pub use writer::*;
+
+use crate::{Error, ErrorKind};
+use std::path::PathBuf;
+
+// Region files hold 32x32 chunks
+pub const REGION_WIDTH: usize = 32;
+pub const REGION_CHUNKS: usize = REGION_WIDTH * REGION_WIDTH;
+pub const SECTOR_SIZE: usize = 4096;
+// The location table and the timestamp table each take a sector
+pub const HEADER_SECTORS: usize = 2;
+
+const COMPRESSION_GZIP: u8 = 1;
+const COMPRESSION_ZLIB: u8 = 2;
+// Set on the compression type when the chunk is stored in an external file
+const COMPRESSION_EXTERNAL: u8 = 0x80;
+
+// The sector count is stored in a single byte
+const MAX_CHUNK_SECTORS: usize = 255;
+
+#[inline]
+fn chunk_index(x: usize, z: usize) -> Result<usize, Error> {
+    if x < REGION_WIDTH && z < REGION_WIDTH {
+        Ok(z * REGION_WIDTH + x)
+    } else {
+        Err(ErrorKind::InvalidChunkPosition.into())
+    }
+}
+
+// Chunks that don't fit in the region file are stored next to it, in
+// c.<x>.<z>.mcc where x and z are the world coordinates of the chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalChunks {
+    directory: PathBuf,
+    region: [i32; 2],
+}
+
+impl ExternalChunks {
+    pub fn new(directory: impl Into<PathBuf>, region: [i32; 2]) -> Self {
+        Self {
+            directory: directory.into(),
+            region,
+        }
+    }
+
+    // The path of the chunk at the region-local coordinates
+    pub fn path(&self, x: usize, z: usize) -> PathBuf {
+        let width = REGION_WIDTH as i32;
+        self.directory.join(format!(
+            "c.{}.{}.mcc",
+            self.region[0] * width + x as i32,
+            self.region[1] * width + z as i32
+        ))
+    }
+}
+
+// Where a chunk is stored in the region file, in sectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLocation {
+    pub offset: u32,
+    pub count: u8,
+}
+
+impl ChunkLocation {
+    fn from_raw(value: u32) -> Option<Self> {
+        if value == 0 {
+            None
+        } else {
+            Some(Self {
+                offset: value >> 8,
+                count: value as u8,
+            })
+        }
+    }
+
+    #[inline]
+    fn to_raw(self) -> u32 {
+        (self.offset << 8) | u32::from(self.count)
+    }
+
+    #[inline]
+    fn sectors(self) -> std::ops::Range<usize> {
+        self.offset as usize..(self.offset as usize + self.count as usize)
+    }
+}
+
+#[derive(Clone)]
+pub struct RegionHeader {
+    locations: Box<[u32]>,
+    timestamps: Box<[u32]>,
+}
+
+impl Default for RegionHeader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for RegionHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let count = self.locations.iter().filter(|l| **l != 0).count();
+        write!(f, "RegionHeader {{ chunks: {} }}", count)
+    }
+}
+
+impl RegionHeader {
+    pub fn new() -> Self {
+        Self {
+            locations: vec![0; REGION_CHUNKS].into_boxed_slice(),
+            timestamps: vec![0; REGION_CHUNKS].into_boxed_slice(),
+        }
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        debug_assert!(data.len() >= HEADER_SECTORS * SECTOR_SIZE);
+        let mut result = Self::new();
+        for i in 0..REGION_CHUNKS {
+            let location = &data[i * 4..i * 4 + 4];
+            let timestamp = &data[SECTOR_SIZE + i * 4..SECTOR_SIZE + i * 4 + 4];
+            result.locations[i] =
+                u32::from_be_bytes([location[0], location[1], location[2], location[3]]);
+            result.timestamps[i] =
+                u32::from_be_bytes([timestamp[0], timestamp[1], timestamp[2], timestamp[3]]);
+        }
+        result
+    }
+
+    #[inline]
+    pub fn location(&self, x: usize, z: usize) -> Result<Option<ChunkLocation>, Error> {
+        Ok(ChunkLocation::from_raw(self.locations[chunk_index(x, z)?]))
+    }
+
+    #[inline]
+    pub fn contains(&self, x: usize, z: usize) -> Result<bool, Error> {
+        Ok(self.location(x, z)?.is_some())
+    }
+
+    #[inline]
+    pub fn set_location(
+        &mut self,
+        x: usize,
+        z: usize,
+        location: Option<ChunkLocation>,
+    ) -> Result<(), Error> {
+        self.locations[chunk_index(x, z)?] = location.map_or(0, ChunkLocation::to_raw);
+        Ok(())
+    }
+
+    // Seconds since the UNIX epoch when the chunk was last saved
+    #[inline]
+    pub fn timestamp(&self, x: usize, z: usize) -> Result<u32, Error> {
+        Ok(self.timestamps[chunk_index(x, z)?])
+    }
+
+    #[inline]
+    pub fn set_timestamp(&mut self, x: usize, z: usize, timestamp: u32) -> Result<(), Error> {
+        self.timestamps[chunk_index(x, z)?] = timestamp;
+        Ok(())
+    }
+
+    pub fn locations(&self) -> impl Iterator<Item = ChunkLocation> + '_ {
+        self.locations
+            .iter()
+            .filter_map(|l| ChunkLocation::from_raw(*l))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(HEADER_SECTORS * SECTOR_SIZE);
+        for location in self.locations.iter() {
+            result.extend_from_slice(&location.to_be_bytes());
+        }
+        for timestamp in self.timestamps.iter() {
+            result.extend_from_slice(&timestamp.to_be_bytes());
+        }
+        result
+    }
+}
@@ -0,0 +1,223 @@
+use super::*;
+use crate::{
+    nbt::{NbtCompression, NbtLimits, Value},
+    BinaryReader, Error, ErrorKind,
+};
+use async_std::io::{prelude::*, Cursor, Read, Seek, SeekFrom};
+use std::{marker::Unpin, sync::Arc};
+
+pub struct RegionReader<R: Read + Seek + Unpin> {
+    reader: R,
+    header: RegionHeader,
+    external: Option<ExternalChunks>,
+}
+
+impl<R: Read + Seek + Unpin> RegionReader<R> {
+    pub async fn new(reader: R) -> Result<Self, Error> {
+        let mut reader = reader;
+        let mut header = vec![0u8; HEADER_SECTORS * SECTOR_SIZE];
+        reader.seek(SeekFrom::Start(0)).await?;
+        reader.read_exact(&mut header).await?;
+        Ok(Self {
+            reader,
+            header: RegionHeader::from_bytes(&header),
+            external: None,
+        })
+    }
+
+    // Allows chunks that are stored outside of the region file to be read
+    pub fn with_external_chunks(mut self, external: ExternalChunks) -> Self {
+        self.external = Some(external);
+        self
+    }
+
+    #[inline]
+    pub fn header(&self) -> &RegionHeader {
+        &self.header
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // Returns the decompressed NBT of the chunk at the region-local
+    // coordinates, or None if the chunk has not been saved.
+    pub async fn chunk_data(&mut self, x: usize, z: usize) -> Result<Option<Vec<u8>>, Error> {
+        let location = match self.header.location(x, z)? {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+        if (location.offset as usize) < HEADER_SECTORS {
+            return Err(ErrorKind::InvalidRegion.into());
+        }
+
+        let start = location.offset as u64 * SECTOR_SIZE as u64;
+        let mut prefix = [0u8; 5];
+        self.reader.seek(SeekFrom::Start(start)).await?;
+        self.reader.read_exact(&mut prefix).await?;
+
+        // The length includes the compression type
+        let length = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+        if length == 0 || length + 4 > location.count as usize * SECTOR_SIZE {
+            return Err(ErrorKind::InvalidRegion.into());
+        }
+
+        let compression = match prefix[4] & !COMPRESSION_EXTERNAL {
+            COMPRESSION_GZIP => NbtCompression::Gzip,
+            COMPRESSION_ZLIB => NbtCompression::Zlib,
+            compression => return Err(ErrorKind::InvalidEnumValue(compression.into()).into()),
+        };
+        let compressed = if prefix[4] & COMPRESSION_EXTERNAL != 0 {
+            let external = match &self.external {
+                Some(r) => r,
+                None => return Err(ErrorKind::MissingExternalChunk.into()),
+            };
+            async_std::fs::read(external.path(x, z)).await?
+        } else {
+            let mut compressed = vec![0u8; length - 1];
+            self.reader.read_exact(&mut compressed).await?;
+            compressed
+        };
+        Ok(Some(compression.decompress(&compressed)?))
+    }
+
+    pub async fn chunk(&mut self, x: usize, z: usize) -> Result<Option<(Arc<str>, Value)>, Error> {
+        match self.chunk_data(x, z).await? {
+            Some(data) => {
+                // Chunks with many entities are larger than the network limits
+                let mut reader = BinaryReader::new(Cursor::new(data));
+                Ok(Some(reader.nbt_with_limits(NbtLimits::trusted()).await?))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    fn make_region_reader() -> RegionReader<Cursor<Vec<u8>>> {
+        let data = include_bytes!("test-data/region-1.in").to_vec();
+        block_on(RegionReader::new(Cursor::new(data))).unwrap()
+    }
+
+    #[test]
+    pub fn region_header() -> Result<(), Error> {
+        let reader = make_region_reader();
+        let header = reader.header();
+        assert_eq!(
+            header.location(0, 0)?,
+            Some(ChunkLocation {
+                offset: 2,
+                count: 1
+            })
+        );
+        assert_eq!(header.timestamp(0, 0)?, 1_577_836_800);
+        assert!(header.contains(31, 1)?);
+        assert!(!header.contains(1, 31)?);
+        assert_eq!(header.timestamp(1, 31)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    pub fn region_reader_chunk() -> Result<(), Error> {
+        let mut reader = make_region_reader();
+        // Zlib compressed
+        assert_eq!(
+            block_on(reader.chunk(0, 0))?,
+            Some((
+                ""[..].into(),
+                crate::nbt_compound! {
+                    "xPos" => Value::Int(0),
+                    "zPos" => Value::Int(0)
+                }
+            ))
+        );
+        // Gzip compressed
+        assert_eq!(
+            block_on(reader.chunk(31, 1))?,
+            Some((
+                ""[..].into(),
+                crate::nbt_compound! {
+                    "xPos" => Value::Int(31),
+                    "zPos" => Value::Int(1)
+                }
+            ))
+        );
+        assert_eq!(block_on(reader.chunk(5, 5))?, None);
+        Ok(())
+    }
+
+    #[test]
+    pub fn region_reader_invalid() -> Result<(), Error> {
+        let mut data = include_bytes!("test-data/region-1.in").to_vec();
+        // Point the first chunk into the header
+        data[2] = 0;
+        let mut reader = block_on(RegionReader::new(Cursor::new(data)))?;
+        match block_on(reader.chunk_data(0, 0)) {
+            Ok(_) => panic!("expected error"),
+            Err(e) => match e.kind() {
+                ErrorKind::InvalidRegion => {}
+                _ => return Err(e),
+            },
+        }
+
+        // Coordinates outside of the region don't wrap around to another
+        // chunk
+        let mut reader = make_region_reader();
+        match block_on(reader.chunk_data(32, 0)) {
+            Ok(_) => panic!("expected error"),
+            Err(e) => match e.kind() {
+                ErrorKind::InvalidChunkPosition => {}
+                _ => return Err(e),
+            },
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn region_reader_external() -> Result<(), Error> {
+        let directory = std::env::temp_dir().join(format!("racemus-region-{}", std::process::id()));
+        std::fs::create_dir_all(&directory)?;
+
+        // Only the compression type is kept in the region
+        let mut data = include_bytes!("test-data/region-1.in").to_vec();
+        let start = 2 * SECTOR_SIZE;
+        let prefix = [0, 0, 0, 1, COMPRESSION_ZLIB | COMPRESSION_EXTERNAL];
+        data[start..start + 5].copy_from_slice(&prefix);
+        let compressed = block_on(crate::nbt::encode_nbt(
+            "",
+            &crate::nbt_compound! { "xPos" => Value::Int(-32) },
+            NbtCompression::Zlib,
+        ))?;
+        std::fs::write(directory.join("c.-32.64.mcc"), &compressed)?;
+
+        let reader = block_on(RegionReader::new(Cursor::new(data)))?;
+        let mut reader = reader.with_external_chunks(ExternalChunks::new(&directory, [-1, 2]));
+        let result = block_on(reader.chunk(0, 0));
+        std::fs::remove_dir_all(&directory)?;
+        assert_eq!(
+            result?,
+            Some((
+                ""[..].into(),
+                crate::nbt_compound! { "xPos" => Value::Int(-32) }
+            ))
+        );
+
+        let mut data = include_bytes!("test-data/region-1.in").to_vec();
+        data[start + 4] |= COMPRESSION_EXTERNAL;
+        let mut reader = block_on(RegionReader::new(Cursor::new(data)))?;
+        match block_on(reader.chunk_data(0, 0)) {
+            Ok(_) => panic!("expected error"),
+            Err(e) => match e.kind() {
+                ErrorKind::MissingExternalChunk => {}
+                _ => return Err(e),
+            },
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,485 @@
+use super::*;
+use crate::{
+    nbt::{self, NbtCompression, Value},
+    Error, ErrorKind,
+};
+use async_std::{
+    fs::{File, OpenOptions},
+    io::{prelude::*, SeekFrom},
+};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+// Chunks are always written to free sectors, so the file on disk stays
+// consistent until the header is replaced. The header is only replaced when
+// flushed, by writing a copy of the region with the new header and renaming
+// it over the original. Sectors that were in use are only reused after that.
+pub struct RegionWriter {
+    path: PathBuf,
+    file: File,
+    external: ExternalChunks,
+    header: RegionHeader,
+    used: Vec<bool>,
+    freed: Vec<ChunkLocation>,
+    // External files that are no longer referenced once the header is
+    // replaced
+    stale: Vec<(usize, usize)>,
+    dirty: bool,
+}
+
+impl RegionWriter {
+    // Opens the region file at the path, or creates a new region if it
+    // doesn't exist. Oversized chunks are stored in the same directory.
+    pub async fn open(path: impl Into<PathBuf>, region: [i32; 2]) -> Result<Self, Error> {
+        let path = path.into();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+            .await?;
+        let length = file.seek(SeekFrom::End(0)).await?;
+
+        let (header, dirty) = if length == 0 {
+            (RegionHeader::new(), true)
+        } else if length < (HEADER_SECTORS * SECTOR_SIZE) as u64 {
+            return Err(ErrorKind::InvalidRegion.into());
+        } else {
+            let mut data = vec![0u8; HEADER_SECTORS * SECTOR_SIZE];
+            file.seek(SeekFrom::Start(0)).await?;
+            file.read_exact(&mut data).await?;
+            (RegionHeader::from_bytes(&data), false)
+        };
+
+        let mut used = vec![true; HEADER_SECTORS];
+        for location in header.locations() {
+            let sectors = location.sectors();
+            if sectors.start < HEADER_SECTORS {
+                return Err(ErrorKind::InvalidRegion.into());
+            }
+            if used.len() < sectors.end {
+                used.resize(sectors.end, false);
+            }
+            for sector in sectors {
+                used[sector] = true;
+            }
+        }
+
+        let directory = path.parent().unwrap_or_else(|| Path::new("")).to_owned();
+        Ok(Self {
+            path,
+            file,
+            external: ExternalChunks::new(directory, region),
+            header,
+            used,
+            freed: Vec::new(),
+            stale: Vec::new(),
+            dirty,
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &RegionHeader {
+        &self.header
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub async fn write_chunk(
+        &mut self,
+        x: usize,
+        z: usize,
+        name: &str,
+        value: &Value,
+        timestamp: u32,
+    ) -> Result<(), Error> {
+        let compressed = nbt::encode_nbt(name, value, NbtCompression::Zlib).await?;
+        self.write_chunk_data(x, z, COMPRESSION_ZLIB, &compressed, timestamp)
+            .await
+    }
+
+    // Writes already compressed chunk data.
+    pub async fn write_chunk_data(
+        &mut self,
+        x: usize,
+        z: usize,
+        compression: u8,
+        data: &[u8],
+        timestamp: u32,
+    ) -> Result<(), Error> {
+        chunk_index(x, z)?;
+
+        // The length includes the compression type
+        let mut length = data.len() + 1;
+        let mut count = (length + 4 + SECTOR_SIZE - 1) / SECTOR_SIZE;
+        let mut compression = compression;
+        let mut data = data;
+        if count > MAX_CHUNK_SECTORS {
+            // Vanilla keeps only the compression type in the region
+            write_file(&self.external.path(x, z), data).await?;
+            length = 1;
+            count = 1;
+            compression |= COMPRESSION_EXTERNAL;
+            data = &[];
+        } else {
+            self.stale.push((x, z));
+        }
+
+        let location = ChunkLocation {
+            offset: self.allocate(count) as u32,
+            count: count as u8,
+        };
+
+        let mut buffer = Vec::with_capacity(count * SECTOR_SIZE);
+        buffer.extend_from_slice(&(length as u32).to_be_bytes());
+        buffer.push(compression);
+        buffer.extend_from_slice(data);
+        buffer.resize(count * SECTOR_SIZE, 0);
+        self.file
+            .seek(SeekFrom::Start(location.offset as u64 * SECTOR_SIZE as u64))
+            .await?;
+        self.file.write_all(&buffer).await?;
+
+        if let Some(previous) = self.header.location(x, z)? {
+            self.freed.push(previous);
+        }
+        self.header.set_location(x, z, Some(location))?;
+        self.header.set_timestamp(x, z, timestamp)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn remove_chunk(&mut self, x: usize, z: usize) -> Result<(), Error> {
+        if let Some(previous) = self.header.location(x, z)? {
+            self.freed.push(previous);
+            self.stale.push((x, z));
+            self.header.set_location(x, z, None)?;
+            self.header.set_timestamp(x, z, 0)?;
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    // Replaces the header and makes the sectors of replaced chunks
+    // available.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        // The chunks have to be on disk before the header refers to them
+        self.file.sync_all().await?;
+        let temporary = temporary_path(&self.path);
+        async_std::fs::copy(&self.path, &temporary).await?;
+        let mut file = OpenOptions::new().write(true).open(&temporary).await?;
+        file.write_all(&self.header.to_bytes()).await?;
+        file.sync_all().await?;
+        drop(file);
+        async_std::fs::rename(&temporary, &self.path).await?;
+
+        // The old file was replaced, so later chunks go to the new one
+        self.file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.path)
+            .await?;
+        self.dirty = false;
+
+        for (x, z) in self.stale.drain(..) {
+            if !is_external(&mut self.file, &self.header, x, z).await? {
+                match async_std::fs::remove_file(self.external.path(x, z)).await {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+        }
+        for location in self.freed.drain(..) {
+            for sector in location.sectors() {
+                self.used[sector] = false;
+            }
+        }
+        Ok(())
+    }
+
+    // Finds the first run of free sectors that is large enough, or appends
+    // sectors to the end of the file.
+    fn allocate(&mut self, count: usize) -> usize {
+        let mut start = HEADER_SECTORS;
+        let mut run = 0;
+        for (sector, used) in self.used.iter().enumerate().skip(HEADER_SECTORS) {
+            if *used {
+                start = sector + 1;
+                run = 0;
+            } else {
+                run += 1;
+                if run == count {
+                    break;
+                }
+            }
+        }
+
+        if start + count > self.used.len() {
+            self.used.resize(start + count, false);
+        }
+        for used in &mut self.used[start..start + count] {
+            *used = true;
+        }
+        start
+    }
+}
+
+fn temporary_path(path: &Path) -> OsString {
+    let mut result = path.as_os_str().to_owned();
+    result.push(".tmp");
+    result
+}
+
+// Replaces the file so that readers never see a partially written chunk
+async fn write_file(path: &Path, data: &[u8]) -> Result<(), Error> {
+    let temporary = temporary_path(path);
+    let mut file = File::create(&temporary).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    drop(file);
+    async_std::fs::rename(&temporary, path).await?;
+    Ok(())
+}
+
+// Whether the chunk that the header refers to is stored externally
+async fn is_external(
+    file: &mut File,
+    header: &RegionHeader,
+    x: usize,
+    z: usize,
+) -> Result<bool, Error> {
+    let location = match header.location(x, z)? {
+        Some(r) => r,
+        None => return Ok(false),
+    };
+    let mut compression = [0u8; 1];
+    file.seek(SeekFrom::Start(
+        location.offset as u64 * SECTOR_SIZE as u64 + 4,
+    ))
+    .await?;
+    file.read_exact(&mut compression).await?;
+    Ok(compression[0] & COMPRESSION_EXTERNAL != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    fn chunk(x: i32, z: i32) -> Value {
+        crate::nbt_compound! {
+            "xPos" => Value::Int(x),
+            "zPos" => Value::Int(z)
+        }
+    }
+
+    // Each test uses its own directory, which is removed when dropped
+    struct TestDirectory(PathBuf);
+
+    impl TestDirectory {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "racemus-region-{}-{}",
+                name,
+                std::process::id()
+            ));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn region(&self) -> PathBuf {
+            self.0.join("r.0.0.mca")
+        }
+    }
+
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn read_back(path: &Path) -> Result<RegionReader<File>, Error> {
+        let directory = path.parent().unwrap();
+        let reader = block_on(RegionReader::new(block_on(File::open(path))?))?;
+        Ok(reader.with_external_chunks(ExternalChunks::new(directory, [0, 0])))
+    }
+
+    #[test]
+    pub fn region_writer_write() -> Result<(), Error> {
+        let directory = TestDirectory::new("write");
+        let mut writer = block_on(RegionWriter::open(directory.region(), [0, 0]))?;
+        block_on(writer.write_chunk(0, 0, "", &chunk(0, 0), 100))?;
+        block_on(writer.write_chunk(31, 1, "", &chunk(31, 1), 200))?;
+        block_on(writer.flush())?;
+
+        assert_eq!(
+            std::fs::metadata(directory.region())?.len(),
+            4 * SECTOR_SIZE as u64
+        );
+        assert!(!Path::new(&temporary_path(&directory.region())).exists());
+
+        let mut reader = read_back(&directory.region())?;
+        assert_eq!(reader.header().timestamp(31, 1)?, 200);
+        assert_eq!(
+            block_on(reader.chunk(0, 0))?,
+            Some((""[..].into(), chunk(0, 0)))
+        );
+        assert_eq!(
+            block_on(reader.chunk(31, 1))?,
+            Some((""[..].into(), chunk(31, 1)))
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn region_writer_existing() -> Result<(), Error> {
+        let directory = TestDirectory::new("existing");
+        std::fs::write(
+            directory.region(),
+            &include_bytes!("test-data/region-1.in")[..],
+        )?;
+        let mut writer = block_on(RegionWriter::open(directory.region(), [0, 0]))?;
+        block_on(writer.write_chunk(5, 5, "", &chunk(5, 5), 300))?;
+        writer.remove_chunk(31, 1)?;
+        block_on(writer.flush())?;
+
+        let mut reader = read_back(&directory.region())?;
+        assert_eq!(
+            reader.header().location(5, 5)?,
+            Some(ChunkLocation {
+                offset: 4,
+                count: 1
+            })
+        );
+        assert_eq!(
+            block_on(reader.chunk(0, 0))?,
+            Some((""[..].into(), chunk(0, 0)))
+        );
+        assert_eq!(block_on(reader.chunk(31, 1))?, None);
+        Ok(())
+    }
+
+    #[test]
+    pub fn region_writer_large_chunk() -> Result<(), Error> {
+        // Larger than the limits for NBT from the network
+        let large = crate::nbt_compound! {
+            "xPos" => Value::Int(0),
+            "zPos" => Value::Int(0),
+            "Data" => Value::ByteArray(vec![0u8; 3 * 1024 * 1024].into())
+        };
+        let directory = TestDirectory::new("large");
+        let mut writer = block_on(RegionWriter::open(directory.region(), [0, 0]))?;
+        block_on(writer.write_chunk(0, 0, "", &large, 0))?;
+        block_on(writer.flush())?;
+
+        let mut reader = read_back(&directory.region())?;
+        assert_eq!(block_on(reader.chunk(0, 0))?, Some((""[..].into(), large)));
+        Ok(())
+    }
+
+    #[test]
+    pub fn region_writer_atomic() -> Result<(), Error> {
+        let directory = TestDirectory::new("atomic");
+        let mut writer = block_on(RegionWriter::open(directory.region(), [0, 0]))?;
+        block_on(writer.write_chunk(0, 0, "", &chunk(0, 0), 0))?;
+        block_on(writer.flush())?;
+
+        // Until the writer is flushed the file still has the old header,
+        // and a copy that was left behind by a crash is ignored
+        std::fs::write(temporary_path(&directory.region()), b"partial")?;
+        block_on(writer.write_chunk(0, 0, "", &chunk(1, 1), 0))?;
+        block_on(writer.write_chunk(1, 0, "", &chunk(1, 0), 0))?;
+        let mut reader = read_back(&directory.region())?;
+        assert_eq!(
+            block_on(reader.chunk(0, 0))?,
+            Some((""[..].into(), chunk(0, 0)))
+        );
+        assert_eq!(block_on(reader.chunk(1, 0))?, None);
+
+        block_on(writer.flush())?;
+        let mut reader = read_back(&directory.region())?;
+        assert_eq!(
+            block_on(reader.chunk(0, 0))?,
+            Some((""[..].into(), chunk(1, 1)))
+        );
+        assert_eq!(
+            block_on(reader.chunk(1, 0))?,
+            Some((""[..].into(), chunk(1, 0)))
+        );
+        assert!(!Path::new(&temporary_path(&directory.region())).exists());
+        Ok(())
+    }
+
+    #[test]
+    pub fn region_writer_sector_reuse() -> Result<(), Error> {
+        let directory = TestDirectory::new("reuse");
+        let mut writer = block_on(RegionWriter::open(directory.region(), [0, 0]))?;
+        block_on(writer.write_chunk(0, 0, "", &chunk(0, 0), 0))?;
+        block_on(writer.flush())?;
+
+        // The old sectors are still referenced by the header on disk
+        block_on(writer.write_chunk(0, 0, "", &chunk(1, 1), 0))?;
+        assert_eq!(writer.header().location(0, 0)?.unwrap().offset, 3);
+        block_on(writer.flush())?;
+
+        block_on(writer.write_chunk(0, 0, "", &chunk(2, 2), 0))?;
+        assert_eq!(writer.header().location(0, 0)?.unwrap().offset, 2);
+        block_on(writer.flush())?;
+
+        let mut reader = read_back(&directory.region())?;
+        assert_eq!(
+            block_on(reader.chunk(0, 0))?,
+            Some((""[..].into(), chunk(2, 2)))
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn region_writer_external() -> Result<(), Error> {
+        let directory = TestDirectory::new("external");
+        let mut writer = block_on(RegionWriter::open(directory.region(), [0, 0]))?;
+
+        // Noise doesn't compress, so the chunk needs more than 255 sectors
+        let mut state = 1u32;
+        let noise: Vec<u8> = (0..MAX_CHUNK_SECTORS * SECTOR_SIZE)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        let value = crate::nbt_compound! {
+            "Noise" => Value::ByteArray(noise[..].into())
+        };
+        block_on(writer.write_chunk(3, 4, "", &value, 0))?;
+        block_on(writer.flush())?;
+
+        let external = directory.0.join("c.3.4.mcc");
+        assert!(external.exists());
+        assert_eq!(writer.header().location(3, 4)?.unwrap().count, 1);
+        let mut reader = read_back(&directory.region())?;
+        // The chunk is larger than the NBT limits allow, so compare the data
+        let expected = block_on(nbt::encode_nbt("", &value, NbtCompression::None))?;
+        assert_eq!(block_on(reader.chunk_data(3, 4))?, Some(expected));
+
+        // The external file is removed once the chunk fits in the region
+        block_on(writer.write_chunk(3, 4, "", &chunk(3, 4), 0))?;
+        assert!(external.exists());
+        block_on(writer.flush())?;
+        assert!(!external.exists());
+        let mut reader = read_back(&directory.region())?;
+        assert_eq!(
+            block_on(reader.chunk(3, 4))?,
+            Some((""[..].into(), chunk(3, 4)))
+        );
+        Ok(())
+    }
+}
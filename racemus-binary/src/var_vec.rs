@@ -1,4 +1,4 @@
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 struct VarVecLayout {
     bits_per_entry: usize,
     capacity: usize,
@@ -78,7 +78,7 @@ impl VarVecLayout {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VarVec {
     entries: Vec<u64>,
     layout: VarVecLayout,
@@ -104,11 +104,35 @@ impl VarVec {
         Self { entries, layout }
     }
 
+    // Wraps already packed entries, such as the block states of an Anvil
+    // chunk section.
+    pub fn from_raw(entries: Vec<u64>, capacity: usize, bits_per_entry: u8) -> Option<Self> {
+        if bits_per_entry == 0 || bits_per_entry > 64 {
+            return None;
+        }
+        let layout = VarVecLayout::new(capacity, bits_per_entry);
+        if entries.len() == layout.required_entries() {
+            Some(Self { entries, layout })
+        } else {
+            None
+        }
+    }
+
     #[inline]
     pub fn get_inner(&self) -> &[u64] {
         &self.entries
     }
 
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.layout.capacity
+    }
+
+    #[inline]
+    pub fn bits_per_entry(&self) -> u8 {
+        self.layout.bits_per_entry as u8
+    }
+
     #[inline]
     pub fn get(&self, index: usize) -> Option<u64> {
         self.layout.get(&self.entries, index)
@@ -212,6 +236,16 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn bit_writer_from_raw() {
+        let entries = vec![0x7020863148418841, 0x8b1018a7260f68c8, 0x0];
+        let b = VarVec::from_raw(entries.clone(), 26, 5).unwrap();
+        assert_eq!(b.get(0), Some(1));
+        assert_eq!(b.get(25), Some(4));
+        assert!(VarVec::from_raw(entries.clone(), 10, 5).is_none());
+        assert!(VarVec::from_raw(entries, 26, 0).is_none());
+    }
+
     #[test]
     pub fn bit_writer_set() {
         let mut b = VarVec::with_capacity(16, 5);
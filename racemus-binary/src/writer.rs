@@ -1,5 +1,5 @@
 use crate::AesCfb8;
-use crate::{Error, ErrorKind};
+use crate::{proto::ProtocolVersion, Error, ErrorKind};
 use async_std::io::{prelude::*, Write};
 use cfb8::stream_cipher::StreamCipher;
 use flate2::{write::ZlibEncoder, Compression};
@@ -17,6 +17,7 @@ pub struct BinaryWriter<W: Write + Unpin> {
     cipher: Option<AesCfb8>,
     compression_buffer: Option<Vec<u8>>,
     compression_threshold: Option<usize>,
+    protocol_version: ProtocolVersion,
 }
 
 macro_rules! build_write_varint {
@@ -103,6 +104,7 @@ impl<W: Write + Unpin> BinaryWriter<W> {
             cipher: None,
             compression_buffer: None,
             compression_threshold: None,
+            protocol_version: ProtocolVersion::default(),
         }
     }
 
@@ -223,6 +225,16 @@ impl<W: Write + Unpin> BinaryWriter<W> {
         self.compression_threshold.is_some()
     }
 
+    #[inline]
+    pub fn set_protocol_version(&mut self, version: ProtocolVersion) {
+        self.protocol_version = version;
+    }
+
+    #[inline]
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
     #[inline]
     #[allow(dead_code)]
     pub(crate) fn try_compress(
@@ -0,0 +1,345 @@
+use serde_json::Value;
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// The blocks report is produced by the vanilla server with:
+// java -cp server.jar net.minecraft.data.Main --reports
+const BLOCKS_REPORT: &str = "data/blocks.json";
+const ENV_BLOCKS_REPORT: &str = "RACEMUS_BLOCKS_REPORT";
+
+// The reports don't include lighting, so it is maintained by hand from the
+// light values and opacities that vanilla 1.15.2 gives each block when it
+// registers them (net.minecraft.block.Blocks). Keys are block states in the
+// same format that BlockState is parsed from, properties that are left out
+// match any value. Blocks that aren't listed emit no light and block all of
+// it.
+const LIGHT: &str = "data/light.json";
+const DEFAULT_OPACITY: u8 = 15;
+
+// The reports don't include materials either, so this is also maintained by
+// hand from the same registrations. "passable" lists the blocks whose material doesn't block motion, and
+// "fluid" the blocks that always contain a fluid. Waterlogged states contain
+// water as well.
+const MOTION: &str = "data/motion.json";
+const BLOCKS_MOTION: u8 = 0x1;
+const HAS_FLUID: u8 = 0x2;
+
+struct Property {
+    name: String,
+    values: Vec<String>,
+}
+
+struct Block {
+    name: String,
+    kind: String,
+    properties: Vec<Property>,
+    min_state: u32,
+    default_state: u32,
+}
+
+fn main() {
+    let report = match env::var_os(ENV_BLOCKS_REPORT) {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(BLOCKS_REPORT),
+    };
+    println!("cargo:rerun-if-changed={}", report.display());
+    println!("cargo:rerun-if-env-changed={}", ENV_BLOCKS_REPORT);
+
+    let data = fs::read_to_string(&report)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", report.display(), e));
+    let data: Value = serde_json::from_str(&data)
+        .unwrap_or_else(|e| panic!("failed to parse {}: {}", report.display(), e));
+
+    let mut blocks: Vec<Block> = data
+        .as_object()
+        .expect("the blocks report must be an object")
+        .iter()
+        .map(|(name, value)| read_block(name, value))
+        .collect();
+    blocks.sort_by_key(|b| b.min_state);
+
+    let light = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(LIGHT);
+    println!("cargo:rerun-if-changed={}", light.display());
+    let light = fs::read_to_string(&light)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", light.display(), e));
+    let light: Value =
+        serde_json::from_str(&light).unwrap_or_else(|e| panic!("failed to parse {}: {}", LIGHT, e));
+    let light = read_light(&data, &light);
+
+    let motion = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(MOTION);
+    println!("cargo:rerun-if-changed={}", motion.display());
+    let motion = fs::read_to_string(&motion)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", motion.display(), e));
+    let motion: Value = serde_json::from_str(&motion)
+        .unwrap_or_else(|e| panic!("failed to parse {}: {}", MOTION, e));
+    let motion = read_motion(&data, &motion);
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("blocks.rs");
+    fs::write(&out, generate(&blocks, &light, &motion)).unwrap();
+}
+
+// Returns the BLOCKS_MOTION and HAS_FLUID flags of every state
+fn read_motion(blocks: &Value, motion: &Value) -> Vec<u8> {
+    let blocks = blocks.as_object().unwrap();
+    let count = blocks
+        .values()
+        .flat_map(|b| b["states"].as_array().unwrap())
+        .count();
+    let mut result = vec![BLOCKS_MOTION; count];
+
+    let states = |table: &str| {
+        motion[table]
+            .as_array()
+            .unwrap_or_else(|| panic!("{} must contain an array named {}", MOTION, table))
+            .iter()
+            .flat_map(|name| {
+                let name = name.as_str().expect("block names must be strings");
+                blocks
+                    .get(name)
+                    .unwrap_or_else(|| panic!("unknown block {} in {}", name, MOTION))["states"]
+                    .as_array()
+                    .unwrap()
+            })
+            .map(|state| state["id"].as_u64().unwrap() as usize)
+            .collect::<Vec<_>>()
+    };
+    for id in states("passable") {
+        result[id] &= !BLOCKS_MOTION;
+    }
+    for id in states("fluid") {
+        result[id] |= HAS_FLUID;
+    }
+
+    for state in blocks
+        .values()
+        .flat_map(|b| b["states"].as_array().unwrap())
+    {
+        if state["properties"]["waterlogged"].as_str() == Some("true") {
+            result[state["id"].as_u64().unwrap() as usize] |= HAS_FLUID;
+        }
+    }
+    result
+}
+
+// Returns the emission and opacity of every state, packed into the low and
+// high nibbles.
+fn read_light(blocks: &Value, light: &Value) -> Vec<u8> {
+    let states: Vec<(&str, &Value)> = blocks
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(name, value)| {
+            value["states"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(move |s| (name.as_str(), s))
+        })
+        .collect();
+    let mut emission = vec![0u8; states.len()];
+    let mut opacity = vec![DEFAULT_OPACITY; states.len()];
+
+    // serde_json sorts the keys, so a state with properties is applied after
+    // the block without them and takes precedence.
+    for (table, result) in [("emission", &mut emission), ("opacity", &mut opacity)].iter_mut() {
+        let entries = light[*table]
+            .as_object()
+            .unwrap_or_else(|| panic!("{} must contain an object named {}", LIGHT, table));
+        for (key, level) in entries {
+            let level = level
+                .as_u64()
+                .filter(|l| *l <= 15)
+                .unwrap_or_else(|| panic!("the {} of {} must be a light level", table, key))
+                as u8;
+            let (name, properties) = match key.find('[') {
+                Some(index) => (&key[..index], &key[index + 1..key.len() - 1]),
+                None => (key.as_str(), ""),
+            };
+            let properties: Vec<(&str, &str)> = properties
+                .split(',')
+                .filter(|p| !p.is_empty())
+                .map(|p| {
+                    let mut parts = p.splitn(2, '=');
+                    (parts.next().unwrap(), parts.next().unwrap_or(""))
+                })
+                .collect();
+
+            let mut found = false;
+            for (block, state) in states.iter() {
+                if *block != name {
+                    continue;
+                }
+                found = true;
+                if properties
+                    .iter()
+                    .all(|(n, v)| state["properties"][*n].as_str() == Some(*v))
+                {
+                    let id = state["id"].as_u64().unwrap() as usize;
+                    result[id] = level;
+                }
+            }
+            assert!(found, "unknown block {} in {}", name, LIGHT);
+        }
+    }
+
+    // Water always dims light, so waterlogged blocks are never transparent
+    for (_, state) in states.iter() {
+        let id = state["id"].as_u64().unwrap() as usize;
+        if state["properties"]["waterlogged"].as_str() == Some("true") {
+            opacity[id] = opacity[id].max(1);
+        }
+    }
+
+    emission
+        .iter()
+        .zip(opacity.iter())
+        .map(|(e, o)| e | (o << 4))
+        .collect()
+}
+
+fn read_block(name: &str, value: &Value) -> Block {
+    // Vanilla enumerates states with the properties sorted by name and the
+    // last property changing the fastest. serde_json also sorts by name.
+    let properties: Vec<Property> = match value.get("properties") {
+        Some(Value::Object(properties)) => properties
+            .iter()
+            .map(|(name, values)| Property {
+                name: name.clone(),
+                values: values
+                    .as_array()
+                    .expect("property values must be an array")
+                    .iter()
+                    .map(|v| v.as_str().expect("property values must be strings").into())
+                    .collect(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let states = value["states"].as_array().expect("states must be an array");
+    let min_state = states
+        .iter()
+        .map(|s| s["id"].as_u64().unwrap() as u32)
+        .min()
+        .expect("blocks must have states");
+    let default_state = states
+        .iter()
+        .find(|s| s.get("default").and_then(Value::as_bool).unwrap_or(false))
+        .map_or(min_state, |s| s["id"].as_u64().unwrap() as u32);
+
+    // The registry computes state IDs from the property values, so make sure
+    // that the report agrees.
+    for state in states {
+        let mut id = 0usize;
+        for property in properties.iter() {
+            let value = state["properties"][&property.name].as_str().unwrap();
+            let index = property.values.iter().position(|v| v == value).unwrap();
+            id = id * property.values.len() + index;
+        }
+        let expected = state["id"].as_u64().unwrap() as u32;
+        assert_eq!(
+            min_state + id as u32,
+            expected,
+            "unexpected state ID for {}",
+            name
+        );
+    }
+
+    Block {
+        name: name.into(),
+        kind: kind_name(name),
+        properties,
+        min_state,
+        default_state,
+    }
+}
+
+// minecraft:oak_log => OakLog
+fn kind_name(name: &str) -> String {
+    let name = name.rsplit(':').next().unwrap();
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(c) => c.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn generate(blocks: &[Block], light: &[u8], motion: &[u8]) -> String {
+    let mut result = String::new();
+    writeln!(
+        result,
+        "// Generated by build.rs from the vanilla blocks report"
+    )
+    .unwrap();
+    writeln!(result).unwrap();
+
+    writeln!(result, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]").unwrap();
+    writeln!(result, "pub enum BlockKind {{").unwrap();
+    for block in blocks {
+        writeln!(result, "    {},", block.kind).unwrap();
+    }
+    writeln!(result, "}}").unwrap();
+    writeln!(result).unwrap();
+
+    writeln!(result, "static BLOCKS: [Block; {}] = [", blocks.len()).unwrap();
+    for block in blocks {
+        writeln!(result, "    Block {{").unwrap();
+        writeln!(result, "        name: {:?},", block.name).unwrap();
+        writeln!(result, "        kind: BlockKind::{},", block.kind).unwrap();
+        writeln!(result, "        properties: &[").unwrap();
+        for property in block.properties.iter() {
+            writeln!(
+                result,
+                "            Property {{ name: {:?}, values: &{:?} }},",
+                property.name, property.values
+            )
+            .unwrap();
+        }
+        writeln!(result, "        ],").unwrap();
+        writeln!(result, "        min_state: {},", block.min_state).unwrap();
+        writeln!(result, "        default_state: {},", block.default_state).unwrap();
+        writeln!(result, "    }},").unwrap();
+    }
+    writeln!(result, "];").unwrap();
+    writeln!(result).unwrap();
+
+    let mut by_name: Vec<usize> = (0..blocks.len()).collect();
+    by_name.sort_by_key(|i| &blocks[*i].name);
+    writeln!(
+        result,
+        "static BLOCKS_BY_NAME: [u16; {}] = {:?};",
+        blocks.len(),
+        by_name
+    )
+    .unwrap();
+    writeln!(result).unwrap();
+
+    writeln!(
+        result,
+        "static STATE_LIGHT: [u8; {}] = {:?};",
+        light.len(),
+        light
+    )
+    .unwrap();
+    writeln!(result).unwrap();
+
+    writeln!(result, "const BLOCKS_MOTION: u8 = {};", BLOCKS_MOTION).unwrap();
+    writeln!(result, "const HAS_FLUID: u8 = {};", HAS_FLUID).unwrap();
+    writeln!(
+        result,
+        "static STATE_MOTION: [u8; {}] = {:?};",
+        motion.len(),
+        motion
+    )
+    .unwrap();
+
+    result
+}
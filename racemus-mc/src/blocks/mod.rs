@@ -0,0 +1,397 @@
+use std::{error::Error, fmt, str::FromStr};
+
+include!(concat!(env!("OUT_DIR"), "/blocks.rs"));
+
+#[derive(Debug)]
+pub struct Property {
+    name: &'static str,
+    values: &'static [&'static str],
+}
+
+impl Property {
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub fn values(&self) -> &'static [&'static str] {
+        self.values
+    }
+}
+
+#[derive(Debug)]
+pub struct Block {
+    name: &'static str,
+    kind: BlockKind,
+    properties: &'static [Property],
+    min_state: u32,
+    default_state: u32,
+}
+
+impl Block {
+    pub fn by_name(name: &str) -> Option<&'static Block> {
+        BLOCKS_BY_NAME
+            .binary_search_by(|i| BLOCKS[*i as usize].name.cmp(name))
+            .ok()
+            .map(|i| &BLOCKS[BLOCKS_BY_NAME[i] as usize])
+    }
+
+    pub fn all() -> &'static [Block] {
+        &BLOCKS
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub fn kind(&self) -> BlockKind {
+        self.kind
+    }
+
+    #[inline]
+    pub fn properties(&self) -> &'static [Property] {
+        self.properties
+    }
+
+    #[inline]
+    pub fn default_state(&self) -> BlockState {
+        BlockState(self.default_state)
+    }
+
+    pub fn states(&self) -> impl Iterator<Item = BlockState> {
+        (self.min_state..(self.min_state + self.state_count())).map(BlockState)
+    }
+
+    fn state_count(&self) -> u32 {
+        self.properties
+            .iter()
+            .fold(1, |acc, p| acc * p.values.len() as u32)
+    }
+
+    fn property_index(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|p| p.name == name)
+    }
+
+    // The distance between states for each value of the property
+    fn property_stride(&self, index: usize) -> u32 {
+        self.properties[index + 1..]
+            .iter()
+            .fold(1, |acc, p| acc * p.values.len() as u32)
+    }
+}
+
+impl BlockKind {
+    #[inline]
+    pub fn block(self) -> &'static Block {
+        &BLOCKS[self as usize]
+    }
+
+    #[inline]
+    pub fn default_state(self) -> BlockState {
+        self.block().default_state()
+    }
+}
+
+// A global palette ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockState(u32);
+
+impl BlockState {
+    pub fn from_id(id: u32) -> Option<Self> {
+        let block = Self(id).find_block()?;
+        if id < block.min_state + block.state_count() {
+            Some(Self(id))
+        } else {
+            None
+        }
+    }
+
+    // Properties that are not specified take the value of the default state.
+    pub fn from_name(name: &str, properties: &[(&str, &str)]) -> Option<Self> {
+        let mut result = Block::by_name(name)?.default_state();
+        for (name, value) in properties.iter() {
+            result = result.with_property(name, value)?;
+        }
+        Some(result)
+    }
+
+    #[inline]
+    pub fn id(self) -> u32 {
+        self.0
+    }
+
+    #[inline]
+    pub fn block(self) -> &'static Block {
+        self.find_block().unwrap()
+    }
+
+    #[inline]
+    pub fn kind(self) -> BlockKind {
+        self.block().kind
+    }
+
+    pub fn property(self, name: &str) -> Option<&'static str> {
+        let block = self.block();
+        let index = block.property_index(name)?;
+        let property = &block.properties[index];
+        let value = (self.0 - block.min_state) / block.property_stride(index);
+        Some(property.values[value as usize % property.values.len()])
+    }
+
+    pub fn property_bool(self, name: &str) -> Option<bool> {
+        self.property(name)?.parse().ok()
+    }
+
+    pub fn property_int(self, name: &str) -> Option<i32> {
+        self.property(name)?.parse().ok()
+    }
+
+    pub fn properties(self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        let block = self.block();
+        block
+            .properties
+            .iter()
+            .map(move |p| (p.name, self.property(p.name).unwrap()))
+    }
+
+    // The light level that the state emits
+    #[inline]
+    pub fn light_emission(self) -> u8 {
+        STATE_LIGHT[self.0 as usize] & 0xf
+    }
+
+    // How many light levels are lost when light passes through the state
+    #[inline]
+    pub fn light_opacity(self) -> u8 {
+        STATE_LIGHT[self.0 as usize] >> 4
+    }
+
+    // Whether the material blocks motion, which isn't the same as having a
+    // collision box. Signs block motion but torches and flowers don't.
+    #[inline]
+    pub fn blocks_motion(self) -> bool {
+        STATE_MOTION[self.0 as usize] & BLOCKS_MOTION != 0
+    }
+
+    // Whether the state contains a fluid, including waterlogged states
+    #[inline]
+    pub fn has_fluid(self) -> bool {
+        STATE_MOTION[self.0 as usize] & HAS_FLUID != 0
+    }
+
+    pub fn with_property(self, name: &str, value: &str) -> Option<Self> {
+        let block = self.block();
+        let index = block.property_index(name)?;
+        let property = &block.properties[index];
+        let stride = block.property_stride(index);
+
+        let current = (self.0 - block.min_state) / stride % property.values.len() as u32;
+        let value = property.values.iter().position(|v| *v == value)? as u32;
+        Some(Self(self.0 - current * stride + value * stride))
+    }
+
+    fn find_block(self) -> Option<&'static Block> {
+        match BLOCKS.binary_search_by_key(&self.0, |b| b.min_state) {
+            Ok(index) => Some(&BLOCKS[index]),
+            Err(0) => None,
+            Err(index) => Some(&BLOCKS[index - 1]),
+        }
+    }
+}
+
+impl From<BlockState> for u32 {
+    fn from(val: BlockState) -> Self {
+        val.0
+    }
+}
+
+impl fmt::Display for BlockState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.block().name)?;
+        let mut properties = self.properties().peekable();
+        if properties.peek().is_some() {
+            write!(f, "[")?;
+            for (i, (name, value)) in properties.enumerate() {
+                if i != 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}={}", name, value)?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidBlockState;
+
+impl Error for InvalidBlockState {}
+
+impl fmt::Display for InvalidBlockState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid block state")
+    }
+}
+
+// Parses the same format that the block state is displayed in, for example
+// minecraft:oak_log[axis=x]
+impl FromStr for BlockState {
+    type Err = InvalidBlockState;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, properties) = match value.find('[') {
+            Some(index) if value.ends_with(']') => {
+                (&value[..index], &value[index + 1..value.len() - 1])
+            }
+            Some(_) => return Err(InvalidBlockState),
+            None => (value, ""),
+        };
+
+        let mut result = Block::by_name(name)
+            .ok_or(InvalidBlockState)?
+            .default_state();
+        for property in properties.split(',').filter(|p| !p.is_empty()) {
+            let mut parts = property.splitn(2, '=');
+            let name = parts.next().ok_or(InvalidBlockState)?;
+            let value = parts.next().ok_or(InvalidBlockState)?;
+            result = result
+                .with_property(name.trim(), value.trim())
+                .ok_or(InvalidBlockState)?;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn block_by_name() {
+        let block = Block::by_name("minecraft:bedrock").unwrap();
+        assert_eq!(block.kind(), BlockKind::Bedrock);
+        assert_eq!(block.default_state().id(), 33);
+        assert!(Block::by_name("minecraft:unknown").is_none());
+    }
+
+    #[test]
+    pub fn block_state_properties() {
+        let state = BlockState::from_name("minecraft:grass_block", &[]).unwrap();
+        assert_eq!(state.id(), 9);
+        assert_eq!(state.property_bool("snowy"), Some(false));
+
+        let state = state.with_property("snowy", "true").unwrap();
+        assert_eq!(state.id(), 8);
+        assert_eq!(state.to_string(), "minecraft:grass_block[snowy=true]");
+        assert!(state.with_property("snowy", "maybe").is_none());
+        assert!(state.with_property("level", "1").is_none());
+
+        let state = BlockState::from_name("minecraft:water", &[("level", "7")]).unwrap();
+        assert_eq!(state.property_int("level"), Some(7));
+        assert_eq!(state.kind(), BlockKind::Water);
+        assert_eq!(BlockState::from_id(state.id()), Some(state));
+    }
+
+    #[test]
+    pub fn block_states() {
+        let block = BlockKind::OakLog.block();
+        let states: Vec<_> = block
+            .states()
+            .map(|s| s.property("axis").unwrap())
+            .collect();
+        assert_eq!(states, ["x", "y", "z"]);
+        assert_eq!(
+            block.default_state().to_string(),
+            "minecraft:oak_log[axis=y]"
+        );
+        assert_eq!(BlockState::from_id(0).unwrap().kind(), BlockKind::Air);
+        assert_eq!(BlockKind::Air.default_state().to_string(), "minecraft:air");
+    }
+
+    #[test]
+    pub fn block_state_parse() {
+        let state: BlockState = "minecraft:oak_log[axis=x]".parse().unwrap();
+        assert_eq!(state.id(), 72);
+        assert_eq!(state.to_string().parse(), Ok(state));
+        assert_eq!(
+            "minecraft:dirt".parse(),
+            Ok(BlockKind::Dirt.default_state())
+        );
+        assert_eq!(
+            "minecraft:oak_log[axis=w]".parse::<BlockState>(),
+            Err(InvalidBlockState)
+        );
+        assert_eq!(
+            "minecraft:oak_log[axis=x".parse::<BlockState>(),
+            Err(InvalidBlockState)
+        );
+    }
+
+    #[test]
+    pub fn block_registry_complete() {
+        assert_eq!(Block::all().len(), 680);
+        assert_eq!(BlockKind::HoneycombBlock.default_state().id(), 11336);
+        assert!(BlockState::from_id(11337).is_none());
+        assert_eq!(BlockKind::CaveAir.default_state().id(), 9130);
+
+        let state: BlockState = "minecraft:oak_stairs[facing=east,half=top]"
+            .parse()
+            .unwrap();
+        assert_eq!(state.id(), 2013);
+        assert_eq!(
+            BlockKind::RedstoneWire.default_state().to_string(),
+            "minecraft:redstone_wire[east=none,north=none,power=0,south=none,west=none]"
+        );
+    }
+
+    #[test]
+    pub fn block_state_light() {
+        let light = |s: &str| {
+            let state: BlockState = s.parse().unwrap();
+            (state.light_emission(), state.light_opacity())
+        };
+        assert_eq!(light("minecraft:air"), (0, 0));
+        assert_eq!(light("minecraft:stone"), (0, 15));
+        assert_eq!(light("minecraft:glass"), (0, 0));
+        assert_eq!(light("minecraft:oak_leaves"), (0, 1));
+        assert_eq!(light("minecraft:water"), (0, 1));
+        assert_eq!(light("minecraft:torch"), (14, 0));
+        assert_eq!(light("minecraft:lava"), (15, 1));
+        assert_eq!(light("minecraft:glowstone"), (15, 15));
+        assert_eq!(light("minecraft:furnace[lit=false]"), (0, 15));
+        assert_eq!(light("minecraft:furnace[lit=true]"), (13, 15));
+        assert_eq!(light("minecraft:oak_slab[type=bottom]"), (0, 0));
+        assert_eq!(light("minecraft:oak_slab[type=double]"), (0, 15));
+        assert_eq!(light("minecraft:oak_stairs[waterlogged=true]"), (0, 1));
+        assert_eq!(
+            light("minecraft:sea_pickle[pickles=3,waterlogged=true]"),
+            (12, 1)
+        );
+        assert_eq!(
+            light("minecraft:sea_pickle[pickles=3,waterlogged=false]"),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    pub fn block_state_motion() {
+        let motion = |s: &str| {
+            let state: BlockState = s.parse().unwrap();
+            (state.blocks_motion(), state.has_fluid())
+        };
+        assert_eq!(motion("minecraft:air"), (false, false));
+        assert_eq!(motion("minecraft:stone"), (true, false));
+        assert_eq!(motion("minecraft:water"), (false, true));
+        assert_eq!(motion("minecraft:lava[level=3]"), (false, true));
+        assert_eq!(motion("minecraft:poppy"), (false, false));
+        assert_eq!(motion("minecraft:torch"), (false, false));
+        assert_eq!(motion("minecraft:snow[layers=1]"), (false, false));
+        assert_eq!(motion("minecraft:oak_sign"), (true, false));
+        assert_eq!(motion("minecraft:oak_sign[waterlogged=true]"), (true, true));
+        assert_eq!(motion("minecraft:kelp"), (false, true));
+    }
+}
@@ -1,4 +1,5 @@
 #![warn(rust_2018_idioms)]
 
 pub mod api;
+pub mod blocks;
 pub mod chat;
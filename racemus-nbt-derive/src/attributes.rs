@@ -0,0 +1,162 @@
+use syn::{Attribute, Error, Lit, Meta, NestedMeta};
+
+#[derive(Debug, Clone, Copy)]
+pub enum RenameRule {
+    Pascal,
+    Camel,
+    Snake,
+    Lower,
+}
+
+impl RenameRule {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "PascalCase" => Some(RenameRule::Pascal),
+            "camelCase" => Some(RenameRule::Camel),
+            "snake_case" => Some(RenameRule::Snake),
+            "lowercase" => Some(RenameRule::Lower),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, name: &str) -> String {
+        let words = words(name);
+        match self {
+            RenameRule::Pascal => words.iter().map(|w| capitalize(w)).collect(),
+            RenameRule::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(i, w)| if i == 0 { w.clone() } else { capitalize(w) })
+                .collect(),
+            RenameRule::Snake => words.join("_"),
+            RenameRule::Lower => words.concat(),
+        }
+    }
+}
+
+// Splits both snake_case and PascalCase names into lowercase words
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c == '_' {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            previous_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lower {
+            words.push(std::mem::take(&mut current));
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// The items of every #[nbt(...)] attribute
+fn nbt_items(attrs: &[Attribute]) -> Result<Vec<NestedMeta>, Error> {
+    let mut result = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("nbt")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => result.extend(list.nested),
+            meta => return Err(Error::new_spanned(meta, "expected #[nbt(...)]")),
+        }
+    }
+    Ok(result)
+}
+
+fn string_value(lit: &Lit) -> Result<String, Error> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        lit => Err(Error::new_spanned(lit, "expected a string")),
+    }
+}
+
+#[derive(Default)]
+pub struct ContainerAttributes {
+    pub rename_all: Option<RenameRule>,
+    // Enums with fields are stored as compounds, with the variant name in
+    // this key
+    pub tag: Option<String>,
+}
+
+impl ContainerAttributes {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self, Error> {
+        let mut result = Self::default();
+        for item in nbt_items(attrs)? {
+            match &item {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename_all") => {
+                    let value = string_value(&nv.lit)?;
+                    match RenameRule::parse(&value) {
+                        Some(rule) => result.rename_all = Some(rule),
+                        None => return Err(Error::new_spanned(&nv.lit, "unknown rename rule")),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("tag") => {
+                    result.tag = Some(string_value(&nv.lit)?);
+                }
+                item => return Err(Error::new_spanned(item, "unknown nbt attribute")),
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Default)]
+pub struct FieldAttributes {
+    pub rename: Option<String>,
+    // Use Default::default() when the field is missing
+    pub default: bool,
+}
+
+impl FieldAttributes {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self, Error> {
+        let mut result = Self::default();
+        for item in nbt_items(attrs)? {
+            match &item {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                    result.rename = Some(string_value(&nv.lit)?);
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("default") => {
+                    result.default = true;
+                }
+                item => return Err(Error::new_spanned(item, "unknown nbt attribute")),
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Default)]
+pub struct VariantAttributes {
+    pub rename: Option<String>,
+}
+
+impl VariantAttributes {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self, Error> {
+        let mut result = Self::default();
+        for item in nbt_items(attrs)? {
+            match &item {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                    result.rename = Some(string_value(&nv.lit)?);
+                }
+                item => return Err(Error::new_spanned(item, "unknown nbt attribute")),
+            }
+        }
+        Ok(result)
+    }
+}
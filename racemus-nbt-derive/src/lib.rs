@@ -0,0 +1,299 @@
+extern crate proc_macro;
+
+mod attributes;
+
+use attributes::*;
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    ext::IdentExt, parse_macro_input, Data, DataEnum, DeriveInput, Error, Fields, FieldsNamed,
+    Ident, Type,
+};
+
+// Implements ToNbt and FromNbt. Structs are stored as compounds, with the
+// fields in the order that they are declared. Option fields are left out
+// when they are None. Enums without fields are stored as strings, other
+// enums need #[nbt(tag = "...")] and are stored as compounds with the
+// variant name in the tag.
+#[proc_macro_derive(NbtCompound, attributes(nbt))]
+pub fn derive_nbt_compound(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(r) => r.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let attributes = ContainerAttributes::parse(&input.attrs)?;
+    let (to_nbt, from_nbt) = match &input.data {
+        Data::Struct(data) => {
+            if attributes.tag.is_some() {
+                return Err(Error::new_spanned(input, "tags can only be used on enums"));
+            }
+            expand_struct(input, &attributes, &data.fields)?
+        }
+        Data::Enum(data) => expand_enum(&attributes, data)?,
+        Data::Union(_) => return Err(Error::new_spanned(input, "unions are not supported")),
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::racemus_binary::nbt::ToNbt for #name #ty_generics #where_clause {
+            fn to_nbt(&self) -> ::racemus_binary::nbt::Value {
+                #to_nbt
+            }
+        }
+
+        impl #impl_generics ::racemus_binary::nbt::FromNbt for #name #ty_generics #where_clause {
+            fn from_nbt(
+                value: &::racemus_binary::nbt::Value,
+            ) -> ::std::result::Result<Self, ::racemus_binary::Error> {
+                #from_nbt
+            }
+        }
+    })
+}
+
+struct Field<'a> {
+    ident: &'a Ident,
+    key: String,
+    optional: bool,
+    default: bool,
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => match path.path.segments.last() {
+            Some(segment) => segment.ident == "Option",
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+fn named_fields<'a>(
+    attributes: &ContainerAttributes,
+    fields: &'a FieldsNamed,
+) -> Result<Vec<Field<'a>>, Error> {
+    let mut result = Vec::with_capacity(fields.named.len());
+    for field in fields.named.iter() {
+        let ident = field.ident.as_ref().unwrap();
+        let field_attributes = FieldAttributes::parse(&field.attrs)?;
+        let name = ident.unraw().to_string();
+        let key = match (field_attributes.rename, attributes.rename_all) {
+            (Some(rename), _) => rename,
+            (None, Some(rule)) => rule.apply(&name),
+            (None, None) => name,
+        };
+        if result.iter().any(|f: &Field| f.key == key) {
+            return Err(Error::new_spanned(field, "duplicate nbt key"));
+        }
+        if attributes.tag.as_ref() == Some(&key) {
+            return Err(Error::new_spanned(field, "the nbt key is used by the tag"));
+        }
+        result.push(Field {
+            ident,
+            key,
+            optional: is_option(&field.ty),
+            default: field_attributes.default,
+        });
+    }
+    Ok(result)
+}
+
+// Inserts the fields into `__nbt_compound`, the fields must be bound to
+// references with the same names. Generated locals start with `__nbt_` so
+// that they can't shadow the fields.
+fn write_fields(fields: &[Field]) -> TokenStream2 {
+    let writes = fields.iter().map(|field| {
+        let ident = field.ident;
+        let key = &field.key;
+        if field.optional {
+            quote! {
+                if let ::std::option::Option::Some(__nbt_value) = #ident {
+                    __nbt_compound.insert(#key.into(), ::racemus_binary::nbt::ToNbt::to_nbt(__nbt_value));
+                }
+            }
+        } else {
+            quote! {
+                __nbt_compound.insert(#key.into(), ::racemus_binary::nbt::ToNbt::to_nbt(#ident));
+            }
+        }
+    });
+    quote! { #(#writes)* }
+}
+
+// Field initializers that read from `__nbt_compound`
+fn read_fields(fields: &[Field]) -> TokenStream2 {
+    let reads = fields.iter().map(|field| {
+        let ident = field.ident;
+        let key = &field.key;
+        if field.optional {
+            quote! { #ident: ::racemus_binary::nbt::optional_field(__nbt_compound, #key)?, }
+        } else if field.default {
+            quote! {
+                #ident: ::racemus_binary::nbt::optional_field(__nbt_compound, #key)?
+                    .unwrap_or_default(),
+            }
+        } else {
+            quote! { #ident: ::racemus_binary::nbt::required_field(__nbt_compound, #key)?, }
+        }
+    });
+    quote! { #(#reads)* }
+}
+
+fn expand_struct(
+    input: &DeriveInput,
+    attributes: &ContainerAttributes,
+    fields: &Fields,
+) -> Result<(TokenStream2, TokenStream2), Error> {
+    match fields {
+        Fields::Named(named) => {
+            let fields = named_fields(attributes, named)?;
+            let idents: Vec<_> = fields.iter().map(|f| f.ident).collect();
+            let writes = write_fields(&fields);
+            let reads = read_fields(&fields);
+            let capacity = fields.len();
+            Ok((
+                quote! {
+                    let Self { #(#idents),* } = self;
+                    #[allow(unused_mut)]
+                    let mut __nbt_compound = ::racemus_binary::nbt::Compound::with_capacity(#capacity);
+                    #writes
+                    ::racemus_binary::nbt::Value::Compound(__nbt_compound)
+                },
+                quote! {
+                    #[allow(unused_variables)]
+                    let __nbt_compound = ::racemus_binary::nbt::expect_compound(value)?;
+                    ::std::result::Result::Ok(Self { #reads })
+                },
+            ))
+        }
+        // Newtypes are stored as the value that they wrap
+        Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => Ok((
+            quote! { ::racemus_binary::nbt::ToNbt::to_nbt(&self.0) },
+            quote! {
+                ::std::result::Result::Ok(Self(::racemus_binary::nbt::FromNbt::from_nbt(value)?))
+            },
+        )),
+        Fields::Unnamed(_) => Err(Error::new_spanned(
+            input,
+            "tuple structs need to have a single field",
+        )),
+        Fields::Unit => Ok((
+            quote! {
+                ::racemus_binary::nbt::Value::Compound(::racemus_binary::nbt::Compound::new())
+            },
+            quote! {
+                ::racemus_binary::nbt::expect_compound(value)?;
+                ::std::result::Result::Ok(Self)
+            },
+        )),
+    }
+}
+
+fn expand_enum(
+    attributes: &ContainerAttributes,
+    data: &DataEnum,
+) -> Result<(TokenStream2, TokenStream2), Error> {
+    let mut writes = Vec::with_capacity(data.variants.len());
+    let mut reads = Vec::with_capacity(data.variants.len());
+    let mut keys = Vec::with_capacity(data.variants.len());
+
+    for variant in data.variants.iter() {
+        let ident = &variant.ident;
+        let variant_attributes = VariantAttributes::parse(&variant.attrs)?;
+        let key = match (variant_attributes.rename, attributes.rename_all) {
+            (Some(rename), _) => rename,
+            (None, Some(rule)) => rule.apply(&ident.unraw().to_string()),
+            (None, None) => ident.unraw().to_string(),
+        };
+        if keys.contains(&key) {
+            return Err(Error::new_spanned(variant, "duplicate variant name"));
+        }
+
+        match (&attributes.tag, &variant.fields) {
+            (None, Fields::Unit) => {
+                writes.push(quote! {
+                    Self::#ident => ::racemus_binary::nbt::Value::String(#key.into()),
+                });
+                reads.push(quote! {
+                    #key => ::std::result::Result::Ok(Self::#ident),
+                });
+            }
+            (None, _) => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "enums with fields need #[nbt(tag = \"...\")]",
+                ))
+            }
+            (Some(tag), Fields::Unit) => {
+                writes.push(quote! {
+                    Self::#ident => {
+                        let mut __nbt_compound = ::racemus_binary::nbt::Compound::with_capacity(1);
+                        __nbt_compound.insert(
+                            #tag.into(),
+                            ::racemus_binary::nbt::Value::String(#key.into()),
+                        );
+                        ::racemus_binary::nbt::Value::Compound(__nbt_compound)
+                    }
+                });
+                reads.push(quote! {
+                    #key => ::std::result::Result::Ok(Self::#ident),
+                });
+            }
+            (Some(tag), Fields::Named(named)) => {
+                let fields = named_fields(attributes, named)?;
+                let idents: Vec<_> = fields.iter().map(|f| f.ident).collect();
+                let field_writes = write_fields(&fields);
+                let field_reads = read_fields(&fields);
+                let capacity = fields.len() + 1;
+                writes.push(quote! {
+                    Self::#ident { #(#idents),* } => {
+                        let mut __nbt_compound =
+                            ::racemus_binary::nbt::Compound::with_capacity(#capacity);
+                        __nbt_compound.insert(
+                            #tag.into(),
+                            ::racemus_binary::nbt::Value::String(#key.into()),
+                        );
+                        #field_writes
+                        ::racemus_binary::nbt::Value::Compound(__nbt_compound)
+                    }
+                });
+                reads.push(quote! {
+                    #key => ::std::result::Result::Ok(Self::#ident { #field_reads }),
+                });
+            }
+            (Some(_), Fields::Unnamed(_)) => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "tuple variants are not supported",
+                ))
+            }
+        }
+        keys.push(key);
+    }
+
+    let from_nbt = match &attributes.tag {
+        None => quote! {
+            match ::racemus_binary::nbt::expect_str(value)? {
+                #(#reads)*
+                _ => ::std::result::Result::Err(::racemus_binary::ErrorKind::InvalidNbt.into()),
+            }
+        },
+        Some(tag) => quote! {
+            #[allow(unused_variables)]
+            let __nbt_compound = ::racemus_binary::nbt::expect_compound(value)?;
+            match ::racemus_binary::nbt::required_field::<::std::sync::Arc<str>>(__nbt_compound, #tag)?
+                .as_ref()
+            {
+                #(#reads)*
+                _ => ::std::result::Result::Err(::racemus_binary::ErrorKind::InvalidNbt.into()),
+            }
+        },
+    };
+    Ok((quote! { match self { #(#writes)* } }, from_nbt))
+}
@@ -0,0 +1,196 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::ToTokens;
+use syn::{Attribute, Error, Ident, Lit, LitInt, Meta, MetaList, NestedMeta, Path};
+
+// The items of every #[packet(...)] attribute
+fn packet_items(attrs: &[Attribute]) -> Result<Vec<NestedMeta>, Error> {
+    let mut result = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("packet")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => result.extend(list.nested),
+            meta => return Err(Error::new_spanned(meta, "expected #[packet(...)]")),
+        }
+    }
+    Ok(result)
+}
+
+fn ident_value(lit: &Lit) -> Result<Ident, Error> {
+    match lit {
+        Lit::Str(s) => s.parse(),
+        lit => Err(Error::new_spanned(lit, "expected a string")),
+    }
+}
+
+// Protocol versions are written like the variants of ProtocolVersion, but in
+// lowercase, e.g. v1_15 for ProtocolVersion::V1_15
+fn version(ident: &Ident) -> Ident {
+    Ident::new(&ident.to_string().to_uppercase(), ident.span())
+}
+
+fn method(path: &Path) -> Result<Ident, Error> {
+    match path.get_ident() {
+        Some(ident) => Ok(ident.clone()),
+        None => Err(Error::new_spanned(path, "expected a reader/writer method")),
+    }
+}
+
+#[derive(Default)]
+pub struct ContainerAttributes {
+    // The name of the BinaryReader method that reads the packets
+    pub read: Option<Ident>,
+    pub write: bool,
+}
+
+impl ContainerAttributes {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self, Error> {
+        let mut result = Self::default();
+        for item in packet_items(attrs)? {
+            match &item {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("read") => {
+                    result.read = Some(ident_value(&nv.lit)?);
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("write") => {
+                    result.write = true;
+                }
+                item => return Err(Error::new_spanned(item, "unknown packet attribute")),
+            }
+        }
+        Ok(result)
+    }
+}
+
+pub enum Id {
+    All(LitInt),
+    // The versions that have the packet, with its id in each of them
+    Versions(Vec<(Ident, LitInt)>),
+}
+
+fn version_ids(list: &MetaList) -> Result<Vec<(Ident, LitInt)>, Error> {
+    let mut result = Vec::with_capacity(list.nested.len());
+    for item in list.nested.iter() {
+        match item {
+            NestedMeta::Meta(Meta::NameValue(nv)) => match (nv.path.get_ident(), &nv.lit) {
+                (Some(ident), Lit::Int(id)) => result.push((version(ident), id.clone())),
+                _ => return Err(Error::new_spanned(item, "expected version = id")),
+            },
+            item => return Err(Error::new_spanned(item, "expected version = id")),
+        }
+    }
+    Ok(result)
+}
+
+#[derive(Default)]
+pub struct VariantAttributes {
+    pub id: Option<Id>,
+    // Receives the ids of packets that are not otherwise handled
+    pub unknown: bool,
+    // Not a packet, e.g. data that is detected before the packet header
+    pub skip: bool,
+    // A BinaryWriter method that writes the fields, for packets that can't be
+    // described field by field
+    pub with: Option<Ident>,
+}
+
+impl VariantAttributes {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self, Error> {
+        let mut result = Self::default();
+        for item in packet_items(attrs)? {
+            match &item {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("id") => match &nv.lit {
+                    Lit::Int(id) => result.id = Some(Id::All(id.clone())),
+                    lit => return Err(Error::new_spanned(lit, "expected an integer")),
+                },
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("id") => {
+                    result.id = Some(Id::Versions(version_ids(list)?));
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("with") => {
+                    result.with = Some(ident_value(&nv.lit)?);
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("unknown") => {
+                    result.unknown = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                    result.skip = true;
+                }
+                item => return Err(Error::new_spanned(item, "unknown packet attribute")),
+            }
+        }
+        Ok(result)
+    }
+}
+
+pub struct Wire {
+    pub method: Ident,
+    // The maximum length of length-prefixed data
+    pub max: Option<TokenStream2>,
+    // Each element of an array is written with the method
+    pub each: bool,
+}
+
+// A constant that is written before a field, such as obsolete fields
+pub struct Padding {
+    pub method: Ident,
+    pub value: Lit,
+}
+
+#[derive(Default)]
+pub struct FieldAttributes {
+    pub wire: Option<Wire>,
+    pub padding: Vec<Padding>,
+}
+
+impl FieldAttributes {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self, Error> {
+        let mut result = Self::default();
+        for item in packet_items(attrs)? {
+            let wire = match &item {
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("pad") => {
+                    for pad in list.nested.iter() {
+                        match pad {
+                            NestedMeta::Meta(Meta::NameValue(nv)) => result.padding.push(Padding {
+                                method: method(&nv.path)?,
+                                value: nv.lit.clone(),
+                            }),
+                            pad => return Err(Error::new_spanned(pad, "expected method = value")),
+                        }
+                    }
+                    continue;
+                }
+                NestedMeta::Meta(Meta::Path(path)) => Wire {
+                    method: method(path)?,
+                    max: None,
+                    each: false,
+                },
+                NestedMeta::Meta(Meta::List(list)) if list.nested.len() == 1 => {
+                    match (list.path.is_ident("each"), &list.nested[0]) {
+                        (true, NestedMeta::Meta(Meta::Path(path))) => Wire {
+                            method: method(path)?,
+                            max: None,
+                            each: true,
+                        },
+                        (false, NestedMeta::Lit(lit)) => Wire {
+                            method: method(&list.path)?,
+                            max: Some(lit.into_token_stream()),
+                            each: false,
+                        },
+                        (false, NestedMeta::Meta(Meta::Path(path))) => Wire {
+                            method: method(&list.path)?,
+                            max: Some(path.into_token_stream()),
+                            each: false,
+                        },
+                        _ => return Err(Error::new_spanned(list, "unknown packet attribute")),
+                    }
+                }
+                item => return Err(Error::new_spanned(item, "unknown packet attribute")),
+            };
+            if result.wire.is_some() {
+                return Err(Error::new_spanned(
+                    item,
+                    "the field already has a wire type",
+                ));
+            }
+            result.wire = Some(wire);
+        }
+        Ok(result)
+    }
+}
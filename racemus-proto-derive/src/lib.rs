@@ -0,0 +1,387 @@
+extern crate proc_macro;
+
+mod attributes;
+
+use attributes::*;
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Ident, Type, Variant};
+
+// Implements reading and/or writing an enum of packets, and adds a method that
+// looks up the id of each packet in a protocol version. Every field names the
+// BinaryReader/BinaryWriter method that is used for it, values are converted
+// to and from the type of the method with TryFrom and Into.
+#[proc_macro_derive(Packet, attributes(packet))]
+pub fn derive_packet(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(r) => r.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+// Readers that take an optional maximum length
+const LENGTH_PREFIXED: &[&str] = &["arr_char", "arr_u8", "arr_u8_remainder"];
+
+struct Field<'a> {
+    ident: &'a Ident,
+    ty: &'a Type,
+    attributes: FieldAttributes,
+}
+
+struct Packet<'a> {
+    variant: &'a Variant,
+    attributes: VariantAttributes,
+    // None for unit variants
+    fields: Option<Vec<Field<'a>>>,
+}
+
+impl Packet<'_> {
+    fn fields(&self) -> impl Iterator<Item = &Field<'_>> {
+        self.fields.iter().flatten()
+    }
+
+    // Matches or constructs the variant, with the fields bound to their names
+    fn pattern(&self, name: &Ident) -> TokenStream2 {
+        let ident = &self.variant.ident;
+        match &self.fields {
+            Some(fields) => {
+                let idents = fields.iter().map(|f| f.ident);
+                quote! { #name::#ident { #(#idents),* } }
+            }
+            None => quote! { #name::#ident },
+        }
+    }
+
+    // Matches the variant without binding any fields
+    fn wildcard(&self, name: &Ident) -> TokenStream2 {
+        let ident = &self.variant.ident;
+        match &self.fields {
+            Some(_) => quote! { #name::#ident { .. } },
+            None => quote! { #name::#ident },
+        }
+    }
+}
+
+// Matches the protocol version and packet id of the packet
+fn id_pattern(id: &Id) -> TokenStream2 {
+    match id {
+        Id::All(id) => quote! { (_, #id) },
+        Id::Versions(ids) => {
+            let ids = ids
+                .iter()
+                .map(|(version, id)| quote! { (crate::proto::ProtocolVersion::#version, #id) });
+            quote! { #(#ids)|* }
+        }
+    }
+}
+
+fn packets(input: &DeriveInput) -> Result<Vec<Packet<'_>>, Error> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => return Err(Error::new_spanned(input, "packets must be enums")),
+    };
+
+    let mut result = Vec::with_capacity(data.variants.len());
+    for variant in data.variants.iter() {
+        let attributes = VariantAttributes::parse(&variant.attrs)?;
+        let kinds = [attributes.id.is_some(), attributes.unknown, attributes.skip];
+        if kinds.iter().filter(|k| **k).count() != 1 {
+            return Err(Error::new_spanned(
+                variant,
+                "expected one of #[packet(id = ...)], #[packet(unknown)] or #[packet(skip)]",
+            ));
+        }
+
+        let fields = match &variant.fields {
+            Fields::Named(named) => {
+                let mut fields = Vec::with_capacity(named.named.len());
+                for field in named.named.iter() {
+                    fields.push(Field {
+                        ident: field.ident.as_ref().unwrap(),
+                        ty: &field.ty,
+                        attributes: FieldAttributes::parse(&field.attrs)?,
+                    });
+                }
+                Some(fields)
+            }
+            Fields::Unit => None,
+            Fields::Unnamed(_) => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "tuple variants are not supported",
+                ))
+            }
+        };
+        if attributes.unknown && fields.as_ref().map(Vec::len) != Some(1) {
+            return Err(Error::new_spanned(
+                variant,
+                "unknown packets need a single field for the packet id",
+            ));
+        }
+        result.push(Packet {
+            variant,
+            attributes,
+            fields,
+        });
+    }
+    Ok(result)
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let attributes = ContainerAttributes::parse(&input.attrs)?;
+    let packets = packets(input)?;
+
+    let name = &input.ident;
+    let ids = packets.iter().map(|packet| {
+        let pattern = packet.wildcard(name);
+        match &packet.attributes.id {
+            Some(Id::All(id)) => quote! { #pattern => ::std::option::Option::Some(#id), },
+            Some(Id::Versions(ids)) => {
+                let ids = ids.iter().map(|(version, id)| {
+                    quote! { crate::proto::ProtocolVersion::#version => ::std::option::Option::Some(#id), }
+                });
+                quote! {
+                    #pattern => match version {
+                        #(#ids)*
+                        _ => ::std::option::Option::None,
+                    },
+                }
+            }
+            None if packet.attributes.unknown => {
+                let pattern = packet.pattern(name);
+                let field = packet.fields().next().map(|f| f.ident);
+                quote! { #pattern => ::std::option::Option::Some(*#field), }
+            }
+            None => quote! { #pattern => ::std::option::Option::None, },
+        }
+    });
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut result = quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            #[allow(unreachable_patterns, unused_variables)]
+            pub fn packet_id(
+                &self,
+                version: crate::proto::ProtocolVersion,
+            ) -> ::std::option::Option<i32> {
+                match self {
+                    #(#ids)*
+                }
+            }
+        }
+    };
+    if let Some(read) = &attributes.read {
+        result.extend(expand_read(input, read, &packets)?);
+    }
+    if attributes.write {
+        result.extend(expand_write(input, &packets)?);
+    }
+    Ok(result)
+}
+
+fn length_prefixed(method: &Ident) -> bool {
+    LENGTH_PREFIXED.iter().any(|m| method == m)
+}
+
+fn wire<'a>(packet: &Packet, field: &'a Field) -> Result<&'a Wire, Error> {
+    match &field.attributes.wire {
+        Some(wire) => Ok(wire),
+        None => Err(Error::new_spanned(
+            packet.variant,
+            format!("{} needs a wire type, e.g. #[packet(var_i32)]", field.ident),
+        )),
+    }
+}
+
+fn read_field(packet: &Packet, field: &Field) -> Result<TokenStream2, Error> {
+    let padding = field.attributes.padding.iter().map(|pad| {
+        let method = &pad.method;
+        if length_prefixed(method) {
+            quote! { self.#method(::std::option::Option::None).await?; }
+        } else {
+            quote! { self.#method().await?; }
+        }
+    });
+
+    let wire = wire(packet, field)?;
+    let method = &wire.method;
+    let args = match &wire.max {
+        Some(max) => quote! { ::std::option::Option::Some(#max) },
+        None if length_prefixed(method) => quote! { ::std::option::Option::None },
+        None => quote! {},
+    };
+    let value = quote! { ::std::convert::TryFrom::try_from(self.#method(#args).await?)? };
+
+    let ident = field.ident;
+    let ty = field.ty;
+    if wire.each {
+        Ok(quote! {
+            #(#padding)*
+            let mut #ident: #ty = ::std::default::Default::default();
+            for value in #ident.iter_mut() {
+                *value = #value;
+            }
+        })
+    } else {
+        Ok(quote! {
+            #(#padding)*
+            let #ident: #ty = #value;
+        })
+    }
+}
+
+fn expand_read(
+    input: &DeriveInput,
+    read: &Ident,
+    packets: &[Packet],
+) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let mut arms = Vec::with_capacity(packets.len());
+    let mut unknown = None;
+    for packet in packets.iter() {
+        if packet.attributes.skip {
+            continue;
+        }
+        if packet.attributes.unknown {
+            let variant = &packet.variant.ident;
+            let field = packet.fields().next().map(|f| f.ident);
+            unknown = Some(quote! { #name::#variant { #field: packet_id } });
+            continue;
+        }
+        if packet.attributes.with.is_some() {
+            return Err(Error::new_spanned(
+                packet.variant,
+                "packets with custom writers can't be read",
+            ));
+        }
+
+        let id = match &packet.attributes.id {
+            Some(id) => id_pattern(id),
+            None => unreachable!(),
+        };
+        let reads = packet
+            .fields()
+            .map(|field| read_field(packet, field))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let result = packet.pattern(name);
+        arms.push(quote! {
+            #id => {
+                #(#reads)*
+                ::std::result::Result::Ok(#result)
+            }
+        });
+    }
+
+    let unknown = match unknown {
+        Some(r) => r,
+        None => {
+            return Err(Error::new_spanned(
+                input,
+                "readable packets need a #[packet(unknown)] variant",
+            ))
+        }
+    };
+    Ok(quote! {
+        impl<R: ::async_std::io::Read + ::std::marker::Unpin> crate::BinaryReader<R> {
+            pub async fn #read(&mut self) -> ::std::result::Result<#name, crate::Error> {
+                let packet_id = self.packet_header().await?;
+                let version = self.protocol_version();
+                match (version, packet_id) {
+                    #(#arms)*
+                    _ => ::std::result::Result::Ok(#unknown),
+                }
+            }
+        }
+    })
+}
+
+fn write_field(packet: &Packet, field: &Field) -> Result<TokenStream2, Error> {
+    let padding = field.attributes.padding.iter().map(|pad| {
+        let method = &pad.method;
+        let value = &pad.value;
+        quote! { self.#method(#value)?; }
+    });
+
+    let wire = wire(packet, field)?;
+    let method = &wire.method;
+    let ident = field.ident;
+    if wire.each {
+        Ok(quote! {
+            #(#padding)*
+            for value in #ident.iter() {
+                self.#method(::std::convert::Into::into(*value))?;
+            }
+        })
+    } else {
+        Ok(quote! {
+            #(#padding)*
+            self.#method(::std::convert::Into::into(*#ident))?;
+        })
+    }
+}
+
+fn expand_write(input: &DeriveInput, packets: &[Packet]) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let mut arms = Vec::with_capacity(packets.len());
+    for packet in packets.iter() {
+        if packet.attributes.id.is_none() {
+            return Err(Error::new_spanned(
+                packet.variant,
+                "only packets with an id can be written",
+            ));
+        }
+
+        let body = match &packet.attributes.with {
+            Some(with) => {
+                let idents = packet.fields().map(|f| f.ident);
+                quote! { self.#with(#(#idents),*)?; }
+            }
+            None => {
+                let writes = packet
+                    .fields()
+                    .map(|field| write_field(packet, field))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                quote! { #(#writes)* }
+            }
+        };
+        let pattern = packet.pattern(name);
+        arms.push(quote! {
+            #pattern => {
+                #body
+            }
+        });
+    }
+
+    let mut generics = input.generics.clone();
+    generics
+        .params
+        .push(parse_quote!(W: ::async_std::io::Write + ::std::marker::Unpin));
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics crate::StructuredWriter<W, #name #ty_generics> for crate::BinaryWriter<W>
+        #where_clause
+        {
+            fn structure(
+                &mut self,
+                val: &#name #ty_generics,
+            ) -> ::std::result::Result<&mut Self, crate::Error> {
+                let version = self.protocol_version();
+                let packet_id = match val.packet_id(version) {
+                    ::std::option::Option::Some(r) => r,
+                    ::std::option::Option::None => {
+                        return ::std::result::Result::Err(crate::ErrorKind::UnsupportedPacket.into())
+                    }
+                };
+                let packet = self.start_packet();
+                self.var_i32(packet_id)?;
+                match val {
+                    #(#arms)*
+                }
+                self.complete_packet(packet)
+            }
+        }
+    })
+}
@@ -0,0 +1,288 @@
+use super::*;
+use crate::models::*;
+use log::trace;
+use racemus_binary::{nbt::ListView, PalettedContainer, VarVec, SECTION_VOLUME};
+use racemus_mc::blocks::{Block, BlockState};
+
+// Vanilla saves chunks before they are completely generated, those chunks
+// are treated as if they do not exist.
+const STATUS_FULL: &str = "full";
+
+// Converts the NBT of a vanilla 1.15 chunk. Returns None if the chunk has
+// not been completely generated.
+pub fn import_chunk(value: &NbtView<'_>) -> Result<Option<Chunk>, Error> {
+    let level = field(compound(value)?, "Level")?;
+    let level = compound(&level)?;
+    if str_field(level, "Status")? != STATUS_FULL {
+        return Ok(None);
+    }
+
+    let position = vek::Vec2::new(int_field(level, "xPos")?, int_field(level, "zPos")?);
+    let mut chunk = Chunk::new(position);
+
+    match level.get("Biomes")? {
+        Some(NbtView::IntArray(biomes)) if biomes.len() == BIOME_COUNT => {
+            // Both use one biome per 4x4x4 cell, ordered by y, z, then x
+            for (index, biome) in biomes.iter().enumerate() {
+                let x = (index & 0b11) * 4;
+                let z = ((index >> 2) & 0b11) * 4;
+                let y = (index >> 4) * 4;
+                chunk.set_biome(x, y, z, biome);
+            }
+        }
+        Some(_) => return Err(ErrorKind::InvalidNbt.into()),
+        None => {}
+    }
+
+    let sections = match level.get("Sections")? {
+        Some(NbtView::List(r)) => Some(r),
+        Some(_) => return Err(ErrorKind::InvalidNbt.into()),
+        None => None,
+    };
+
+    let mut light = LightData::new();
+    for section in sections.iter().flat_map(ListView::iter) {
+        let section = section?;
+        let section = compound(&section)?;
+        // Light is also saved for the sections directly below and above
+        let y = int_field(section, "Y")?;
+        if y < -1 || y > CHUNK_SECTIONS as i32 {
+            return Err(ErrorKind::InvalidNbt.into());
+        }
+        let light_index = (y + 1) as usize;
+
+        if let Some(layer) = light_layer(section, "BlockLight")? {
+            light.set_block_layer(light_index, Some(layer));
+        }
+        if let Some(layer) = light_layer(section, "SkyLight")? {
+            light.set_sky_layer(light_index, Some(layer));
+        }
+
+        if let (Some(palette), Some(states)) =
+            (section.get("Palette")?, section.get("BlockStates")?)
+        {
+            if y < 0 || y >= CHUNK_SECTIONS as i32 {
+                return Err(ErrorKind::InvalidNbt.into());
+            }
+            let section = import_section(&palette, &states)?;
+            if !section.is_empty() {
+                chunk.set_section(y as usize, Some(section));
+            }
+        }
+    }
+    chunk.set_light(light);
+
+    Ok(Some(chunk))
+}
+
+// Whether vanilla had finished lighting the chunk when it was saved, in
+// which case the saved light can be used as it is.
+pub fn is_lit(value: &NbtView<'_>) -> bool {
+    let level = match value.as_compound().map(|r| r.get("Level")) {
+        Some(Ok(Some(r))) => r,
+        _ => return false,
+    };
+    match level.as_compound().map(|r| r.get("isLightOn")) {
+        Some(Ok(Some(r))) => r.as_bool().unwrap_or(false),
+        _ => false,
+    }
+}
+
+fn light_layer(section: &CompoundView<'_>, name: &str) -> Result<Option<LightLayer>, Error> {
+    match section.get(name)? {
+        Some(NbtView::ByteArray(data)) => match LightLayer::from_bytes(data) {
+            Some(r) => Ok(Some(r)),
+            None => Err(ErrorKind::InvalidNbt.into()),
+        },
+        Some(_) => Err(ErrorKind::InvalidNbt.into()),
+        None => Ok(None),
+    }
+}
+
+fn import_section(palette: &NbtView<'_>, states: &NbtView<'_>) -> Result<ChunkSection, Error> {
+    let palette = match palette {
+        NbtView::List(r) => r
+            .iter()
+            .map(|state| import_block_state(&state?))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => return Err(ErrorKind::InvalidNbt.into()),
+    };
+    let states: Vec<u64> = match states {
+        NbtView::LongArray(r) => r.iter().map(|l| l as u64).collect(),
+        _ => return Err(ErrorKind::InvalidNbt.into()),
+    };
+
+    // Entries span longs, so every bit is used
+    let bits_per_entry = states.len() * 64 / SECTION_VOLUME;
+    let entries = match VarVec::from_raw(states, SECTION_VOLUME, bits_per_entry as u8) {
+        Some(r) => r,
+        None => return Err(ErrorKind::InvalidNbt.into()),
+    };
+
+    // The palette is always local in Anvil files, even when the network
+    // protocol would use the global palette.
+    let mut blocks = PalettedContainer::new();
+    for index in 0..SECTION_VOLUME {
+        let entry = entries.get(index).unwrap() as usize;
+        match palette.get(entry) {
+            Some(state) => blocks.set_index(index, *state),
+            None => return Err(ErrorKind::InvalidNbt.into()),
+        };
+    }
+    Ok(ChunkSection::from_blocks(blocks))
+}
+
+fn import_block_state(value: &NbtView<'_>) -> Result<u32, Error> {
+    let value = compound(value)?;
+    let name = str_field(value, "Name")?;
+
+    let mut values = Vec::new();
+    if let Some(properties) = value.get("Properties")? {
+        for entry in compound(&properties)?.iter() {
+            match entry? {
+                (name, NbtView::String(value)) => values.push((name, value)),
+                _ => return Err(ErrorKind::InvalidNbt.into()),
+            }
+        }
+    }
+    let properties: Vec<_> = values
+        .iter()
+        .map(|(name, value)| (name.as_ref(), value.as_ref()))
+        .collect();
+
+    let state = BlockState::from_name(&name, &properties)
+        .or_else(|| Block::by_name(&name).map(Block::default_state));
+    match state {
+        Some(state) => Ok(state.id()),
+        None => {
+            trace!("unknown block {}, using air", name);
+            Ok(AIR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use racemus_binary::{
+        nbt::{encode_nbt, view_nbt, NbtCompression},
+        nbt_compound, nbt_list,
+        proto::LightSection,
+    };
+
+    const STONE: u32 = 1;
+    const OAK_LOG_X: u32 = 72;
+
+    fn block_state(name: &str, properties: Value) -> Value {
+        nbt_compound! {
+            "Name" => Value::String(name.into()),
+            "Properties" => properties
+        }
+    }
+
+    fn section(y: i8) -> Value {
+        let mut states = VarVec::with_capacity(SECTION_VOLUME, 4);
+        states.set(PalettedContainer::index(1, 2, 3), 1);
+        states.set(PalettedContainer::index(4, 5, 6), 2);
+        states.set(PalettedContainer::index(7, 8, 9), 3);
+        let states: Vec<i64> = states.get_inner().iter().map(|l| *l as i64).collect();
+
+        let mut block_light = LightLayer::new();
+        block_light.set(1, 2, 3, 14);
+
+        nbt_compound! {
+            "Y" => Value::Byte(y),
+            "Palette" => nbt_list! {
+                block_state("minecraft:air", nbt_compound! {}),
+                block_state("minecraft:stone", nbt_compound! {}),
+                block_state("minecraft:oak_log", nbt_compound! {
+                    "axis" => Value::String("x".into())
+                }),
+                block_state("minecraft:unknown", nbt_compound! {})
+            },
+            "BlockStates" => Value::LongArray(states.into()),
+            "BlockLight" => Value::ByteArray(block_light.as_bytes().into())
+        }
+    }
+
+    fn chunk(status: &str) -> Value {
+        let mut biomes = vec![1; BIOME_COUNT];
+        biomes[(2 * 4 + 3) * 4 + 1] = 2;
+
+        nbt_compound! {
+            "DataVersion" => Value::Int(2230),
+            "Level" => nbt_compound! {
+                "xPos" => Value::Int(-3),
+                "zPos" => Value::Int(7),
+                "Status" => Value::String(status.into()),
+                "isLightOn" => Value::Byte(1),
+                "Biomes" => Value::IntArray(biomes.into()),
+                "Sections" => nbt_list! {
+                    nbt_compound! {
+                        "Y" => Value::Byte(-1),
+                        "SkyLight" => Value::ByteArray(LightLayer::filled(15).as_bytes().into())
+                    },
+                    section(2)
+                }
+            }
+        }
+    }
+
+    fn encode(value: &Value) -> Result<Vec<u8>, Error> {
+        async_std::task::block_on(encode_nbt("", value, NbtCompression::None))
+    }
+
+    #[test]
+    pub fn import_chunk_full() -> Result<(), Error> {
+        let data = encode(&chunk("full"))?;
+        let (_, value) = view_nbt(&data)?;
+        assert!(is_lit(&value));
+        let chunk = import_chunk(&value)?.unwrap();
+        assert_eq!(chunk.position(), vek::Vec2::new(-3, 7));
+        assert_eq!(chunk.get(1, 34, 3), STONE);
+        assert_eq!(chunk.get(4, 37, 6), OAK_LOG_X);
+        assert_eq!(chunk.get(7, 40, 9), AIR);
+        assert_eq!(chunk.get(1, 2, 3), AIR);
+        assert!(chunk.section(0).is_none());
+        assert_eq!(chunk.height(HeightmapKind::WorldSurface, 4, 6), 38);
+
+        assert_eq!(chunk.biome(4, 8, 12), 2);
+        assert_eq!(chunk.biome(0, 0, 0), 1);
+
+        assert_eq!(chunk.light().block(1, 34, 3), 14);
+        assert_eq!(chunk.light().sky_layer(0), Some(&LightLayer::filled(15)));
+        assert!(chunk.light().sky_layer(3).is_none());
+
+        // Vanilla doesn't save sky light above the terrain, so the client
+        // has to work it out instead of being told that it is dark
+        let sky = chunk.light().sky_sections();
+        assert_eq!(
+            sky[0],
+            LightSection::Data(LightLayer::filled(15).as_bytes())
+        );
+        assert_eq!(sky[3], LightSection::Unchanged);
+        assert_eq!(sky[17], LightSection::Unchanged);
+        Ok(())
+    }
+
+    #[test]
+    pub fn import_chunk_incomplete() -> Result<(), Error> {
+        let data = encode(&chunk("features"))?;
+        let (_, value) = view_nbt(&data)?;
+        assert!(import_chunk(&value)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    pub fn import_chunk_invalid() -> Result<(), Error> {
+        let palette = encode(&nbt_list! { block_state("minecraft:stone", nbt_compound! {}) })?;
+        let (_, palette) = view_nbt(&palette)?;
+        let states = encode(&Value::LongArray(vec![0i64; 3].into()))?;
+        assert!(import_section(&palette, &view_nbt(&states)?.1).is_err());
+
+        // Every entry refers past the end of the palette
+        let states = encode(&Value::LongArray(vec![-1i64; 256].into()))?;
+        assert!(import_section(&palette, &view_nbt(&states)?.1).is_err());
+        Ok(())
+    }
+}
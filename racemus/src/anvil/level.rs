@@ -0,0 +1,79 @@
+use super::*;
+use racemus_binary::nbt::{self, FromNbt, NbtCompound};
+
+const LEVEL_FILE: &str = "level.dat";
+
+// The parts of level.dat that the server uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelData {
+    pub name: Arc<str>,
+    pub seed: i64,
+    pub spawn_position: vek::Vec3<i32>,
+}
+
+// The parts of the level.dat layout that are read
+#[derive(NbtCompound)]
+#[nbt(rename_all = "PascalCase")]
+struct LevelFile {
+    data: LevelFileData,
+}
+
+#[derive(NbtCompound)]
+#[nbt(rename_all = "PascalCase")]
+struct LevelFileData {
+    level_name: Arc<str>,
+    random_seed: i64,
+    spawn_x: i32,
+    spawn_y: i32,
+    spawn_z: i32,
+}
+
+impl LevelData {
+    pub fn from_nbt(value: &Value) -> Result<Self, Error> {
+        let data = LevelFile::from_nbt(value)?.data;
+        Ok(Self {
+            name: data.level_name,
+            seed: data.random_seed,
+            spawn_position: vek::Vec3::new(data.spawn_x, data.spawn_y, data.spawn_z),
+        })
+    }
+}
+
+impl AnvilWorld {
+    pub fn has_level(&self) -> bool {
+        self.path.join(LEVEL_FILE).is_file()
+    }
+
+    pub async fn read_level(&self) -> Result<LevelData, Error> {
+        let (_, value, _) = nbt::read_nbt_file(self.path.join(LEVEL_FILE)).await?;
+        LevelData::from_nbt(&value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use racemus_binary::nbt_compound;
+
+    #[test]
+    pub fn level_data_from_nbt() {
+        let value = nbt_compound! {
+            "Data" => nbt_compound! {
+                "LevelName" => Value::String("New World".into()),
+                "RandomSeed" => Value::Long(-4_172_144_997_902_289_642),
+                "SpawnX" => Value::Int(-208),
+                "SpawnY" => Value::Int(64),
+                "SpawnZ" => Value::Int(240)
+            }
+        };
+        assert_eq!(
+            LevelData::from_nbt(&value).unwrap(),
+            LevelData {
+                name: "New World".into(),
+                seed: -4_172_144_997_902_289_642,
+                spawn_position: vek::Vec3::new(-208, 64, 240),
+            }
+        );
+        assert!(LevelData::from_nbt(&nbt_compound! {}).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+mod chunk;
+mod level;
+
+pub use chunk::*;
+pub use level::*;
+
+use racemus_binary::{
+    nbt::{CompoundView, NbtView, Value},
+    region::{ExternalChunks, RegionReader, REGION_WIDTH},
+    Error, ErrorKind,
+};
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const REGION_DIRECTORY: &str = "region";
+
+// A vanilla world directory, containing level.dat and the region files.
+#[derive(Debug, Clone)]
+pub struct AnvilWorld {
+    path: PathBuf,
+}
+
+impl AnvilWorld {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn region_path(&self, region: vek::Vec2<i32>) -> PathBuf {
+        self.path
+            .join(REGION_DIRECTORY)
+            .join(format!("r.{}.{}.mca", region.x, region.y))
+    }
+
+    // Reads the uncompressed NBT of a chunk, or None if the chunk has not
+    // been saved. Chunks are large, so they are read with nbt::view_nbt
+    // rather than being parsed.
+    pub async fn read_chunk(&self, position: vek::Vec2<i32>) -> Result<Option<Vec<u8>>, Error> {
+        let width = REGION_WIDTH as i32;
+        let region = vek::Vec2::new(position.x.div_euclid(width), position.y.div_euclid(width));
+        let file = match async_std::fs::File::open(self.region_path(region)).await {
+            Ok(r) => r,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let external = ExternalChunks::new(self.path.join(REGION_DIRECTORY), region.into_array());
+        let mut reader = RegionReader::new(file)
+            .await?
+            .with_external_chunks(external);
+        reader
+            .chunk_data(
+                position.x.rem_euclid(width) as usize,
+                position.y.rem_euclid(width) as usize,
+            )
+            .await
+    }
+}
+
+// Missing fields and fields with the wrong type make the NBT invalid
+fn required<T>(value: Option<T>) -> Result<T, Error> {
+    match value {
+        Some(r) => Ok(r),
+        None => Err(ErrorKind::InvalidNbt.into()),
+    }
+}
+
+fn compound<'a, 'b>(value: &'b NbtView<'a>) -> Result<&'b CompoundView<'a>, Error> {
+    required(value.as_compound())
+}
+
+fn field<'a>(value: &CompoundView<'a>, name: &str) -> Result<NbtView<'a>, Error> {
+    required(value.get(name)?)
+}
+
+fn int_field(value: &CompoundView<'_>, name: &str) -> Result<i32, Error> {
+    required(field(value, name)?.as_i32())
+}
+
+fn str_field<'a>(value: &CompoundView<'a>, name: &str) -> Result<Cow<'a, str>, Error> {
+    match field(value, name)? {
+        NbtView::String(r) => Ok(r),
+        _ => Err(ErrorKind::InvalidNbt.into()),
+    }
+}
@@ -1,8 +1,13 @@
-use crate::models::*;
+use crate::{
+    anvil::AnvilWorld,
+    generator::{FlatLayer, GeneratorKind},
+    models::*,
+};
 use async_std::prelude::*;
+use racemus_mc::blocks::BlockState;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use serde_derive::Deserialize;
-use std::{convert::TryFrom, convert::TryInto, error::Error, sync::Arc};
+use std::{convert::TryFrom, convert::TryInto, error::Error, path::PathBuf, sync::Arc};
 
 #[derive(Debug)]
 pub enum ConfigError {
@@ -29,6 +34,8 @@ struct RawConfig {
     security: RawSecurityConfig,
     #[serde(rename = "game", default = "game_default")]
     game: RawGameConfig,
+    #[serde(rename = "world", default = "world_default")]
+    world: RawWorldConfig,
 }
 
 impl RawConfig {
@@ -170,17 +177,94 @@ fn enable_respawn_screen_default() -> bool {
     true
 }
 
+#[derive(Deserialize)]
+struct RawWorldConfig {
+    #[serde(rename = "generator", default = "generator_default")]
+    generator: String,
+    #[serde(rename = "layers", default = "layers_default")]
+    layers: Vec<RawFlatLayer>,
+    #[serde(rename = "biome", default = "biome_default")]
+    biome: i32,
+    #[serde(rename = "chunk-cache-size", default = "chunk_cache_size_default")]
+    chunk_cache_size: usize,
+    #[serde(rename = "path", default)]
+    path: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawFlatLayer {
+    #[serde(rename = "block")]
+    block: String,
+    #[serde(rename = "height", default = "layer_height_default")]
+    height: usize,
+}
+
+fn world_default() -> RawWorldConfig {
+    RawWorldConfig {
+        generator: generator_default(),
+        layers: layers_default(),
+        biome: biome_default(),
+        chunk_cache_size: chunk_cache_size_default(),
+        path: None,
+    }
+}
+
+fn generator_default() -> String {
+    "flat".to_string()
+}
+
+fn layers_default() -> Vec<RawFlatLayer> {
+    vec![
+        RawFlatLayer {
+            block: "minecraft:bedrock".to_string(),
+            height: 1,
+        },
+        RawFlatLayer {
+            block: "minecraft:dirt".to_string(),
+            height: 2,
+        },
+        RawFlatLayer {
+            block: "minecraft:grass_block".to_string(),
+            height: 1,
+        },
+    ]
+}
+
+fn layer_height_default() -> usize {
+    1
+}
+
+fn biome_default() -> i32 {
+    1
+}
+
+fn chunk_cache_size_default() -> usize {
+    256
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     network: NetworkConfig,
     security: SecurityConfig,
     game: GameConfig,
+    world: WorldConfig,
 }
 
 impl<'a> Config {
     pub async fn read(file_name: &str) -> Result<Self, Box<dyn Error>> {
         let raw = RawConfig::read(file_name).await?;
-        Config::try_from(raw)
+        let mut config = Config::try_from(raw)?;
+
+        // An existing vanilla world decides the seed and spawn
+        if let Some(path) = config.world.path() {
+            let world = AnvilWorld::new(path);
+            if world.has_level() {
+                let level = world.read_level().await?;
+                config.game.seed = level.seed as u64;
+                config.game.spawn_position = level.spawn_position;
+            }
+        }
+        Ok(config)
     }
 
     pub fn network(&'a self) -> &'a NetworkConfig {
@@ -192,6 +276,9 @@ impl<'a> Config {
     pub fn game(&'a self) -> &'a GameConfig {
         &self.game
     }
+    pub fn world(&'a self) -> &'a WorldConfig {
+        &self.world
+    }
 }
 
 impl TryFrom<RawConfig> for Config {
@@ -210,11 +297,16 @@ impl TryFrom<RawConfig> for Config {
             Ok(r) => r,
             Err(e) => return Err(e),
         };
+        let world = match WorldConfig::try_from(value.world) {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
 
         Ok(Self {
             network,
             security,
             game,
+            world,
         })
     }
 }
@@ -291,6 +383,7 @@ impl SecurityConfig {
 #[derive(Debug, Clone)]
 pub struct GameConfig {
     seed: u64,
+    spawn_position: vek::Vec3<i32>,
     game_mode: GameMode,
     difficulty: Difficulty,
     view_distance: u8,
@@ -300,9 +393,19 @@ pub struct GameConfig {
 }
 
 impl GameConfig {
+    // The world seed, which the generator uses
     pub fn seed(&self) -> u64 {
         self.seed
     }
+    // Clients are sent this instead of the seed, which must not be revealed.
+    // Like vanilla it is the start of the SHA-256 of the seed.
+    pub fn hashed_seed(&self) -> u64 {
+        let digest = ring::digest::digest(&ring::digest::SHA256, &self.seed.to_le_bytes());
+        u64::from_le_bytes(digest.as_ref()[0..8].try_into().unwrap())
+    }
+    pub fn spawn_position(&self) -> vek::Vec3<i32> {
+        self.spawn_position
+    }
     pub fn game_mode(&self) -> GameMode {
         self.game_mode
     }
@@ -361,6 +464,7 @@ impl TryFrom<RawGameConfig> for GameConfig {
 
         Ok(Self {
             seed,
+            spawn_position: vek::Vec3::new(0, 255, 0),
             game_mode,
             difficulty,
             view_distance,
@@ -370,3 +474,70 @@ impl TryFrom<RawGameConfig> for GameConfig {
         })
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct WorldConfig {
+    generator: GeneratorKind,
+    flat_layers: Arc<[FlatLayer]>,
+    biome: i32,
+    chunk_cache_size: usize,
+    path: Option<PathBuf>,
+}
+
+impl WorldConfig {
+    pub fn generator(&self) -> GeneratorKind {
+        self.generator
+    }
+    pub fn flat_layers(&self) -> &[FlatLayer] {
+        &self.flat_layers
+    }
+    pub fn biome(&self) -> i32 {
+        self.biome
+    }
+    // In bytes
+    pub fn chunk_cache_size(&self) -> usize {
+        self.chunk_cache_size
+    }
+    pub fn path(&self) -> Option<&std::path::Path> {
+        self.path.as_deref()
+    }
+}
+
+impl TryFrom<RawWorldConfig> for WorldConfig {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: RawWorldConfig) -> Result<Self, Self::Error> {
+        let generator = match value.generator.as_str() {
+            "flat" => GeneratorKind::Flat,
+            "overworld" => GeneratorKind::Overworld,
+            _ => return Err(ConfigError::InvalidValue("world.generator".to_string()).into()),
+        };
+
+        let mut flat_layers = Vec::with_capacity(value.layers.len());
+        for layer in value.layers {
+            let block = match layer.block.parse::<BlockState>() {
+                Ok(r) => r,
+                Err(_) => return Err(ConfigError::InvalidValue("world.layers".to_string()).into()),
+            };
+            flat_layers.push(FlatLayer {
+                block,
+                height: layer.height,
+            });
+        }
+
+        Ok(Self {
+            generator,
+            flat_layers: flat_layers.into(),
+            biome: value.biome,
+            path: value.path.map(PathBuf::from),
+            chunk_cache_size: match value.chunk_cache_size.checked_mul(1024 * 1024) {
+                Some(r) => r,
+                None => {
+                    return Err(
+                        ConfigError::InvalidValue("world.chunk-cache-size".to_string()).into(),
+                    )
+                }
+            },
+        })
+    }
+}
@@ -8,12 +8,15 @@ use racemus_tools::crypto::insecure::InsecurePrivateKey;
 use crate::controllers::{player, Controllers};
 use async_std::{
     io::{Read, Write},
-    sync::Receiver,
+    prelude::*,
+    task,
 };
 use log::{error, info, trace};
 use rand::{self, RngCore};
 use std::{error::Error, net::SocketAddr, sync::Arc};
 
+type SendError = Box<dyn Error + Send + Sync>;
+
 #[derive(Debug)]
 pub enum ConnectionError {
     NotImplemented,
@@ -24,6 +27,7 @@ pub enum ConnectionError {
     UnsupportedVersion,
     AuthenticationFailed,
     UnknownPacketType(i32),
+    Kicked(Arc<str>),
 }
 
 impl Error for ConnectionError {}
@@ -39,6 +43,7 @@ impl std::fmt::Display for ConnectionError {
             Self::UnsupportedVersion => write!(f, "client not supported"),
             Self::AuthenticationFailed => write!(f, "authentication failed"),
             Self::UnknownPacketType(packet_id) => write!(f, "unknown packet type: {}", packet_id),
+            Self::Kicked(reason) => write!(f, "{}", reason),
         }
     }
 }
@@ -50,9 +55,9 @@ pub struct Connection<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send
     player_uuid: Option<Arc<str>>,
     player_name: Option<Arc<str>>,
     verify: Option<Vec<u8>>,
-    reader: BinaryReader<R>,
+    reader: Option<BinaryReader<R>>,
     writer: BinaryWriter<W>,
-    recv: Option<Receiver<ClientMessage>>,
+    recv: Option<ClientReceiver>,
     version: Option<i32>,
     controllers: Controllers,
 }
@@ -93,7 +98,7 @@ impl<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send + 'static> Connec
         controllers: Controllers,
     ) -> Self {
         let writer = BinaryWriter::new(writer);
-        let reader = BinaryReader::new(reader);
+        let reader = Some(BinaryReader::new(reader));
         Self {
             addr,
             reader,
@@ -110,7 +115,7 @@ impl<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send + 'static> Connec
     }
 
     pub fn execute(mut self) {
-        async_std::task::spawn(async move {
+        task::spawn(async move {
             let e = loop {
                 let result = match self.state {
                     ConnectionState::Open => self.execute_open().await,
@@ -156,26 +161,42 @@ impl<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send + 'static> Connec
         }
     }
 
+    fn reader(&mut self) -> Result<&mut BinaryReader<R>, ConnectionError> {
+        match &mut self.reader {
+            Some(reader) => Ok(reader),
+            // The reader is taken by execute_game, which reads alongside the
+            // writer until the game ends
+            None => Err(ConnectionError::InvalidTransition),
+        }
+    }
+
     async fn execute_open(&mut self) -> Result<(), Box<dyn Error>> {
-        match self.reader.read_open().await? {
+        match self.reader()?.read_open().await? {
             OpenRequest::Handshake {
                 address: _,
                 port: _,
                 version,
                 next_state,
-            } => match next_state {
-                RequestedState::Login => {
-                    trace!("{} request to transition to login state", self);
-                    self.version = Some(version);
-                    self.state = ConnectionState::AwaitingLogin;
-                    Ok(())
+            } => {
+                self.version = Some(version);
+                // Unsupported clients are still told about the server status
+                if let Some(version) = ProtocolVersion::from_number(version) {
+                    self.reader()?.set_protocol_version(version);
+                    self.writer.set_protocol_version(version);
                 }
-                RequestedState::Status => {
-                    trace!("{} request to transition to status state", self);
-                    self.state = ConnectionState::AwaitingStatusRequest;
-                    Ok(())
+                match next_state {
+                    RequestedState::Login => {
+                        trace!("{} request to transition to login state", self);
+                        self.state = ConnectionState::AwaitingLogin;
+                        Ok(())
+                    }
+                    RequestedState::Status => {
+                        trace!("{} request to transition to status state", self);
+                        self.state = ConnectionState::AwaitingStatusRequest;
+                        Ok(())
+                    }
                 }
-            },
+            }
             OpenRequest::HttpGet {} => {
                 trace!("{} responding to HTTP probe", self);
                 self.writer.structure(&OpenResponse::HttpOK {})?;
@@ -189,10 +210,15 @@ impl<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send + 'static> Connec
         }
     }
     async fn execute_status_request(&mut self) -> Result<(), Box<dyn Error>> {
-        match self.reader.read_status().await? {
+        match self.reader()?.read_status().await? {
             StatusRequest::InfoRequest => {
                 trace!("{} request for server status", self);
+                let protocol_version = match self.version {
+                    Some(version) if ProtocolVersion::from_number(version).is_some() => version,
+                    _ => racemus_binary::SERVER_VERSION_NUMBER,
+                };
                 self.writer.structure(&StatusResponse::InfoResponse {
+                    protocol_version,
                     max_players: self.controllers.config().game().max_players(),
                     current_players: 0,
                     description: &self.controllers.config().network().motd(),
@@ -213,12 +239,12 @@ impl<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send + 'static> Connec
     }
 
     async fn execute_login(&mut self) -> Result<(), Box<dyn Error>> {
-        match self.reader.read_login().await? {
+        match self.reader()?.read_login().await? {
             LoginRequest::Start { player_name } => {
                 trace!("{} request to login as: {}", self, player_name);
-                match self.version {
-                    Some(racemus_binary::SERVER_VERSION_NUMBER) => {}
-                    Some(_) => return Err(ConnectionError::UnsupportedVersion.into()),
+                match self.version.map(ProtocolVersion::from_number) {
+                    Some(Some(_)) => {}
+                    Some(None) => return Err(ConnectionError::UnsupportedVersion.into()),
                     None => return Err(ConnectionError::InvalidTransition.into()),
                 };
                 let mut verify = vec![0u8; 16];
@@ -242,7 +268,7 @@ impl<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send + 'static> Connec
     }
 
     async fn execute_encryption_response(&mut self) -> Result<(), Box<dyn Error>> {
-        match self.reader.read_login().await? {
+        match self.reader()?.read_login().await? {
             LoginRequest::EncryptionResponse {
                 encrypted_shared_secret,
                 encrypted_verifier,
@@ -306,7 +332,7 @@ impl<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send + 'static> Connec
                 let aes_in = racemus_binary::create_aes_cfb8(&key, &key)?;
 
                 self.writer.encrypt(aes_out);
-                self.reader.decrypt(aes_in);
+                self.reader()?.decrypt(aes_in);
 
                 if let Some(compression_threshold) =
                     self.controllers.config().network().compression_threshold()
@@ -315,7 +341,7 @@ impl<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send + 'static> Connec
                         compression_threshold,
                     })?;
                     self.writer.flush().await?;
-                    self.reader.allow_compression();
+                    self.reader()?.allow_compression();
                     self.writer
                         .allow_compression(compression_threshold as usize);
                 }
@@ -340,7 +366,7 @@ impl<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send + 'static> Connec
                 self.player_name = Some(player_name.clone());
                 self.state = ConnectionState::RunningGame;
 
-                let (sender, rx) = async_std::sync::channel(10);
+                let (sender, rx) = futures_channel::mpsc::unbounded();
                 self.recv = Some(rx);
 
                 self.controllers
@@ -360,20 +386,78 @@ impl<R: Read + Unpin + Send + 'static, W: Write + Unpin + Send + 'static> Connec
         }
     }
     async fn execute_game(&mut self) -> Result<(), Box<dyn Error>> {
-        let recv = match &mut self.recv {
+        let mut recv = match self.recv.take() {
+            None => return Err(ConnectionError::InvalidTransition.into()),
+            Some(m) => m,
+        };
+        let player_uuid = match &self.player_uuid {
+            None => return Err(ConnectionError::InvalidTransition.into()),
+            Some(m) => m.clone(),
+        };
+        let reader = match self.reader.take() {
             None => return Err(ConnectionError::InvalidTransition.into()),
             Some(m) => m,
         };
 
-        while self.state == ConnectionState::RunningGame {
-            let message = match recv.recv().await {
-                None => return Err(ConnectionError::ServerClosing.into()),
-                Some(m) => m,
-            };
+        // Packets from the client are read concurrently so that they are not
+        // held up by (or holding up) packets being sent to the client. The
+        // reader is not spawned: a detached task would keep reading from the
+        // socket after the writer stops, whereas this future is dropped with
+        // the race.
+        let reading = Self::execute_game_reader(
+            format!("{}", self),
+            reader,
+            player_uuid,
+            self.controllers.clone(),
+        );
+        let reading = async move {
+            match reading.await {
+                Ok(()) => Ok(()),
+                Err(e) => Err(SendError::from(e)),
+            }
+        };
+
+        let writer = &mut self.writer;
+        let writing = async move {
+            loop {
+                let message = match recv.next().await {
+                    None => return Err(ConnectionError::ServerClosing.into()),
+                    Some(m) => m,
+                };
 
-            message.write(&mut self.writer).await?;
+                if let ClientMessage::Disconnect { reason } = message {
+                    return Err(ConnectionError::Kicked(reason).into());
+                }
+
+                message.write(writer).await?;
+            }
+        };
+
+        match writing.race(reading).await {
+            Ok(()) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn execute_game_reader(
+        name: String,
+        mut reader: BinaryReader<R>,
+        player_uuid: Arc<str>,
+        controllers: Controllers,
+    ) -> Result<(), racemus_binary::Error> {
+        loop {
+            match ServerMessage::read(&mut reader).await? {
+                Some(message) => {
+                    controllers
+                        .send_player(player::Message::ConnectionMessage {
+                            player_uuid: player_uuid.clone(),
+                            message,
+                        })
+                        .await
+                }
+                None => trace!("{} ignoring unsupported packet", name),
+            }
         }
-        Ok(())
     }
 }
 
@@ -1,8 +1,14 @@
 use crate::models::*;
-use async_std::io::Write;
-use racemus_binary::{proto::*, BinaryWriter, *};
+use async_std::io::{Read, Write};
+use racemus_binary::{proto::*, BinaryReader, BinaryWriter, *};
 use std::{marker::Unpin, sync::Arc};
 
+// The queue of messages for a connection is unbounded, so that controllers
+// are never held up by a slow or disconnected client. Sending fails once the
+// connection has closed.
+pub type ClientSender = futures_channel::mpsc::UnboundedSender<ClientMessage>;
+pub type ClientReceiver = futures_channel::mpsc::UnboundedReceiver<ClientMessage>;
+
 #[derive(Debug)]
 pub enum ClientMessage {
     JoinGame {
@@ -32,8 +38,24 @@ pub enum ClientMessage {
         teleport_id: i32,
     },
     ChunkData {
+        chunk: Arc<Chunk>,
+    },
+    UpdateLight {
+        position: vek::Vec2<i32>,
+        update: Box<LightUpdate>,
+    },
+    UnloadChunk {
         position: vek::Vec2<i32>,
     },
+    UpdateViewPosition {
+        position: vek::Vec2<i32>,
+    },
+    KeepAlive {
+        keep_alive_id: u64,
+    },
+    Disconnect {
+        reason: Arc<str>,
+    },
 }
 
 impl ClientMessage {
@@ -96,7 +118,181 @@ impl ClientMessage {
                 })?;
                 writer.flush().await
             }
-            Self::ChunkData { position: _ } => Ok(()),
+            Self::ChunkData { chunk } => {
+                let position = chunk.position();
+                let light = chunk.light();
+                // Light has to arrive first, otherwise the client lights the
+                // chunk itself
+                writer.structure(&PlayResponse::UpdateLight {
+                    position: [position.x, position.y],
+                    sky_light: &light.sky_sections(),
+                    block_light: &light.block_sections(),
+                })?;
+                writer.structure(&PlayResponse::ChunkData {
+                    position: [position.x, position.y],
+                    sections: &chunk.to_packet_sections(),
+                    heightmaps: &chunk.heightmaps_nbt(),
+                    biomes: Some(chunk.biomes()),
+                    block_entities: &[],
+                })?;
+                writer.flush().await
+            }
+            Self::UpdateLight { position, update } => {
+                writer.structure(&PlayResponse::UpdateLight {
+                    position: [position.x, position.y],
+                    sky_light: &update.sky_sections(),
+                    block_light: &update.block_sections(),
+                })?;
+                writer.flush().await
+            }
+            Self::UnloadChunk { position } => {
+                writer.structure(&PlayResponse::UnloadChunk {
+                    position: [position.x, position.y],
+                })?;
+                writer.flush().await
+            }
+            Self::UpdateViewPosition { position } => {
+                writer.structure(&PlayResponse::UpdateViewPosition {
+                    position: [position.x, position.y],
+                })?;
+                writer.flush().await
+            }
+            Self::KeepAlive { keep_alive_id } => {
+                writer.structure(&PlayResponse::KeepAlive {
+                    keep_alive_id: *keep_alive_id,
+                })?;
+                writer.flush().await
+            }
+            // The connection closes itself when it sees this message
+            Self::Disconnect { reason: _ } => Ok(()),
         }
     }
 }
+
+#[derive(Debug)]
+pub enum ServerMessage {
+    TeleportConfirm {
+        teleport_id: i32,
+    },
+    ChatMessage {
+        message: Arc<str>,
+    },
+    ClientSettings {
+        locale: Arc<str>,
+        view_distance: u8,
+        main_hand: crate::models::MainHand,
+    },
+    PluginMessage {
+        channel: Arc<str>,
+        data: Arc<[u8]>,
+    },
+    KeepAlive {
+        keep_alive_id: u64,
+    },
+    PlayerPosition {
+        position: vek::Vec3<f64>,
+        on_ground: bool,
+    },
+    PlayerLook {
+        look: vek::Vec2<f32>,
+        on_ground: bool,
+    },
+    PlayerPositionAndLook {
+        position: vek::Vec3<f64>,
+        look: vek::Vec2<f32>,
+        on_ground: bool,
+    },
+    PlayerMovement {
+        on_ground: bool,
+    },
+    HeldItemChange {
+        slot: i16,
+    },
+    Animation {
+        hand: crate::models::Hand,
+    },
+    PlayerDigging {
+        status: crate::models::DiggingStatus,
+        location: vek::Vec3<i32>,
+        face: crate::models::BlockFace,
+    },
+    PlayerBlockPlacement {
+        hand: crate::models::Hand,
+        location: vek::Vec3<i32>,
+        face: crate::models::BlockFace,
+        cursor_position: vek::Vec3<f32>,
+    },
+    UseItem {
+        hand: crate::models::Hand,
+    },
+}
+
+impl ServerMessage {
+    pub async fn read<R: Read + Unpin>(
+        reader: &mut BinaryReader<R>,
+    ) -> Result<Option<Self>, Error> {
+        let message = match reader.read_play().await? {
+            PlayRequest::TeleportConfirm { teleport_id } => Self::TeleportConfirm { teleport_id },
+            PlayRequest::ChatMessage { message } => Self::ChatMessage { message },
+            PlayRequest::ClientSettings {
+                locale,
+                view_distance,
+                main_hand,
+                ..
+            } => Self::ClientSettings {
+                locale,
+                view_distance,
+                main_hand: main_hand.into(),
+            },
+            PlayRequest::Plugin { channel, data } => Self::PluginMessage { channel, data },
+            PlayRequest::KeepAlive { keep_alive_id } => Self::KeepAlive { keep_alive_id },
+            PlayRequest::PlayerPosition {
+                position,
+                on_ground,
+            } => Self::PlayerPosition {
+                position: position.into(),
+                on_ground,
+            },
+            PlayRequest::PlayerLook { look, on_ground } => Self::PlayerLook {
+                look: look.into(),
+                on_ground,
+            },
+            PlayRequest::PlayerPositionAndLook {
+                position,
+                look,
+                on_ground,
+            } => Self::PlayerPositionAndLook {
+                position: position.into(),
+                look: look.into(),
+                on_ground,
+            },
+            PlayRequest::PlayerMovement { on_ground } => Self::PlayerMovement { on_ground },
+            PlayRequest::HeldItemChange { slot } => Self::HeldItemChange { slot },
+            PlayRequest::Animation { hand } => Self::Animation { hand: hand.into() },
+            PlayRequest::PlayerDigging {
+                status,
+                location,
+                face,
+            } => Self::PlayerDigging {
+                status: status.into(),
+                location: location.into(),
+                face: face.into(),
+            },
+            PlayRequest::PlayerBlockPlacement {
+                hand,
+                location,
+                face,
+                cursor_position,
+                ..
+            } => Self::PlayerBlockPlacement {
+                hand: hand.into(),
+                location: location.into(),
+                face: face.into(),
+                cursor_position: cursor_position.into(),
+            },
+            PlayRequest::UseItem { hand } => Self::UseItem { hand: hand.into() },
+            _ => return Ok(None),
+        };
+        Ok(Some(message))
+    }
+}
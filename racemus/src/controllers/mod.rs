@@ -1,5 +1,6 @@
 pub mod player;
 pub mod server;
+pub mod world;
 
 use async_std::sync::{channel, Sender};
 
@@ -8,19 +9,23 @@ pub struct Controllers {
     config: crate::config::Config,
     server: Sender<server::Message>,
     player: Sender<player::Message>,
+    world: Sender<world::Message>,
 }
 
 impl Controllers {
     pub fn new(config: &crate::config::Config, cap: usize) -> Controllers {
         let (server_tx, server_rx) = channel(cap);
         let (player_tx, player_rx) = channel(cap);
+        let (world_tx, world_rx) = channel(cap);
         let controllers = Controllers {
             config: config.clone(),
             server: server_tx,
             player: player_tx,
+            world: world_tx,
         };
         player::Controller::start(controllers.clone(), player_rx);
         server::Controller::start(controllers.clone(), server_rx);
+        world::Controller::start(controllers.clone(), world_rx);
         controllers
     }
 
@@ -35,4 +40,8 @@ impl Controllers {
     pub async fn send_player(&self, message: player::Message) {
         self.player.send(message).await
     }
+
+    pub async fn send_world(&self, message: world::Message) {
+        self.world.send(message).await
+    }
 }
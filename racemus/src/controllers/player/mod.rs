@@ -1,21 +1,34 @@
 use crate::models::*;
-use crate::{connection::ClientMessage, controllers::server};
-use async_std::{
-    sync::{Receiver, Sender},
-    task,
+use crate::{
+    connection::{ClientMessage, ClientSender, ServerMessage},
+    controllers::{server, world},
 };
+use async_std::{prelude::*, stream, sync::Receiver, task};
+use log::{info, trace};
 use racemus_tools::task::wait;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub enum Message {
     ConnectionOpened {
         player_uuid: Arc<str>,
         player_name: Arc<str>,
-        sender: Sender<crate::connection::ClientMessage>,
+        sender: ClientSender,
     },
     ConnectionClosed {
         player_uuid: Arc<str>,
     },
+    ConnectionMessage {
+        player_uuid: Arc<str>,
+        message: ServerMessage,
+    },
+    KeepAliveTick,
 }
 
 pub struct Controller {
@@ -26,6 +39,7 @@ pub struct Controller {
 
 impl Controller {
     pub fn start(controllers: super::Controllers, receiver: Receiver<Message>) {
+        let ticker = controllers.clone();
         let mut controller = Controller {
             controllers,
             receiver,
@@ -34,6 +48,12 @@ impl Controller {
         task::spawn(async move {
             controller.execute().await;
         });
+        task::spawn(async move {
+            let mut interval = stream::interval(KEEP_ALIVE_INTERVAL);
+            while interval.next().await.is_some() {
+                ticker.send_player(Message::KeepAliveTick).await;
+            }
+        });
     }
 
     async fn execute(&mut self) {
@@ -52,13 +72,69 @@ impl Controller {
                         Player::new(player_uuid, player_name, sender, self.controllers.config());
                     self.load_player(player).await;
                 }
-                Some(Message::ConnectionClosed { player_uuid: _ }) => {}
+                Some(Message::ConnectionClosed { player_uuid }) => {
+                    if let Some(player) = self.players.remove(&player_uuid) {
+                        self.remove_viewer(&player).await;
+                    }
+                }
+                Some(Message::ConnectionMessage {
+                    player_uuid,
+                    message,
+                }) => {
+                    if let Some(player) = self.players.get_mut(&player_uuid) {
+                        player.handle_message(message);
+                        self.update_view(&player_uuid).await;
+                    }
+                }
+                Some(Message::KeepAliveTick) => self.keep_alive().await,
+            }
+        }
+    }
+
+    async fn keep_alive(&mut self) {
+        let now = Instant::now();
+        let mut timed_out = Vec::new();
+
+        for player in self.players.values_mut() {
+            match player.keep_alive {
+                Some((_, sent)) if now.duration_since(sent) > KEEP_ALIVE_TIMEOUT => {
+                    timed_out.push(player.uuid.clone());
+                }
+                Some(_) => {}
+                None => {
+                    // Vanilla uses the current time as the ID
+                    let keep_alive_id = match SystemTime::now().duration_since(UNIX_EPOCH) {
+                        Ok(r) => r.as_millis() as u64,
+                        Err(_) => 0,
+                    };
+                    player.keep_alive = Some((keep_alive_id, now));
+                    player.send(ClientMessage::KeepAlive { keep_alive_id });
+                }
+            }
+        }
+
+        for player_uuid in timed_out {
+            if let Some(player) = self.players.remove(&player_uuid) {
+                info!("({}) keep alive timed out", player.uuid);
+                self.remove_viewer(&player).await;
+                player.send(ClientMessage::Disconnect {
+                    reason: "Timed out".into(),
+                });
             }
         }
     }
 
     async fn disconnect(&self) {}
 
+    // The chunks are not unloaded on the client, which is going away
+    async fn remove_viewer(&self, player: &Player) {
+        self.controllers
+            .send_world(world::Message::RemoveViewer {
+                viewer: player.uuid.clone(),
+            })
+            .await;
+    }
+
     async fn load_player(&mut self, player: Player) {
         let eid = wait(|complete| {
             self.controllers
@@ -71,57 +147,107 @@ impl Controller {
             player.entity_id = eid
         }
 
-        player
-            .sender
-            .send(ClientMessage::JoinGame {
-                entity_id: player.entity_id,
-                game_mode: player.game_mode,
-                dimension: player.dimension,
-                hashed_seed: self.controllers.config().game().seed(),
-                level_type: "default".into(),
-                view_distance: self.controllers.config().game().view_distance(),
-                reduce_debug: self.controllers.config().game().reduce_debug_info(),
-                enable_respawn_screen: self.controllers.config().game().enable_respawn_screen(),
-            })
-            .await;
+        player.send(ClientMessage::JoinGame {
+            entity_id: player.entity_id,
+            game_mode: player.game_mode,
+            dimension: player.dimension,
+            hashed_seed: self.controllers.config().game().hashed_seed(),
+            level_type: "default".into(),
+            view_distance: self.controllers.config().game().view_distance(),
+            reduce_debug: self.controllers.config().game().reduce_debug_info(),
+            enable_respawn_screen: self.controllers.config().game().enable_respawn_screen(),
+        });
 
-        player
-            .sender
-            .send(ClientMessage::PluginBrand { brand: "racemus" })
-            .await;
+        player.send(ClientMessage::PluginBrand { brand: "racemus" });
 
-        player
-            .sender
-            .send(ClientMessage::ServerDifficulty {
-                difficulty: self.controllers.config().game().difficulty(),
-                difficulty_locked: true,
-            })
-            .await;
+        player.send(ClientMessage::ServerDifficulty {
+            difficulty: self.controllers.config().game().difficulty(),
+            difficulty_locked: true,
+        });
 
-        player
-            .sender
-            .send(ClientMessage::PlayerPositionAndLook {
-                position: player.position,
-                look: player.look,
-                flags: 0,
-                teleport_id: 0,
-            })
-            .await;
+        player.send(ClientMessage::PlayerPositionAndLook {
+            position: player.position,
+            look: player.look,
+            flags: 0,
+            teleport_id: 0,
+        });
 
-        player
-            .sender
-            .send(ClientMessage::ChunkData {
-                position: vek::Vec2::zero(),
-            })
-            .await;
+        let player_uuid = player.uuid.clone();
+        self.players.insert(player_uuid.clone(), player);
+        self.update_view(&player_uuid).await;
+    }
 
-        self.players.insert(player.uuid.clone(), player);
+    // Requests the chunks that have come into view, nearest first, and
+    // releases the chunks that are no longer in view.
+    async fn update_view(&mut self, player_uuid: &Arc<str>) {
+        let server_view_distance = self.controllers.config().game().view_distance();
+        let player = match self.players.get_mut(player_uuid) {
+            Some(player) => player,
+            None => return,
+        };
+
+        let center = player.chunk_position();
+        let radius = i32::from(player.view_distance.min(server_view_distance));
+        let moved = match player.view {
+            Some(view) if view == (center, radius) => return,
+            Some((previous, _)) => previous != center,
+            None => true,
+        };
+        player.view = Some((center, radius));
+
+        let in_view = |position: vek::Vec2<i32>| {
+            (position.x - center.x).abs() <= radius && (position.y - center.y).abs() <= radius
+        };
+        let unload: Vec<_> = player
+            .loaded_chunks
+            .iter()
+            .copied()
+            .filter(|position| !in_view(*position))
+            .collect();
+        for position in unload.iter() {
+            player.loaded_chunks.remove(position);
+        }
+
+        let mut load = Vec::new();
+        for z in (center.y - radius)..=(center.y + radius) {
+            for x in (center.x - radius)..=(center.x + radius) {
+                let position = vek::Vec2::new(x, z);
+                if player.loaded_chunks.insert(position) {
+                    load.push(position);
+                }
+            }
+        }
+        load.sort_by_key(|position| (*position - center).magnitude_squared());
+
+        let sender = player.sender.clone();
+        if moved {
+            player.send(ClientMessage::UpdateViewPosition { position: center });
+        }
+        // The world controller sends the chunks to the client as they load,
+        // and unloads them when they are released
+        for position in unload {
+            self.controllers
+                .send_world(world::Message::ReleaseChunk {
+                    position,
+                    viewer: player_uuid.clone(),
+                })
+                .await;
+        }
+        for position in load {
+            self.controllers
+                .send_world(world::Message::AcquireChunk {
+                    position,
+                    viewer: player_uuid.clone(),
+                    sender: sender.clone(),
+                })
+                .await;
+        }
     }
 }
 
 struct Player {
     uuid: Arc<str>,
-    sender: Sender<crate::connection::ClientMessage>,
+    sender: ClientSender,
     entity_id: EntityId,
 
     game_mode: GameMode,
@@ -129,13 +255,23 @@ struct Player {
 
     position: vek::Vec3<f64>,
     look: vek::Vec2<f32>,
+
+    // The view distance requested by the client
+    view_distance: u8,
+    // The chunk that the player was in and the view distance when chunks
+    // were last sent
+    view: Option<(vek::Vec2<i32>, i32)>,
+    loaded_chunks: HashSet<vek::Vec2<i32>>,
+
+    keep_alive: Option<(u64, Instant)>,
+    latency: Duration,
 }
 
 impl Player {
     pub fn new(
         uuid: Arc<str>,
         _name: Arc<str>,
-        sender: Sender<crate::connection::ClientMessage>,
+        sender: ClientSender,
         config: &crate::config::Config,
     ) -> Self {
         Self {
@@ -145,8 +281,53 @@ impl Player {
             entity_id: EntityId::default(),
             game_mode: config.game().game_mode(),
             dimension: 0,
-            position: vek::Vec3::new(0.0, 255.0, 0.0),
+            position: config.game().spawn_position().map(f64::from),
             look: vek::Vec2::zero(),
+            view_distance: config.game().view_distance(),
+            view: None,
+            loaded_chunks: HashSet::new(),
+            keep_alive: None,
+            latency: Duration::default(),
+        }
+    }
+
+    // Errors are ignored, the connection sends ConnectionClosed once it has
+    // gone
+    fn send(&self, message: ClientMessage) {
+        let _ = self.sender.unbounded_send(message);
+    }
+
+    fn chunk_position(&self) -> vek::Vec2<i32> {
+        vek::Vec2::new(
+            (self.position.x.floor() as i32).div_euclid(CHUNK_WIDTH as i32),
+            (self.position.z.floor() as i32).div_euclid(CHUNK_WIDTH as i32),
+        )
+    }
+
+    fn handle_message(&mut self, message: ServerMessage) {
+        match message {
+            ServerMessage::ClientSettings { view_distance, .. } => {
+                self.view_distance = view_distance;
+            }
+            ServerMessage::PlayerPosition { position, .. } => {
+                self.position = position;
+            }
+            ServerMessage::PlayerLook { look, .. } => {
+                self.look = look;
+            }
+            ServerMessage::PlayerPositionAndLook { position, look, .. } => {
+                self.position = position;
+                self.look = look;
+            }
+            ServerMessage::KeepAlive { keep_alive_id } => match self.keep_alive {
+                Some((expected, sent)) if expected == keep_alive_id => {
+                    self.keep_alive = None;
+                    self.latency = sent.elapsed();
+                    trace!("({}) latency: {:?}", self.uuid, self.latency);
+                }
+                _ => trace!("({}) unexpected keep alive: {}", self.uuid, keep_alive_id),
+            },
+            message => trace!("({}) unhandled message: {:?}", self.uuid, message),
         }
     }
 }
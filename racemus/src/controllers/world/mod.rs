@@ -0,0 +1,360 @@
+use crate::models::*;
+use crate::{
+    anvil::{self, AnvilWorld},
+    connection::{ClientMessage, ClientSender},
+    generator::{self, WorldGenerator},
+};
+use async_std::{sync::Receiver, task};
+use log::{error, trace};
+use racemus_binary::{nbt, Error};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+pub enum Message {
+    // Loads the chunk if needed and sends it to the viewer, the chunk stays
+    // loaded until every viewer has released it
+    AcquireChunk {
+        position: vek::Vec2<i32>,
+        viewer: Arc<str>,
+        sender: ClientSender,
+    },
+    // Unloads the chunk on the client if it was sent
+    ReleaseChunk {
+        position: vek::Vec2<i32>,
+        viewer: Arc<str>,
+    },
+    // Forgets the viewer without unloading its chunks, for clients that have
+    // gone
+    RemoveViewer {
+        viewer: Arc<str>,
+    },
+    ChunkLoaded {
+        chunk: Box<Chunk>,
+        // Whether the chunk already has its light
+        lit: bool,
+    },
+}
+
+struct ChunkEntry {
+    viewers: HashMap<Arc<str>, ClientSender>,
+    last_used: u64,
+}
+
+impl ChunkEntry {
+    // Sending never waits for the viewer, viewers that have disconnected are
+    // removed instead
+    fn send(&mut self, message: impl Fn() -> ClientMessage) {
+        self.viewers.retain(|viewer, sender| {
+            let result = sender.unbounded_send(message()).is_ok();
+            if !result {
+                trace!("viewer {} has disconnected", viewer);
+            }
+            result
+        });
+    }
+}
+
+pub struct Controller {
+    controllers: super::Controllers,
+    receiver: Receiver<Message>,
+    generator: Arc<dyn WorldGenerator>,
+    world: Option<Arc<AnvilWorld>>,
+    light_engine: LightEngine<RegistryLightProperties>,
+    chunks: HashMap<vek::Vec2<i32>, Arc<Chunk>>,
+    // The sum of Chunk::memory_usage for every loaded chunk
+    memory_usage: usize,
+    // Includes chunks that are still being loaded
+    entries: HashMap<vek::Vec2<i32>, ChunkEntry>,
+    loading: HashSet<vek::Vec2<i32>>,
+    clock: u64,
+}
+
+impl Controller {
+    pub fn start(controllers: super::Controllers, receiver: Receiver<Message>) {
+        let generator = generator::create(controllers.config());
+        let world = controllers
+            .config()
+            .world()
+            .path()
+            .map(|path| Arc::new(AnvilWorld::new(path)));
+        let mut controller = Controller {
+            controllers,
+            receiver,
+            generator,
+            world,
+            light_engine: LightEngine::new(RegistryLightProperties),
+            chunks: HashMap::new(),
+            memory_usage: 0,
+            entries: HashMap::new(),
+            loading: HashSet::new(),
+            clock: 0,
+        };
+        task::spawn(async move {
+            controller.execute().await;
+        });
+    }
+
+    async fn execute(&mut self) {
+        loop {
+            match self.receiver.recv().await {
+                None => {
+                    self.close().await;
+                    return;
+                }
+                Some(Message::AcquireChunk {
+                    position,
+                    viewer,
+                    sender,
+                }) => self.acquire_chunk(position, viewer, sender),
+                Some(Message::ReleaseChunk { position, viewer }) => {
+                    self.release_chunk(position, viewer)
+                }
+                Some(Message::RemoveViewer { viewer }) => self.remove_viewer(viewer),
+                Some(Message::ChunkLoaded { chunk, lit }) => self.chunk_loaded(*chunk, lit),
+            }
+        }
+    }
+
+    async fn close(&self) {}
+
+    fn touch(&mut self, position: vek::Vec2<i32>) -> &mut ChunkEntry {
+        self.clock += 1;
+        let clock = self.clock;
+        let entry = self.entries.entry(position).or_insert(ChunkEntry {
+            viewers: HashMap::new(),
+            last_used: clock,
+        });
+        entry.last_used = clock;
+        entry
+    }
+
+    fn acquire_chunk(&mut self, position: vek::Vec2<i32>, viewer: Arc<str>, sender: ClientSender) {
+        if let Some(chunk) = self.chunks.get(&position) {
+            let message = ClientMessage::ChunkData {
+                chunk: chunk.clone(),
+            };
+            if sender.unbounded_send(message).is_ok() {
+                self.touch(position).viewers.insert(viewer, sender);
+            }
+            return;
+        }
+        self.touch(position).viewers.insert(viewer, sender);
+
+        // The viewer is sent the chunk once it has loaded
+        if !self.loading.insert(position) {
+            return;
+        }
+
+        let world = self.world.clone();
+        let generator = self.generator.clone();
+        let controllers = self.controllers.clone();
+        task::spawn(async move {
+            let (chunk, lit) = load_chunk(world, generator, position).await;
+            controllers
+                .send_world(Message::ChunkLoaded {
+                    chunk: Box::new(chunk),
+                    lit,
+                })
+                .await;
+        });
+    }
+
+    fn release_chunk(&mut self, position: vek::Vec2<i32>, viewer: Arc<str>) {
+        let sender = match self.entries.get_mut(&position) {
+            Some(entry) => entry.viewers.remove(&viewer),
+            None => None,
+        };
+        let sender = match sender {
+            Some(sender) => sender,
+            None => {
+                trace!("unexpected release of chunk {:?}", position);
+                return;
+            }
+        };
+
+        // Chunks that are still loading were never sent
+        if self.chunks.contains_key(&position) {
+            let _ = sender.unbounded_send(ClientMessage::UnloadChunk { position });
+        }
+        self.touch(position);
+        self.evict();
+    }
+
+    fn remove_viewer(&mut self, viewer: Arc<str>) {
+        for entry in self.entries.values_mut() {
+            entry.viewers.remove(&viewer);
+        }
+        self.evict();
+    }
+
+    fn chunk_loaded(&mut self, chunk: Chunk, lit: bool) {
+        let position = chunk.position();
+        self.loading.remove(&position);
+        self.memory_usage += chunk.memory_usage();
+        self.chunks.insert(position, Arc::new(chunk));
+
+        // Lighting the chunk also changes the light in its neighbours. Saved
+        // light is kept, because it includes light from sources that are not
+        // known here.
+        let before = self.neighbourhood_memory_usage(position);
+        if lit {
+            self.light_engine.spread_chunk(&mut self.chunks, position);
+        } else {
+            self.light_engine.light_chunk(&mut self.chunks, position);
+        }
+        self.memory_usage = self.memory_usage - before + self.neighbourhood_memory_usage(position);
+        self.send_light_updates(position);
+
+        if let Some(entry) = self.entries.get_mut(&position) {
+            let chunk = &self.chunks[&position];
+            entry.send(|| ClientMessage::ChunkData {
+                chunk: chunk.clone(),
+            });
+        }
+        self.evict();
+    }
+
+    // Sends the light that has changed around the chunk to the viewers of
+    // the neighbouring chunks, which have already been sent.
+    fn send_light_updates(&mut self, position: vek::Vec2<i32>) {
+        for z in -1..=1 {
+            for x in -1..=1 {
+                let neighbour = position + vek::Vec2::new(x, z);
+                let update = match self.chunks.get_mut(&neighbour) {
+                    Some(chunk) if chunk.light().is_dirty() => {
+                        Arc::make_mut(chunk).light_mut().take_update()
+                    }
+                    _ => None,
+                };
+                let update = match update {
+                    Some(update) => update,
+                    None => continue,
+                };
+                // The chunk itself is sent along with all of its light
+                if neighbour == position {
+                    continue;
+                }
+                let entry = match self.entries.get_mut(&neighbour) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                entry.send(|| ClientMessage::UpdateLight {
+                    position: neighbour,
+                    update: Box::new(update.clone()),
+                });
+            }
+        }
+    }
+
+    fn neighbourhood_memory_usage(&self, position: vek::Vec2<i32>) -> usize {
+        let mut result = 0;
+        for z in -1..=1 {
+            for x in -1..=1 {
+                if let Some(chunk) = self.chunks.get(&(position + vek::Vec2::new(x, z))) {
+                    result += chunk.memory_usage();
+                }
+            }
+        }
+        result
+    }
+
+    // Unloads the least recently used chunks that are not referenced until
+    // the memory used by chunks is within the configured limit.
+    fn evict(&mut self) {
+        let limit = self.controllers.config().world().chunk_cache_size();
+        if self.memory_usage <= limit {
+            return;
+        }
+
+        let mut unused: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(position, entry)| {
+                entry.viewers.is_empty() && self.chunks.contains_key(*position)
+            })
+            .map(|(position, entry)| (entry.last_used, *position))
+            .collect();
+        unused.sort_unstable_by_key(|(last_used, _)| *last_used);
+
+        for (_, position) in unused {
+            if self.memory_usage <= limit {
+                break;
+            }
+            if let Some(chunk) = self.chunks.remove(&position) {
+                self.memory_usage -= chunk.memory_usage();
+            }
+            self.entries.remove(&position);
+            trace!("unloaded chunk {:?}", position);
+        }
+    }
+}
+
+// Imports the chunk from the world directory if it was saved there, and
+// generates it otherwise. Conversion and generation are CPU bound, so they
+// are kept off of the async executor. Also returns whether the chunk was
+// saved with its light.
+async fn load_chunk(
+    world: Option<Arc<AnvilWorld>>,
+    generator: Arc<dyn WorldGenerator>,
+    position: vek::Vec2<i32>,
+) -> (Chunk, bool) {
+    if let Some(world) = world {
+        match world.read_chunk(position).await {
+            Ok(Some(data)) => {
+                let result = task::spawn_blocking(move || {
+                    let (_, value) = nbt::view_nbt(&data)?;
+                    let chunk = anvil::import_chunk(&value)?;
+                    Ok::<_, Error>(chunk.map(|r| (r, anvil::is_lit(&value))))
+                });
+                match result.await {
+                    Ok(Some((chunk, lit))) if chunk.position() == position => return (chunk, lit),
+                    Ok(Some((chunk, _))) => error!(
+                        "chunk {:?} was saved with the position {:?}",
+                        position,
+                        chunk.position()
+                    ),
+                    Ok(None) => {}
+                    Err(e) => error!("failed to import chunk {:?}: {}", position, e),
+                }
+            }
+            Ok(None) => {}
+            Err(e) => error!("failed to read chunk {:?}: {}", position, e),
+        }
+    }
+    let chunk = task::spawn_blocking(move || generator.generate(position)).await;
+    (chunk, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn chunk_entry_send_disconnected() {
+        let (sender, mut receiver) = futures_channel::mpsc::unbounded();
+        let (closed_sender, closed_receiver) = futures_channel::mpsc::unbounded();
+        let mut entry = ChunkEntry {
+            viewers: HashMap::new(),
+            last_used: 0,
+        };
+        entry.viewers.insert("a".into(), sender);
+        entry.viewers.insert("b".into(), closed_sender);
+
+        // The viewer has gone with messages still queued
+        let position = vek::Vec2::new(1, 2);
+        entry.send(|| ClientMessage::UnloadChunk { position });
+        drop(closed_receiver);
+        entry.send(|| ClientMessage::UnloadChunk { position });
+
+        assert_eq!(entry.viewers.len(), 1);
+        assert!(entry.viewers.contains_key("a"));
+        for _ in 0..2 {
+            match receiver.try_next() {
+                Ok(Some(ClientMessage::UnloadChunk { position: p })) => assert_eq!(p, position),
+                m => panic!("unexpected message {:?}", m),
+            }
+        }
+    }
+}
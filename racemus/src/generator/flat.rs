@@ -0,0 +1,92 @@
+use crate::{
+    generator::WorldGenerator,
+    models::{Chunk, CHUNK_HEIGHT, CHUNK_WIDTH},
+};
+use racemus_mc::blocks::BlockState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlatLayer {
+    pub block: BlockState,
+    pub height: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct FlatGenerator {
+    // One block state for each y, up to the top layer
+    column: Vec<BlockState>,
+    biome: i32,
+}
+
+impl FlatGenerator {
+    pub fn new(layers: &[FlatLayer], biome: i32) -> Self {
+        let column = layers
+            .iter()
+            .flat_map(|layer| std::iter::repeat(layer.block).take(layer.height))
+            .take(CHUNK_HEIGHT)
+            .collect();
+        Self { column, biome }
+    }
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn generate(&self, position: vek::Vec2<i32>) -> Chunk {
+        let mut chunk = Chunk::new(position);
+        for (y, block) in self.column.iter().enumerate() {
+            for z in 0..CHUNK_WIDTH {
+                for x in 0..CHUNK_WIDTH {
+                    chunk.set(x, y, z, block.id());
+                }
+            }
+        }
+        for y in (0..CHUNK_HEIGHT).step_by(4) {
+            for z in (0..CHUNK_WIDTH).step_by(4) {
+                for x in (0..CHUNK_WIDTH).step_by(4) {
+                    chunk.set_biome(x, y, z, self.biome);
+                }
+            }
+        }
+        chunk.take_dirty();
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HeightmapKind;
+    use racemus_mc::blocks::BlockKind;
+
+    #[test]
+    pub fn flat_generator_layers() {
+        let generator = FlatGenerator::new(
+            &[
+                FlatLayer {
+                    block: BlockKind::Bedrock.default_state(),
+                    height: 1,
+                },
+                FlatLayer {
+                    block: BlockKind::Dirt.default_state(),
+                    height: 2,
+                },
+                FlatLayer {
+                    block: BlockKind::GrassBlock.default_state(),
+                    height: 1,
+                },
+            ],
+            2,
+        );
+
+        let chunk = generator.generate(vek::Vec2::new(3, -4));
+        assert_eq!(chunk.position(), vek::Vec2::new(3, -4));
+        assert_eq!(chunk.get(5, 0, 6), BlockKind::Bedrock.default_state().id());
+        assert_eq!(chunk.get(5, 2, 6), BlockKind::Dirt.default_state().id());
+        assert_eq!(
+            chunk.get(5, 3, 6),
+            BlockKind::GrassBlock.default_state().id()
+        );
+        assert_eq!(chunk.get(5, 4, 6), 0);
+        assert_eq!(chunk.height(HeightmapKind::WorldSurface, 15, 15), 4);
+        assert_eq!(chunk.biome(15, 255, 15), 2);
+        assert!(!chunk.is_dirty());
+    }
+}
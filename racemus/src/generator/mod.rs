@@ -0,0 +1,28 @@
+mod flat;
+mod noise;
+mod overworld;
+
+pub use flat::*;
+pub use noise::*;
+pub use overworld::*;
+
+use crate::{config::Config, models::Chunk};
+use std::sync::Arc;
+
+pub trait WorldGenerator: Send + Sync {
+    fn generate(&self, position: vek::Vec2<i32>) -> Chunk;
+}
+
+pub fn create(config: &Config) -> Arc<dyn WorldGenerator> {
+    let world = config.world();
+    match world.generator() {
+        GeneratorKind::Flat => Arc::new(FlatGenerator::new(world.flat_layers(), world.biome())),
+        GeneratorKind::Overworld => Arc::new(OverworldGenerator::new(config.game().seed())),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorKind {
+    Flat,
+    Overworld,
+}
@@ -0,0 +1,155 @@
+// A small, self-contained PRNG so that generated terrain never changes
+// because a dependency changed its algorithm.
+#[derive(Debug, Clone)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+const GRADIENTS: [(f64, f64); 8] = [
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+    (
+        std::f64::consts::FRAC_1_SQRT_2,
+        std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        -std::f64::consts::FRAC_1_SQRT_2,
+        std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        std::f64::consts::FRAC_1_SQRT_2,
+        -std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        -std::f64::consts::FRAC_1_SQRT_2,
+        -std::f64::consts::FRAC_1_SQRT_2,
+    ),
+];
+
+// Two dimensional gradient noise.
+#[derive(Clone)]
+pub struct Perlin {
+    // Doubled so that lookups never need to wrap
+    permutation: Box<[u8]>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SplitMix64::new(seed);
+        let mut permutation: Vec<u8> = (0..=255).collect();
+        for i in (1..permutation.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            permutation.swap(i, j);
+        }
+        let copy = permutation.clone();
+        permutation.extend(copy);
+        Self {
+            permutation: permutation.into_boxed_slice(),
+        }
+    }
+
+    // Returns a value in the range [-1, 1]
+    pub fn get(&self, x: f64, y: f64) -> f64 {
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let xf = x - x0;
+        let yf = y - y0;
+        let xi = (x0 as i64 & 255) as usize;
+        let yi = (y0 as i64 & 255) as usize;
+
+        let p = &self.permutation;
+        let a = p[xi] as usize + yi;
+        let b = p[xi + 1] as usize + yi;
+
+        let u = fade(xf);
+        let v = fade(yf);
+        let bottom = lerp(u, grad(p[a], xf, yf), grad(p[b], xf - 1.0, yf));
+        let top = lerp(
+            u,
+            grad(p[a + 1], xf, yf - 1.0),
+            grad(p[b + 1], xf - 1.0, yf - 1.0),
+        );
+        // The largest possible value of 2D gradient noise is sqrt(2) / 2
+        (lerp(v, bottom, top) * std::f64::consts::SQRT_2)
+            .max(-1.0)
+            .min(1.0)
+    }
+
+    // Sums several octaves of noise, each at double the frequency and half
+    // the amplitude of the last. Returns a value in the range [-1, 1]
+    pub fn fractal(&self, x: f64, y: f64, octaves: u32) -> f64 {
+        let mut result = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut total = 0.0;
+        for _ in 0..octaves {
+            result += self.get(x * frequency, y * frequency) * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        result / total
+    }
+}
+
+#[inline]
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+#[inline]
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+#[inline]
+fn grad(hash: u8, x: f64, y: f64) -> f64 {
+    let (gx, gy) = GRADIENTS[(hash & 7) as usize];
+    gx * x + gy * y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn split_mix_64() {
+        let mut rng = SplitMix64::new(1234567);
+        assert_eq!(rng.next_u64(), 6457827717110365317);
+        assert_eq!(rng.next_u64(), 3203168211198807973);
+    }
+
+    #[test]
+    pub fn perlin_range() {
+        let perlin = Perlin::new(42);
+        for i in 0..10_000 {
+            let value = perlin.get(i as f64 * 0.173, i as f64 * -0.311);
+            assert!((-1.0..=1.0).contains(&value));
+        }
+        // Noise is always zero on the lattice
+        assert_eq!(perlin.get(3.0, -7.0), 0.0);
+    }
+
+    #[test]
+    pub fn perlin_seeded() {
+        let a = Perlin::new(1);
+        let b = Perlin::new(1);
+        let c = Perlin::new(2);
+        assert_eq!(a.fractal(10.5, 20.25, 4), b.fractal(10.5, 20.25, 4));
+        assert_ne!(a.fractal(10.5, 20.25, 4), c.fractal(10.5, 20.25, 4));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{
+    generator::{Perlin, SplitMix64, WorldGenerator},
+    models::{Chunk, CHUNK_HEIGHT, CHUNK_WIDTH},
+};
+use racemus_mc::blocks::{BlockKind, BlockState};
+
+pub const SEA_LEVEL: usize = 63;
+
+const HEIGHT_SCALE: f64 = 1.0 / 256.0;
+const HEIGHT_OCTAVES: u32 = 5;
+const HEIGHT_AMPLITUDE: f64 = 48.0;
+const TEMPERATURE_SCALE: f64 = 1.0 / 512.0;
+const TEMPERATURE_OCTAVES: u32 = 2;
+const SOIL_DEPTH: usize = 3;
+
+const OCEAN: i32 = 0;
+const PLAINS: i32 = 1;
+const DESERT: i32 = 2;
+
+#[derive(Clone)]
+pub struct OverworldGenerator {
+    height: Perlin,
+    temperature: Perlin,
+    bedrock: BlockState,
+    stone: BlockState,
+    dirt: BlockState,
+    grass: BlockState,
+    sand: BlockState,
+    water: BlockState,
+}
+
+impl OverworldGenerator {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SplitMix64::new(seed);
+        Self {
+            height: Perlin::new(rng.next_u64()),
+            temperature: Perlin::new(rng.next_u64()),
+            bedrock: BlockKind::Bedrock.default_state(),
+            stone: BlockKind::Stone.default_state(),
+            dirt: BlockKind::Dirt.default_state(),
+            grass: BlockKind::GrassBlock.default_state(),
+            sand: BlockKind::Sand.default_state(),
+            water: BlockKind::Water.default_state(),
+        }
+    }
+
+    // The y of the highest solid block in the column
+    fn height(&self, x: i32, z: i32) -> usize {
+        let noise = self.height.fractal(
+            f64::from(x) * HEIGHT_SCALE,
+            f64::from(z) * HEIGHT_SCALE,
+            HEIGHT_OCTAVES,
+        );
+        let height = SEA_LEVEL as f64 + noise * HEIGHT_AMPLITUDE;
+        (height.round() as usize).max(1).min(CHUNK_HEIGHT - 1)
+    }
+
+    fn biome(&self, x: i32, z: i32, height: usize) -> i32 {
+        if height < SEA_LEVEL {
+            return OCEAN;
+        }
+        let temperature = self.temperature.fractal(
+            f64::from(x) * TEMPERATURE_SCALE,
+            f64::from(z) * TEMPERATURE_SCALE,
+            TEMPERATURE_OCTAVES,
+        );
+        if temperature > 0.25 {
+            DESERT
+        } else {
+            PLAINS
+        }
+    }
+}
+
+impl WorldGenerator for OverworldGenerator {
+    fn generate(&self, position: vek::Vec2<i32>) -> Chunk {
+        let mut chunk = Chunk::new(position);
+        for z in 0..CHUNK_WIDTH {
+            for x in 0..CHUNK_WIDTH {
+                let world_x = position.x * CHUNK_WIDTH as i32 + x as i32;
+                let world_z = position.y * CHUNK_WIDTH as i32 + z as i32;
+                let height = self.height(world_x, world_z);
+                let biome = self.biome(world_x, world_z, height);
+
+                let (top, soil) = match biome {
+                    PLAINS if height > SEA_LEVEL => (self.grass, self.dirt),
+                    PLAINS => (self.dirt, self.dirt),
+                    _ => (self.sand, self.sand),
+                };
+
+                chunk.set(x, 0, z, self.bedrock.id());
+                for y in 1..=height {
+                    let block = if y == height {
+                        top
+                    } else if y + SOIL_DEPTH >= height {
+                        soil
+                    } else {
+                        self.stone
+                    };
+                    chunk.set(x, y, z, block.id());
+                }
+                for y in (height + 1)..=SEA_LEVEL {
+                    chunk.set(x, y, z, self.water.id());
+                }
+
+                // Biomes are stored in 4x4x4 cells, the column decides the
+                // biome for its whole cell
+                if x % 4 == 0 && z % 4 == 0 {
+                    for y in (0..CHUNK_HEIGHT).step_by(4) {
+                        chunk.set_biome(x, y, z, biome);
+                    }
+                }
+            }
+        }
+        chunk.take_dirty();
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HeightmapKind;
+
+    // FNV-1a over every block and biome, so that any change in the output
+    // is detected
+    fn checksum(chunk: &Chunk) -> u64 {
+        let mut result = 0xcbf2_9ce4_8422_2325u64;
+        let mut add = |value: u32| {
+            for byte in value.to_le_bytes().iter() {
+                result ^= u64::from(*byte);
+                result = result.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        };
+        for y in 0..CHUNK_HEIGHT {
+            for z in 0..CHUNK_WIDTH {
+                for x in 0..CHUNK_WIDTH {
+                    add(chunk.get(x, y, z));
+                }
+            }
+        }
+        for biome in chunk.biomes().iter() {
+            add(*biome as u32);
+        }
+        result
+    }
+
+    #[test]
+    pub fn overworld_generator_deterministic() {
+        let a = OverworldGenerator::new(1234);
+        let b = OverworldGenerator::new(1234);
+        for position in [vek::Vec2::new(0, 0), vek::Vec2::new(-7, 12)].iter() {
+            assert_eq!(a.generate(*position), b.generate(*position));
+        }
+
+        let c = OverworldGenerator::new(4321);
+        assert_ne!(
+            checksum(&a.generate(vek::Vec2::zero())),
+            checksum(&c.generate(vek::Vec2::zero()))
+        );
+    }
+
+    #[test]
+    pub fn overworld_generator_stable() {
+        // These only change if the generator itself changes, which would
+        // create seams between old and new chunks in existing worlds.
+        let generator = OverworldGenerator::new(1234);
+        assert_eq!(
+            checksum(&generator.generate(vek::Vec2::new(0, 0))),
+            14888993642485392821
+        );
+        assert_eq!(
+            checksum(&generator.generate(vek::Vec2::new(-7, 12))),
+            13169914637121650421
+        );
+    }
+
+    #[test]
+    pub fn overworld_generator_layers() {
+        let generator = OverworldGenerator::new(1234);
+        let chunk = generator.generate(vek::Vec2::new(3, 5));
+        for z in 0..CHUNK_WIDTH {
+            for x in 0..CHUNK_WIDTH {
+                assert_eq!(chunk.get(x, 0, z), BlockKind::Bedrock.default_state().id());
+                let height = chunk.height(HeightmapKind::WorldSurface, x, z);
+                assert!(height > SEA_LEVEL);
+                assert_eq!(chunk.get(x, height, z), 0);
+            }
+        }
+    }
+}
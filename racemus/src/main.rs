@@ -1,8 +1,10 @@
 #![warn(rust_2018_idioms)]
 
+pub mod anvil;
 pub mod config;
 pub mod connection;
 pub mod controllers;
+pub mod generator;
 pub mod models;
 
 use async_std::net::TcpListener;
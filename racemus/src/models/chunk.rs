@@ -1 +1,423 @@
+use crate::models::LightData;
+use racemus_binary::{nbt, proto, PalettedContainer, VarVec, SECTION_WIDTH};
+use racemus_mc::blocks::{BlockKind, BlockState};
 
+pub const CHUNK_WIDTH: usize = SECTION_WIDTH;
+pub const CHUNK_SECTIONS: usize = 16;
+pub const CHUNK_HEIGHT: usize = CHUNK_SECTIONS * SECTION_WIDTH;
+
+// Biomes are stored per 4x4x4 cell
+const BIOME_CELL_WIDTH: usize = 4;
+const BIOME_WIDTH: usize = CHUNK_WIDTH / BIOME_CELL_WIDTH;
+const BIOME_HEIGHT: usize = CHUNK_HEIGHT / BIOME_CELL_WIDTH;
+pub const BIOME_COUNT: usize = BIOME_WIDTH * BIOME_WIDTH * BIOME_HEIGHT;
+pub const DEFAULT_BIOME: i32 = 1;
+
+// Heights range from 0 to 256 inclusive
+const HEIGHTMAP_BITS_PER_ENTRY: u8 = 9;
+
+// The only state that empty sections are filled with
+pub const AIR: u32 = 0;
+
+pub fn is_air(state: u32) -> bool {
+    matches!(
+        BlockState::from_id(state).map(BlockState::kind),
+        Some(BlockKind::Air) | Some(BlockKind::CaveAir) | Some(BlockKind::VoidAir)
+    )
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkSection {
+    blocks: PalettedContainer,
+}
+
+impl ChunkSection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_blocks(blocks: PalettedContainer) -> Self {
+        Self { blocks }
+    }
+
+    #[inline]
+    pub fn blocks(&self) -> &PalettedContainer {
+        &self.blocks
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    #[inline]
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.blocks.get(x, y, z)
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: usize, y: usize, z: usize, state: u32) -> u32 {
+        self.blocks.set(x, y, z, state)
+    }
+}
+
+impl<'a> From<&'a ChunkSection> for proto::ChunkSection<'a> {
+    fn from(value: &'a ChunkSection) -> Self {
+        (&value.blocks).into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeightmapKind {
+    MotionBlocking,
+    WorldSurface,
+}
+
+impl HeightmapKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MotionBlocking => "MOTION_BLOCKING",
+            Self::WorldSurface => "WORLD_SURFACE",
+        }
+    }
+
+    fn includes(self, state: u32) -> bool {
+        match self {
+            // The client uses this for rain and snow, which also stop at
+            // fluids
+            Self::MotionBlocking => {
+                BlockState::from_id(state).map_or(false, |s| s.blocks_motion() || s.has_fluid())
+            }
+            Self::WorldSurface => !is_air(state),
+        }
+    }
+}
+
+// Stores the height of the first free block above the highest block that
+// the heightmap kind includes, for each column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heightmap {
+    kind: HeightmapKind,
+    heights: VarVec,
+}
+
+impl Heightmap {
+    pub fn new(kind: HeightmapKind) -> Self {
+        Self {
+            kind,
+            heights: VarVec::with_capacity(CHUNK_WIDTH * CHUNK_WIDTH, HEIGHTMAP_BITS_PER_ENTRY),
+        }
+    }
+
+    #[inline]
+    pub fn kind(&self) -> HeightmapKind {
+        self.kind
+    }
+
+    #[inline]
+    pub fn get(&self, x: usize, z: usize) -> usize {
+        self.heights.get(z * CHUNK_WIDTH + x).unwrap() as usize
+    }
+
+    #[inline]
+    fn set(&mut self, x: usize, z: usize, height: usize) {
+        self.heights.set(z * CHUNK_WIDTH + x, height as u64);
+    }
+
+    pub fn to_nbt(&self) -> nbt::Value {
+        let longs: Vec<i64> = self.heights.get_inner().iter().map(|l| *l as i64).collect();
+        nbt::Value::LongArray(longs.into())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    position: vek::Vec2<i32>,
+    sections: [Option<Box<ChunkSection>>; CHUNK_SECTIONS],
+    motion_blocking: Heightmap,
+    world_surface: Heightmap,
+    biomes: Box<[i32]>,
+    light: LightData,
+    // One bit per section that has changed since the last call to take_dirty
+    dirty: u16,
+}
+
+impl Chunk {
+    pub fn new(position: vek::Vec2<i32>) -> Self {
+        Self {
+            position,
+            sections: Default::default(),
+            motion_blocking: Heightmap::new(HeightmapKind::MotionBlocking),
+            world_surface: Heightmap::new(HeightmapKind::WorldSurface),
+            biomes: vec![DEFAULT_BIOME; BIOME_COUNT].into_boxed_slice(),
+            light: LightData::new(),
+            dirty: 0,
+        }
+    }
+
+    #[inline]
+    pub fn position(&self) -> vek::Vec2<i32> {
+        self.position
+    }
+
+    #[inline]
+    pub fn section(&self, index: usize) -> Option<&ChunkSection> {
+        self.sections[index].as_deref()
+    }
+
+    pub fn set_section(&mut self, index: usize, section: Option<ChunkSection>) {
+        self.sections[index] = section.map(Box::new);
+        self.dirty |= 1 << index;
+        self.update_heightmaps();
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u32 {
+        match &self.sections[y / SECTION_WIDTH] {
+            Some(section) => section.get(x, y % SECTION_WIDTH, z),
+            None => AIR,
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, state: u32) -> u32 {
+        let index = y / SECTION_WIDTH;
+        if self.sections[index].is_none() {
+            if is_air(state) {
+                return AIR;
+            }
+            self.sections[index] = Some(Default::default());
+        }
+
+        let section = self.sections[index].as_mut().unwrap();
+        let old = section.set(x, y % SECTION_WIDTH, z, state);
+        if section.is_empty() {
+            self.sections[index] = None;
+        }
+
+        if old != state {
+            self.dirty |= 1 << index;
+            self.update_column(x, y, z, state);
+        }
+        old
+    }
+
+    #[inline]
+    pub fn heightmap(&self, kind: HeightmapKind) -> &Heightmap {
+        match kind {
+            HeightmapKind::MotionBlocking => &self.motion_blocking,
+            HeightmapKind::WorldSurface => &self.world_surface,
+        }
+    }
+
+    #[inline]
+    pub fn height(&self, kind: HeightmapKind, x: usize, z: usize) -> usize {
+        self.heightmap(kind).get(x, z)
+    }
+
+    pub fn heightmaps_nbt(&self) -> nbt::Value {
+        let mut result = nbt::Compound::new();
+        for heightmap in [&self.motion_blocking, &self.world_surface].iter() {
+            result.insert(heightmap.kind().name().into(), heightmap.to_nbt());
+        }
+        nbt::Value::Compound(result)
+    }
+
+    #[inline]
+    pub fn biomes(&self) -> &[i32] {
+        &self.biomes
+    }
+
+    pub fn biome(&self, x: usize, y: usize, z: usize) -> i32 {
+        self.biomes[Self::biome_index(x, y, z)]
+    }
+
+    pub fn set_biome(&mut self, x: usize, y: usize, z: usize, biome: i32) {
+        self.biomes[Self::biome_index(x, y, z)] = biome;
+        // Biomes are only sent with full chunks
+        self.dirty = !0;
+    }
+
+    #[inline]
+    fn biome_index(x: usize, y: usize, z: usize) -> usize {
+        let x = x / BIOME_CELL_WIDTH;
+        let y = y / BIOME_CELL_WIDTH;
+        let z = z / BIOME_CELL_WIDTH;
+        (y * BIOME_WIDTH + z) * BIOME_WIDTH + x
+    }
+
+    #[inline]
+    pub fn light(&self) -> &LightData {
+        &self.light
+    }
+
+    #[inline]
+    pub fn light_mut(&mut self) -> &mut LightData {
+        &mut self.light
+    }
+
+    pub fn set_light(&mut self, light: LightData) {
+        self.light = light;
+        self.light.mark_dirty();
+    }
+
+    // An estimate of the memory used by the chunk, in bytes
+    pub fn memory_usage(&self) -> usize {
+        let sections: usize = self
+            .sections
+            .iter()
+            .flatten()
+            .map(|s| std::mem::size_of::<ChunkSection>() + s.blocks.heap_size())
+            .sum();
+        let heightmaps = (self.motion_blocking.heights.get_inner().len()
+            + self.world_surface.heights.get_inner().len())
+            * std::mem::size_of::<u64>();
+        std::mem::size_of::<Self>()
+            + sections
+            + heightmaps
+            + self.biomes.len() * std::mem::size_of::<i32>()
+            + self.light.heap_size()
+    }
+
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty != 0
+    }
+
+    // Returns the sections that have changed since the last call, as a
+    // bitmask, and marks the chunk as clean.
+    pub fn take_dirty(&mut self) -> u16 {
+        std::mem::replace(&mut self.dirty, 0)
+    }
+
+    pub fn to_packet_sections(&self) -> [Option<proto::ChunkSection<'_>>; CHUNK_SECTIONS] {
+        let mut result = [None; CHUNK_SECTIONS];
+        for (index, section) in self.sections.iter().enumerate() {
+            result[index] = section.as_deref().map(|section| section.into());
+        }
+        result
+    }
+
+    fn update_column(&mut self, x: usize, y: usize, z: usize, state: u32) {
+        for kind in [HeightmapKind::MotionBlocking, HeightmapKind::WorldSurface].iter() {
+            let height = self.height(*kind, x, z);
+            if kind.includes(state) {
+                if y >= height {
+                    self.heightmap_mut(*kind).set(x, z, y + 1);
+                }
+            } else if y + 1 == height {
+                let height = self.scan_column(*kind, x, y, z);
+                self.heightmap_mut(*kind).set(x, z, height);
+            }
+        }
+    }
+
+    fn update_heightmaps(&mut self) {
+        for kind in [HeightmapKind::MotionBlocking, HeightmapKind::WorldSurface].iter() {
+            for z in 0..CHUNK_WIDTH {
+                for x in 0..CHUNK_WIDTH {
+                    let height = self.scan_column(*kind, x, CHUNK_HEIGHT, z);
+                    self.heightmap_mut(*kind).set(x, z, height);
+                }
+            }
+        }
+    }
+
+    // Finds the height of the column below (exclusive) the specified y.
+    fn scan_column(&self, kind: HeightmapKind, x: usize, y: usize, z: usize) -> usize {
+        (0..y)
+            .rev()
+            .find(|y| kind.includes(self.get(x, *y, z)))
+            .map_or(0, |y| y + 1)
+    }
+
+    #[inline]
+    fn heightmap_mut(&mut self, kind: HeightmapKind) -> &mut Heightmap {
+        match kind {
+            HeightmapKind::MotionBlocking => &mut self.motion_blocking,
+            HeightmapKind::WorldSurface => &mut self.world_surface,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: u32 = 1;
+    const CAVE_AIR: u32 = 9130;
+
+    #[test]
+    pub fn chunk_set() {
+        let mut chunk = Chunk::new(vek::Vec2::new(1, 2));
+        assert_eq!(chunk.set(1, 40, 2, STONE), AIR);
+        assert_eq!(chunk.get(1, 40, 2), STONE);
+        assert_eq!(chunk.get(1, 41, 2), AIR);
+        assert!(chunk.section(0).is_none());
+        assert_eq!(chunk.section(2).unwrap().blocks().block_count(), 1);
+        assert_eq!(chunk.take_dirty(), 1 << 2);
+        assert!(!chunk.is_dirty());
+
+        assert_eq!(chunk.set(1, 40, 2, AIR), STONE);
+        assert!(chunk.section(2).is_none());
+        assert_eq!(chunk.take_dirty(), 1 << 2);
+    }
+
+    #[test]
+    pub fn chunk_heightmaps() {
+        let mut chunk = Chunk::new(vek::Vec2::zero());
+        chunk.set(3, 0, 4, STONE);
+        chunk.set(3, 100, 4, STONE);
+        assert_eq!(chunk.height(HeightmapKind::WorldSurface, 3, 4), 101);
+        assert_eq!(chunk.height(HeightmapKind::MotionBlocking, 3, 4), 101);
+        assert_eq!(chunk.height(HeightmapKind::WorldSurface, 4, 3), 0);
+
+        chunk.set(3, 50, 4, STONE);
+        assert_eq!(chunk.height(HeightmapKind::WorldSurface, 3, 4), 101);
+
+        chunk.set(3, 100, 4, CAVE_AIR);
+        assert_eq!(chunk.height(HeightmapKind::WorldSurface, 3, 4), 51);
+        chunk.set(3, 50, 4, AIR);
+        assert_eq!(chunk.height(HeightmapKind::WorldSurface, 3, 4), 1);
+
+        // Flowers don't block motion, but water does
+        let poppy = BlockKind::Poppy.default_state().id();
+        let water = BlockKind::Water.default_state().id();
+        chunk.set(3, 1, 4, poppy);
+        assert_eq!(chunk.height(HeightmapKind::WorldSurface, 3, 4), 2);
+        assert_eq!(chunk.height(HeightmapKind::MotionBlocking, 3, 4), 1);
+        chunk.set(3, 2, 4, water);
+        assert_eq!(chunk.height(HeightmapKind::WorldSurface, 3, 4), 3);
+        assert_eq!(chunk.height(HeightmapKind::MotionBlocking, 3, 4), 3);
+    }
+
+    #[test]
+    pub fn chunk_set_section() {
+        let mut section = ChunkSection::new();
+        section.set(0, 15, 0, STONE);
+
+        let mut chunk = Chunk::new(vek::Vec2::zero());
+        chunk.set_section(1, Some(section));
+        assert_eq!(chunk.get(0, 31, 0), STONE);
+        assert_eq!(chunk.height(HeightmapKind::MotionBlocking, 0, 0), 32);
+        assert_eq!(chunk.take_dirty(), 1 << 1);
+    }
+
+    #[test]
+    pub fn chunk_biomes() {
+        let mut chunk = Chunk::new(vek::Vec2::zero());
+        assert_eq!(chunk.biome(15, 255, 15), DEFAULT_BIOME);
+        chunk.set_biome(4, 8, 12, 2);
+        assert_eq!(chunk.biome(7, 11, 15), 2);
+        assert_eq!(chunk.biome(3, 8, 12), DEFAULT_BIOME);
+        assert_eq!(chunk.biomes()[(2 * BIOME_WIDTH + 3) * BIOME_WIDTH + 1], 2);
+    }
+
+    #[test]
+    pub fn chunk_memory_usage() {
+        let mut chunk = Chunk::new(vek::Vec2::zero());
+        let empty = chunk.memory_usage();
+        chunk.set(0, 0, 0, STONE);
+        assert!(chunk.memory_usage() > empty);
+        chunk.light_mut().set_sky(0, 0, 0, 15);
+        assert!(chunk.memory_usage() > empty + 2048);
+    }
+}
@@ -1,17 +1,289 @@
-const CHUNK_SIZE: usize = 16;
+use crate::models::CHUNK_SECTIONS;
+use racemus_binary::{proto, SECTION_WIDTH};
 
-struct LightLayer([u8; CHUNK_SIZE * CHUNK_SIZE]);
+pub const MAX_LIGHT: u8 = 15;
+// Light is also stored for the sections directly below and above the chunk
+pub const LIGHT_SECTIONS: usize = CHUNK_SECTIONS + 2;
+
+// Light levels for a single section, packed two per byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LightLayer(Box<[u8]>);
+
+impl Default for LightLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl LightLayer {
     pub fn new() -> Self {
-        Self([0u64; 16])
+        Self::filled(0)
+    }
+
+    pub fn filled(level: u8) -> Self {
+        let level = level & MAX_LIGHT;
+        Self(vec![level | (level << 4); proto::LIGHT_ARRAY_LEN].into_boxed_slice())
+    }
+
+    // Uses the same layout as the network protocol and Anvil files
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() == proto::LIGHT_ARRAY_LEN {
+            Some(Self(data.into()))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn index(x: usize, y: usize, z: usize) -> (usize, usize) {
+        debug_assert!(x < SECTION_WIDTH && y < SECTION_WIDTH && z < SECTION_WIDTH);
+        let index = (y * SECTION_WIDTH + z) * SECTION_WIDTH + x;
+        (index >> 1, (index & 1) * 4)
     }
 
-    pub fn set(x: u8, z: u8, level: u8) {
-        let level = level & 0b1111;
+    #[inline]
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        let (index, shift) = Self::index(x, y, z);
+        (self.0[index] >> shift) & MAX_LIGHT
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        let (index, shift) = Self::index(x, y, z);
+        let value = &mut self.0[index];
+        *value = (*value & !(MAX_LIGHT << shift)) | ((level & MAX_LIGHT) << shift);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|b| *b == 0)
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
     }
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct LightData {
+    // Indexed by section Y + 1, absent layers are completely dark. They
+    // aren't sent to the client, which works them out itself like it does
+    // for vanilla, which doesn't save sky light above the terrain.
+    sky: [Option<LightLayer>; LIGHT_SECTIONS],
+    block: [Option<LightLayer>; LIGHT_SECTIONS],
+    // One bit per layer that has changed since the last call to take_update
+    sky_dirty: u32,
+    block_dirty: u32,
+}
+
+impl LightData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn sky_layer(&self, index: usize) -> Option<&LightLayer> {
+        self.sky[index].as_ref()
+    }
+
+    #[inline]
+    pub fn block_layer(&self, index: usize) -> Option<&LightLayer> {
+        self.block[index].as_ref()
+    }
+
+    pub fn set_sky_layer(&mut self, index: usize, layer: Option<LightLayer>) {
+        self.sky[index] = layer;
+        self.sky_dirty |= 1 << index;
+    }
+
+    pub fn set_block_layer(&mut self, index: usize, layer: Option<LightLayer>) {
+        self.block[index] = layer;
+        self.block_dirty |= 1 << index;
+    }
+
+    pub fn sky(&self, x: usize, y: usize, z: usize) -> u8 {
+        Self::get(&self.sky, x, y, z)
+    }
+
+    pub fn block(&self, x: usize, y: usize, z: usize) -> u8 {
+        Self::get(&self.block, x, y, z)
+    }
+
+    pub fn set_sky(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        Self::set(&mut self.sky, &mut self.sky_dirty, x, y, z, level)
+    }
+
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        Self::set(&mut self.block, &mut self.block_dirty, x, y, z, level)
+    }
+
+    pub fn sky_sections(&self) -> [proto::LightSection<'_>; LIGHT_SECTIONS] {
+        Self::sections(&self.sky)
+    }
+
+    pub fn block_sections(&self) -> [proto::LightSection<'_>; LIGHT_SECTIONS] {
+        Self::sections(&self.block)
+    }
+
+    // The number of bytes allocated for the layers
+    pub fn heap_size(&self) -> usize {
+        let layers = self.sky.iter().chain(self.block.iter()).flatten().count();
+        layers * proto::LIGHT_ARRAY_LEN
+    }
+
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        self.sky_dirty != 0 || self.block_dirty != 0
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.sky_dirty = (1 << LIGHT_SECTIONS) - 1;
+        self.block_dirty = (1 << LIGHT_SECTIONS) - 1;
+    }
+
+    // Copies the layers that have changed since the last call and marks the
+    // light as clean.
+    pub fn take_update(&mut self) -> Option<LightUpdate> {
+        if !self.is_dirty() {
+            return None;
+        }
+
+        let mut result = LightUpdate::default();
+        for index in 0..LIGHT_SECTIONS {
+            if self.sky_dirty & (1 << index) != 0 {
+                result.sky[index] = Some(self.sky[index].clone().unwrap_or_default());
+            }
+            if self.block_dirty & (1 << index) != 0 {
+                result.block[index] = Some(self.block[index].clone().unwrap_or_default());
+            }
+        }
+        self.sky_dirty = 0;
+        self.block_dirty = 0;
+        Some(result)
+    }
+
+    // Coordinates are relative to the chunk, the layers below and above the
+    // chunk are only accessible through the layer functions.
+    fn get(layers: &[Option<LightLayer>], x: usize, y: usize, z: usize) -> u8 {
+        match &layers[y / SECTION_WIDTH + 1] {
+            Some(layer) => layer.get(x, y % SECTION_WIDTH, z),
+            None => 0,
+        }
+    }
+
+    fn set(
+        layers: &mut [Option<LightLayer>],
+        dirty: &mut u32,
+        x: usize,
+        y: usize,
+        z: usize,
+        level: u8,
+    ) {
+        let index = y / SECTION_WIDTH + 1;
+        let layer = &mut layers[index];
+        let y = y % SECTION_WIDTH;
+        match layer {
+            Some(layer) if layer.get(x, y, z) == level => return,
+            Some(layer) => layer.set(x, y, z, level),
+            None if level == 0 => return,
+            None => layer
+                .get_or_insert_with(Default::default)
+                .set(x, y, z, level),
+        }
+        *dirty |= 1 << index;
+    }
+
+    fn sections(layers: &[Option<LightLayer>]) -> [proto::LightSection<'_>; LIGHT_SECTIONS] {
+        let mut result = [proto::LightSection::Unchanged; LIGHT_SECTIONS];
+        for (index, layer) in layers.iter().enumerate() {
+            result[index] = match layer {
+                Some(layer) if layer.is_empty() => proto::LightSection::Empty,
+                Some(layer) => proto::LightSection::Data(layer.as_bytes()),
+                None => proto::LightSection::Unchanged,
+            };
+        }
+        result
+    }
+}
 
-}
\ No newline at end of file
+// The layers of a chunk that have changed, absent layers are unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LightUpdate {
+    sky: [Option<LightLayer>; LIGHT_SECTIONS],
+    block: [Option<LightLayer>; LIGHT_SECTIONS],
+}
+
+impl LightUpdate {
+    pub fn sky_sections(&self) -> [proto::LightSection<'_>; LIGHT_SECTIONS] {
+        Self::sections(&self.sky)
+    }
+
+    pub fn block_sections(&self) -> [proto::LightSection<'_>; LIGHT_SECTIONS] {
+        Self::sections(&self.block)
+    }
+
+    fn sections(layers: &[Option<LightLayer>]) -> [proto::LightSection<'_>; LIGHT_SECTIONS] {
+        let mut result = [proto::LightSection::Unchanged; LIGHT_SECTIONS];
+        for (index, layer) in layers.iter().enumerate() {
+            result[index] = match layer {
+                Some(layer) if layer.is_empty() => proto::LightSection::Empty,
+                Some(layer) => proto::LightSection::Data(layer.as_bytes()),
+                None => proto::LightSection::Unchanged,
+            };
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn light_layer_set() {
+        let mut layer = LightLayer::new();
+        layer.set(0, 0, 0, 3);
+        layer.set(1, 0, 0, 12);
+        layer.set(15, 15, 15, 15);
+        assert_eq!(layer.get(0, 0, 0), 3);
+        assert_eq!(layer.get(1, 0, 0), 12);
+        assert_eq!(layer.get(15, 15, 15), 15);
+        assert_eq!(layer.as_bytes()[0], 0xc3);
+
+        layer.set(0, 0, 0, 0);
+        assert_eq!(layer.get(1, 0, 0), 12);
+        assert_eq!(layer.as_bytes()[0], 0xc0);
+    }
+
+    #[test]
+    pub fn light_data_take_update() {
+        let mut light = LightData::new();
+        light.set_sky(2, 20, 3, MAX_LIGHT);
+        light.set_block(0, 0, 0, 0);
+        assert_eq!(light.sky(2, 20, 3), MAX_LIGHT);
+        assert_eq!(light.block(0, 0, 0), 0);
+        assert!(light.block_layer(1).is_none());
+
+        let update = light.take_update().unwrap();
+        let sky = update.sky_sections();
+        assert_eq!(sky[1], proto::LightSection::Unchanged);
+        assert_eq!(
+            sky[2],
+            proto::LightSection::Data(light.sky_layer(2).unwrap().as_bytes())
+        );
+        assert_eq!(update.block_sections()[1], proto::LightSection::Unchanged);
+        assert!(light.take_update().is_none());
+
+        light.set_sky(2, 20, 3, MAX_LIGHT);
+        assert!(!light.is_dirty());
+        light.set_sky(2, 20, 3, 0);
+        assert_eq!(
+            light.take_update().unwrap().sky_sections()[2],
+            proto::LightSection::Empty
+        );
+
+        let sections = light.sky_sections();
+        assert_eq!(sections[0], proto::LightSection::Unchanged);
+        assert_eq!(sections[2], proto::LightSection::Empty);
+    }
+}
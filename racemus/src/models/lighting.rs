@@ -0,0 +1,597 @@
+use crate::models::{
+    Chunk, HeightmapKind, LightData, LightLayer, CHUNK_HEIGHT, CHUNK_WIDTH, LIGHT_SECTIONS,
+    MAX_LIGHT,
+};
+use racemus_mc::blocks::BlockState;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+const DOWN: vek::Vec3<i32> = vek::Vec3 { x: 0, y: -1, z: 0 };
+const DIRECTIONS: [vek::Vec3<i32>; 6] = [
+    DOWN,
+    vek::Vec3 { x: 0, y: 1, z: 0 },
+    vek::Vec3 { x: -1, y: 0, z: 0 },
+    vek::Vec3 { x: 1, y: 0, z: 0 },
+    vek::Vec3 { x: 0, y: 0, z: -1 },
+    vek::Vec3 { x: 0, y: 0, z: 1 },
+];
+
+pub trait LightProperties {
+    // The light level that the block state emits
+    fn emission(&self, state: u32) -> u8;
+    // How much light is lost when passing through the block state, light
+    // always loses at least one level per block
+    fn opacity(&self, state: u32) -> u8;
+}
+
+// Uses the emission and opacity of the vanilla block states, states that are
+// not in the registry block all light.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RegistryLightProperties;
+
+impl LightProperties for RegistryLightProperties {
+    fn emission(&self, state: u32) -> u8 {
+        BlockState::from_id(state).map_or(0, BlockState::light_emission)
+    }
+
+    fn opacity(&self, state: u32) -> u8 {
+        BlockState::from_id(state).map_or(MAX_LIGHT, BlockState::light_opacity)
+    }
+}
+
+pub trait ChunkStore {
+    fn chunk(&self, position: vek::Vec2<i32>) -> Option<&Chunk>;
+    fn chunk_mut(&mut self, position: vek::Vec2<i32>) -> Option<&mut Chunk>;
+}
+
+impl ChunkStore for HashMap<vek::Vec2<i32>, Chunk> {
+    fn chunk(&self, position: vek::Vec2<i32>) -> Option<&Chunk> {
+        self.get(&position)
+    }
+
+    fn chunk_mut(&mut self, position: vek::Vec2<i32>) -> Option<&mut Chunk> {
+        self.get_mut(&position)
+    }
+}
+
+// Chunks that are shared are copied before they are changed
+impl ChunkStore for HashMap<vek::Vec2<i32>, Arc<Chunk>> {
+    fn chunk(&self, position: vek::Vec2<i32>) -> Option<&Chunk> {
+        self.get(&position).map(|c| &**c)
+    }
+
+    fn chunk_mut(&mut self, position: vek::Vec2<i32>) -> Option<&mut Chunk> {
+        self.get_mut(&position).map(Arc::make_mut)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LightKind {
+    Sky,
+    Block,
+}
+
+// Splits a world position into the chunk position and the position within
+// that chunk.
+fn locate(position: vek::Vec3<i32>) -> Option<(vek::Vec2<i32>, usize, usize, usize)> {
+    if position.y < 0 || position.y >= CHUNK_HEIGHT as i32 {
+        return None;
+    }
+    let width = CHUNK_WIDTH as i32;
+    Some((
+        vek::Vec2::new(position.x.div_euclid(width), position.z.div_euclid(width)),
+        position.x.rem_euclid(width) as usize,
+        position.y as usize,
+        position.z.rem_euclid(width) as usize,
+    ))
+}
+
+// Flood fills light through a set of loaded chunks. Light does not enter
+// chunks that are not loaded, they are expected to be lit with light_chunk
+// once they are.
+#[derive(Debug, Clone, Default)]
+pub struct LightEngine<P: LightProperties> {
+    properties: P,
+}
+
+impl<P: LightProperties> LightEngine<P> {
+    pub fn new(properties: P) -> Self {
+        Self { properties }
+    }
+
+    // Computes all light for a chunk from scratch, including light that
+    // flows in from and out to neighbouring chunks.
+    pub fn light_chunk<C: ChunkStore>(&self, chunks: &mut C, position: vek::Vec2<i32>) {
+        let chunk = match chunks.chunk_mut(position) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+
+        let origin = vek::Vec3::new(
+            position.x * CHUNK_WIDTH as i32,
+            0,
+            position.y * CHUNK_WIDTH as i32,
+        );
+        let mut sky_queue = VecDeque::new();
+        let mut block_queue = VecDeque::new();
+
+        let mut light = LightData::new();
+        light.set_sky_layer(LIGHT_SECTIONS - 1, Some(LightLayer::filled(MAX_LIGHT)));
+
+        // Blocks above the heightmap never block light, so sky light only
+        // needs to be traced downwards from it.
+        let mut tops = [0usize; CHUNK_WIDTH * CHUNK_WIDTH];
+        for z in 0..CHUNK_WIDTH {
+            for x in 0..CHUNK_WIDTH {
+                let mut y = chunk.height(HeightmapKind::MotionBlocking, x, z);
+                while y > 0 && self.properties.opacity(chunk.get(x, y - 1, z)) == 0 {
+                    y -= 1;
+                }
+                for y in y..CHUNK_HEIGHT {
+                    light.set_sky(x, y, z, MAX_LIGHT);
+                }
+                tops[z * CHUNK_WIDTH + x] = y;
+            }
+        }
+
+        // Only columns next to a taller column, or at the edge of the chunk,
+        // spread sky light sideways.
+        for z in 0..CHUNK_WIDTH {
+            for x in 0..CHUNK_WIDTH {
+                let top = tops[z * CHUNK_WIDTH + x];
+                let edge = x == 0 || z == 0 || x == CHUNK_WIDTH - 1 || z == CHUNK_WIDTH - 1;
+                let end = if edge {
+                    CHUNK_HEIGHT
+                } else {
+                    let neighbours = [
+                        tops[z * CHUNK_WIDTH + x - 1],
+                        tops[z * CHUNK_WIDTH + x + 1],
+                        tops[(z - 1) * CHUNK_WIDTH + x],
+                        tops[(z + 1) * CHUNK_WIDTH + x],
+                    ];
+                    *neighbours.iter().max().unwrap()
+                };
+                for y in top..end.max(top + 1).min(CHUNK_HEIGHT) {
+                    sky_queue.push_back(origin + vek::Vec3::new(x as i32, y as i32, z as i32));
+                }
+            }
+        }
+
+        for index in 0..(CHUNK_HEIGHT / CHUNK_WIDTH) {
+            let section = match chunk.section(index) {
+                Some(section) => section,
+                None => continue,
+            };
+            for y in 0..CHUNK_WIDTH {
+                for z in 0..CHUNK_WIDTH {
+                    for x in 0..CHUNK_WIDTH {
+                        let emission = self.properties.emission(section.get(x, y, z));
+                        if emission > 0 {
+                            let y = index * CHUNK_WIDTH + y;
+                            light.set_block(x, y, z, emission);
+                            block_queue
+                                .push_back(origin + vek::Vec3::new(x as i32, y as i32, z as i32));
+                        }
+                    }
+                }
+            }
+        }
+
+        chunk.set_light(light);
+
+        // Light from neighbouring chunks flows into this one
+        Self::queue_border(chunks, origin, -1, &mut sky_queue, &mut block_queue);
+        Self::queue_border(
+            chunks,
+            origin,
+            CHUNK_WIDTH as i32,
+            &mut sky_queue,
+            &mut block_queue,
+        );
+
+        self.propagate(chunks, LightKind::Sky, &mut sky_queue);
+        self.propagate(chunks, LightKind::Block, &mut block_queue);
+    }
+
+    // Spreads light between a chunk that is already lit, such as one that
+    // was saved with its light, and its neighbours without recomputing it.
+    pub fn spread_chunk<C: ChunkStore>(&self, chunks: &mut C, position: vek::Vec2<i32>) {
+        if chunks.chunk(position).is_none() {
+            return;
+        }
+
+        let origin = vek::Vec3::new(
+            position.x * CHUNK_WIDTH as i32,
+            0,
+            position.y * CHUNK_WIDTH as i32,
+        );
+        let mut sky_queue = VecDeque::new();
+        let mut block_queue = VecDeque::new();
+        for offset in [-1, 0, CHUNK_WIDTH as i32 - 1, CHUNK_WIDTH as i32].iter() {
+            Self::queue_border(chunks, origin, *offset, &mut sky_queue, &mut block_queue);
+        }
+
+        self.propagate(chunks, LightKind::Sky, &mut sky_queue);
+        self.propagate(chunks, LightKind::Block, &mut block_queue);
+    }
+
+    // Queues the lit positions of the ring that is offset from the origin of
+    // a chunk by the same amount on the x and z axes, -1 and CHUNK_WIDTH are
+    // just outside of the chunk.
+    fn queue_border<C: ChunkStore>(
+        chunks: &C,
+        origin: vek::Vec3<i32>,
+        offset: i32,
+        sky_queue: &mut VecDeque<vek::Vec3<i32>>,
+        block_queue: &mut VecDeque<vek::Vec3<i32>>,
+    ) {
+        for i in 0..(CHUNK_WIDTH as i32) {
+            for y in 0..(CHUNK_HEIGHT as i32) {
+                for border in [vek::Vec3::new(offset, y, i), vek::Vec3::new(i, y, offset)].iter() {
+                    let border = origin + *border;
+                    if Self::light(chunks, LightKind::Sky, border).unwrap_or(0) > 1 {
+                        sky_queue.push_back(border);
+                    }
+                    if Self::light(chunks, LightKind::Block, border).unwrap_or(0) > 1 {
+                        block_queue.push_back(border);
+                    }
+                }
+            }
+        }
+    }
+
+    // Sets a block and updates the light around it, returning the previous
+    // block state or None if the chunk is not loaded.
+    pub fn set_block<C: ChunkStore>(
+        &self,
+        chunks: &mut C,
+        position: vek::Vec3<i32>,
+        state: u32,
+    ) -> Option<u32> {
+        let (chunk, x, y, z) = locate(position)?;
+        let old = chunks.chunk_mut(chunk)?.set(x, y, z, state);
+        if old != state {
+            self.relight(chunks, LightKind::Sky, position, state);
+            self.relight(chunks, LightKind::Block, position, state);
+        }
+        Some(old)
+    }
+
+    fn relight<C: ChunkStore>(
+        &self,
+        chunks: &mut C,
+        kind: LightKind,
+        position: vek::Vec3<i32>,
+        state: u32,
+    ) {
+        let mut removal = VecDeque::new();
+        let mut queue = VecDeque::new();
+
+        let level = Self::light(chunks, kind, position).unwrap_or(0);
+        if level > 0 {
+            Self::set_light(chunks, kind, position, 0);
+            removal.push_back((position, level));
+            self.unpropagate(chunks, kind, &mut removal, &mut queue);
+        }
+
+        let level = match kind {
+            LightKind::Block => self.properties.emission(state),
+            // The layer above the chunk is always fully lit
+            LightKind::Sky if position.y == CHUNK_HEIGHT as i32 - 1 => {
+                self.attenuate(kind, MAX_LIGHT, DOWN, state)
+            }
+            LightKind::Sky => 0,
+        };
+        if level > 0 {
+            Self::set_light(chunks, kind, position, level);
+            queue.push_back(position);
+        }
+
+        // The neighbours may now be able to light the block
+        for direction in DIRECTIONS.iter() {
+            let neighbour = position + *direction;
+            if Self::light(chunks, kind, neighbour).unwrap_or(0) > 1 {
+                queue.push_back(neighbour);
+            }
+        }
+
+        self.propagate(chunks, kind, &mut queue);
+    }
+
+    fn propagate<C: ChunkStore>(
+        &self,
+        chunks: &mut C,
+        kind: LightKind,
+        queue: &mut VecDeque<vek::Vec3<i32>>,
+    ) {
+        while let Some(position) = queue.pop_front() {
+            let level = Self::light(chunks, kind, position).unwrap_or(0);
+            if level <= 1 {
+                continue;
+            }
+
+            for direction in DIRECTIONS.iter() {
+                let neighbour = position + *direction;
+                let state = match Self::state(chunks, neighbour) {
+                    Some(state) => state,
+                    None => continue,
+                };
+                let target = self.attenuate(kind, level, *direction, state);
+                if target > Self::light(chunks, kind, neighbour).unwrap_or(0) {
+                    Self::set_light(chunks, kind, neighbour, target);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    // Darkens everything that was lit by the positions in the removal queue,
+    // and queues the light that borders the darkened area so that it can be
+    // propagated back in.
+    fn unpropagate<C: ChunkStore>(
+        &self,
+        chunks: &mut C,
+        kind: LightKind,
+        removal: &mut VecDeque<(vek::Vec3<i32>, u8)>,
+        queue: &mut VecDeque<vek::Vec3<i32>>,
+    ) {
+        while let Some((position, level)) = removal.pop_front() {
+            for direction in DIRECTIONS.iter() {
+                let neighbour = position + *direction;
+                let current = match Self::light(chunks, kind, neighbour) {
+                    Some(current) if current > 0 => current,
+                    _ => continue,
+                };
+
+                let sky_column = kind == LightKind::Sky
+                    && *direction == DOWN
+                    && level == MAX_LIGHT
+                    && current == MAX_LIGHT;
+                if current < level || sky_column {
+                    Self::set_light(chunks, kind, neighbour, 0);
+                    removal.push_back((neighbour, current));
+
+                    let state = Self::state(chunks, neighbour).unwrap_or(0);
+                    if kind == LightKind::Block && self.properties.emission(state) > 0 {
+                        Self::set_light(chunks, kind, neighbour, self.properties.emission(state));
+                        queue.push_back(neighbour);
+                    }
+                } else {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    fn attenuate(&self, kind: LightKind, level: u8, direction: vek::Vec3<i32>, state: u32) -> u8 {
+        let opacity = self.properties.opacity(state);
+        // Sky light travels straight down without losing any strength
+        if kind == LightKind::Sky && level == MAX_LIGHT && direction == DOWN && opacity == 0 {
+            MAX_LIGHT
+        } else {
+            level.saturating_sub(opacity.max(1))
+        }
+    }
+
+    fn state<C: ChunkStore>(chunks: &C, position: vek::Vec3<i32>) -> Option<u32> {
+        let (chunk, x, y, z) = locate(position)?;
+        Some(chunks.chunk(chunk)?.get(x, y, z))
+    }
+
+    fn light<C: ChunkStore>(chunks: &C, kind: LightKind, position: vek::Vec3<i32>) -> Option<u8> {
+        let (chunk, x, y, z) = locate(position)?;
+        let light = chunks.chunk(chunk)?.light();
+        Some(match kind {
+            LightKind::Sky => light.sky(x, y, z),
+            LightKind::Block => light.block(x, y, z),
+        })
+    }
+
+    fn set_light<C: ChunkStore>(
+        chunks: &mut C,
+        kind: LightKind,
+        position: vek::Vec3<i32>,
+        level: u8,
+    ) {
+        if let Some((chunk, x, y, z)) = locate(position) {
+            if let Some(chunk) = chunks.chunk_mut(chunk) {
+                let light = chunk.light_mut();
+                match kind {
+                    LightKind::Sky => light.set_sky(x, y, z, level),
+                    LightKind::Block => light.set_block(x, y, z, level),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: u32 = 1;
+    const TORCH: u32 = 2;
+
+    struct TestLightProperties;
+
+    impl LightProperties for TestLightProperties {
+        fn emission(&self, state: u32) -> u8 {
+            if state == TORCH {
+                14
+            } else {
+                0
+            }
+        }
+
+        fn opacity(&self, state: u32) -> u8 {
+            if state == STONE {
+                MAX_LIGHT
+            } else {
+                0
+            }
+        }
+    }
+
+    fn make_chunks(positions: &[vek::Vec2<i32>]) -> HashMap<vek::Vec2<i32>, Chunk> {
+        let mut chunks = HashMap::new();
+        for position in positions.iter() {
+            let mut chunk = Chunk::new(*position);
+            for z in 0..CHUNK_WIDTH {
+                for x in 0..CHUNK_WIDTH {
+                    chunk.set(x, 10, z, STONE);
+                }
+            }
+            chunks.insert(*position, chunk);
+        }
+        chunks
+    }
+
+    fn sky(chunks: &HashMap<vek::Vec2<i32>, Chunk>, x: i32, y: i32, z: i32) -> u8 {
+        LightEngine::<TestLightProperties>::light(chunks, LightKind::Sky, vek::Vec3::new(x, y, z))
+            .unwrap()
+    }
+
+    fn block(chunks: &HashMap<vek::Vec2<i32>, Chunk>, x: i32, y: i32, z: i32) -> u8 {
+        LightEngine::<TestLightProperties>::light(chunks, LightKind::Block, vek::Vec3::new(x, y, z))
+            .unwrap()
+    }
+
+    #[test]
+    pub fn light_engine_sky() {
+        let engine = LightEngine::new(TestLightProperties);
+        let mut chunks = make_chunks(&[vek::Vec2::zero()]);
+        engine.light_chunk(&mut chunks, vek::Vec2::zero());
+
+        assert_eq!(sky(&chunks, 3, 255, 3), MAX_LIGHT);
+        assert_eq!(sky(&chunks, 3, 11, 3), MAX_LIGHT);
+        assert_eq!(sky(&chunks, 3, 10, 3), 0);
+        assert_eq!(sky(&chunks, 3, 9, 3), 0);
+
+        // A hole lets sky light straight down, and it spreads sideways
+        engine.set_block(&mut chunks, vek::Vec3::new(3, 10, 3), 0);
+        assert_eq!(sky(&chunks, 3, 10, 3), MAX_LIGHT);
+        assert_eq!(sky(&chunks, 3, 0, 3), MAX_LIGHT);
+        assert_eq!(sky(&chunks, 4, 9, 3), MAX_LIGHT - 1);
+        assert_eq!(sky(&chunks, 8, 9, 3), MAX_LIGHT - 5);
+
+        // Closing it darkens everything below again
+        engine.set_block(&mut chunks, vek::Vec3::new(3, 10, 3), STONE);
+        assert_eq!(sky(&chunks, 3, 10, 3), 0);
+        assert_eq!(sky(&chunks, 3, 0, 3), 0);
+        assert_eq!(sky(&chunks, 8, 9, 3), 0);
+        assert_eq!(sky(&chunks, 3, 11, 3), MAX_LIGHT);
+    }
+
+    #[test]
+    pub fn light_engine_block() {
+        let engine = LightEngine::new(TestLightProperties);
+        let mut chunks = make_chunks(&[vek::Vec2::zero(), vek::Vec2::new(-1, 0)]);
+        engine.light_chunk(&mut chunks, vek::Vec2::zero());
+        engine.light_chunk(&mut chunks, vek::Vec2::new(-1, 0));
+        chunks
+            .get_mut(&vek::Vec2::zero())
+            .unwrap()
+            .light_mut()
+            .take_update();
+
+        // Light crosses into the neighbouring chunk
+        engine.set_block(&mut chunks, vek::Vec3::new(1, 5, 1), TORCH);
+        assert_eq!(block(&chunks, 1, 5, 1), 14);
+        assert_eq!(block(&chunks, 1, 6, 1), 13);
+        assert_eq!(block(&chunks, -1, 5, 1), 12);
+        assert_eq!(block(&chunks, 1, 10, 1), 0);
+
+        let update = chunks
+            .get_mut(&vek::Vec2::zero())
+            .unwrap()
+            .light_mut()
+            .take_update()
+            .unwrap();
+        let sections = update.block_sections();
+        assert_ne!(sections[1], racemus_binary::proto::LightSection::Unchanged);
+        assert_eq!(sections[2], racemus_binary::proto::LightSection::Unchanged);
+
+        // Walls block light
+        engine.set_block(&mut chunks, vek::Vec3::new(0, 5, 1), STONE);
+        assert_eq!(block(&chunks, 0, 5, 1), 0);
+        assert_eq!(block(&chunks, -1, 5, 1), 10);
+
+        engine.set_block(&mut chunks, vek::Vec3::new(1, 5, 1), 0);
+        assert_eq!(block(&chunks, 1, 5, 1), 0);
+        assert_eq!(block(&chunks, -1, 5, 1), 0);
+        assert_eq!(block(&chunks, 1, 6, 1), 0);
+    }
+
+    #[test]
+    pub fn light_engine_registry() {
+        use racemus_mc::blocks::BlockKind;
+
+        let engine = LightEngine::new(RegistryLightProperties);
+        let mut chunks = HashMap::new();
+        let mut chunk = Chunk::new(vek::Vec2::zero());
+        chunk.set(3, 5, 3, BlockKind::Torch.default_state().id());
+        chunk.set(4, 5, 3, BlockKind::Stone.default_state().id());
+        chunk.set(2, 5, 3, BlockKind::Glass.default_state().id());
+        chunks.insert(vek::Vec2::zero(), chunk);
+        engine.light_chunk(&mut chunks, vek::Vec2::zero());
+
+        let block = |x, y, z| {
+            LightEngine::<RegistryLightProperties>::light(
+                &chunks,
+                LightKind::Block,
+                vek::Vec3::new(x, y, z),
+            )
+            .unwrap()
+        };
+        assert_eq!(block(3, 5, 3), 14);
+        assert_eq!(block(4, 5, 3), 0);
+        assert_eq!(block(2, 5, 3), 13);
+        assert_eq!(block(5, 5, 3), 10);
+    }
+
+    #[test]
+    pub fn light_engine_spread() {
+        let engine = LightEngine::new(TestLightProperties);
+        let mut chunks = make_chunks(&[vek::Vec2::zero()]);
+        engine.light_chunk(&mut chunks, vek::Vec2::zero());
+
+        // Saved light is kept even though nothing emits it
+        let mut chunk = make_chunks(&[vek::Vec2::new(1, 0)])
+            .remove(&vek::Vec2::new(1, 0))
+            .unwrap();
+        chunk.light_mut().set_block(0, 5, 3, 12);
+        chunks.insert(vek::Vec2::new(1, 0), chunk);
+        engine.spread_chunk(&mut chunks, vek::Vec2::new(1, 0));
+
+        assert_eq!(block(&chunks, 16, 5, 3), 12);
+        assert_eq!(block(&chunks, 15, 5, 3), 11);
+        assert_eq!(block(&chunks, 17, 5, 3), 11);
+        assert_eq!(sky(&chunks, 16, 11, 3), MAX_LIGHT - 1);
+        assert_eq!(sky(&chunks, 16, 5, 3), 0);
+    }
+
+    #[test]
+    pub fn light_engine_neighbours() {
+        let engine = LightEngine::new(TestLightProperties);
+        let mut chunks = make_chunks(&[vek::Vec2::zero()]);
+        chunks
+            .get_mut(&vek::Vec2::zero())
+            .unwrap()
+            .set(15, 5, 3, TORCH);
+        engine.light_chunk(&mut chunks, vek::Vec2::zero());
+
+        // Light flows both ways when the neighbour is lit later
+        chunks.extend(make_chunks(&[vek::Vec2::new(1, 0)]));
+        chunks
+            .get_mut(&vek::Vec2::new(1, 0))
+            .unwrap()
+            .set(0, 10, 0, 0);
+        engine.light_chunk(&mut chunks, vek::Vec2::new(1, 0));
+        assert_eq!(block(&chunks, 16, 5, 3), 13);
+        assert_eq!(sky(&chunks, 16, 5, 0), MAX_LIGHT);
+        assert_eq!(sky(&chunks, 17, 5, 0), MAX_LIGHT - 1);
+        assert_eq!(sky(&chunks, 15, 5, 0), MAX_LIGHT - 1);
+    }
+}
@@ -1,5 +1,9 @@
 mod chunk;
+mod light;
+mod lighting;
 pub use chunk::*;
+pub use light::*;
+pub use lighting::*;
 use racemus_binary::proto;
 
 #[derive(Debug, Clone, Copy)]
@@ -100,3 +104,81 @@ impl From<EntityId> for u32 {
         val.0
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hand {
+    Main,
+    Off,
+}
+
+impl From<proto::Hand> for Hand {
+    fn from(val: proto::Hand) -> Self {
+        match val {
+            proto::Hand::Main => Self::Main,
+            proto::Hand::Off => Self::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MainHand {
+    Left,
+    Right,
+}
+
+impl From<proto::MainHand> for MainHand {
+    fn from(val: proto::MainHand) -> Self {
+        match val {
+            proto::MainHand::Left => Self::Left,
+            proto::MainHand::Right => Self::Right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockFace {
+    Bottom,
+    Top,
+    North,
+    South,
+    West,
+    East,
+}
+
+impl From<proto::BlockFace> for BlockFace {
+    fn from(val: proto::BlockFace) -> Self {
+        match val {
+            proto::BlockFace::Bottom => Self::Bottom,
+            proto::BlockFace::Top => Self::Top,
+            proto::BlockFace::North => Self::North,
+            proto::BlockFace::South => Self::South,
+            proto::BlockFace::West => Self::West,
+            proto::BlockFace::East => Self::East,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiggingStatus {
+    Started,
+    Cancelled,
+    Finished,
+    DropItemStack,
+    DropItem,
+    ShootArrowOrFinishEating,
+    SwapItemInHand,
+}
+
+impl From<proto::DiggingStatus> for DiggingStatus {
+    fn from(val: proto::DiggingStatus) -> Self {
+        match val {
+            proto::DiggingStatus::Started => Self::Started,
+            proto::DiggingStatus::Cancelled => Self::Cancelled,
+            proto::DiggingStatus::Finished => Self::Finished,
+            proto::DiggingStatus::DropItemStack => Self::DropItemStack,
+            proto::DiggingStatus::DropItem => Self::DropItem,
+            proto::DiggingStatus::ShootArrowOrFinishEating => Self::ShootArrowOrFinishEating,
+            proto::DiggingStatus::SwapItemInHand => Self::SwapItemInHand,
+        }
+    }
+}